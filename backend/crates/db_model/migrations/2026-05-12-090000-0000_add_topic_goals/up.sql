@@ -0,0 +1,14 @@
+CREATE TABLE topic_goals (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    topic_id BIGINT NOT NULL,
+    comparison VARCHAR(16) NOT NULL COMMENT 'at_least (daily target) or at_most (daily budget)',
+    target_minutes INT NOT NULL COMMENT 'Minutes per day the goal compares against',
+    include_subtopics TINYINT(1) NOT NULL DEFAULT 0,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME ON UPDATE CURRENT_TIMESTAMP,
+    CONSTRAINT fk_topic_goals_user FOREIGN KEY (user_id) REFERENCES users(id),
+    CONSTRAINT fk_topic_goals_topic FOREIGN KEY (topic_id) REFERENCES topic(id),
+    UNIQUE KEY uq_topic_goals_topic_comparison (topic_id, comparison),
+    INDEX idx_topic_goals_user (user_id)
+);
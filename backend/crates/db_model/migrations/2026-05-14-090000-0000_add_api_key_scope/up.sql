@@ -0,0 +1,3 @@
+ALTER TABLE api_keys
+    ADD COLUMN scope VARCHAR(32) NOT NULL DEFAULT 'full' AFTER name
+    COMMENT 'full: general API access; calendar_feed: read-only calendar feed only';
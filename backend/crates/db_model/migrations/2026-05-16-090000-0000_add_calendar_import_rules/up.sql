@@ -0,0 +1,10 @@
+CREATE TABLE calendar_import_rules (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    keyword VARCHAR(255) NOT NULL COMMENT 'Case-insensitive substring matched against event summary/description',
+    topic_id BIGINT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_calendar_import_rules_user FOREIGN KEY (user_id) REFERENCES users(id),
+    CONSTRAINT fk_calendar_import_rules_topic FOREIGN KEY (topic_id) REFERENCES topic(id),
+    INDEX idx_calendar_import_rules_user (user_id)
+);
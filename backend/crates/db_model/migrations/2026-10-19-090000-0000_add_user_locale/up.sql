@@ -0,0 +1,3 @@
+ALTER TABLE users
+    ADD COLUMN locale VARCHAR(16) NOT NULL DEFAULT 'en' AFTER email_verified
+    COMMENT 'Language tag used for emails, e.g. en or de';
@@ -0,0 +1,16 @@
+CREATE TABLE email_outbox (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    template VARCHAR(64) NOT NULL COMMENT 'Email template name, e.g. verification',
+    locale VARCHAR(16) NOT NULL,
+    payload TEXT NOT NULL COMMENT 'JSON values the template is rendered with',
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' COMMENT 'pending, sent or failed',
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME NOT NULL COMMENT 'Earliest time a worker may pick the email up',
+    last_error TEXT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at DATETIME NULL,
+    CONSTRAINT fk_email_outbox_user FOREIGN KEY (user_id) REFERENCES users(id),
+    INDEX idx_email_outbox_due (status, next_attempt_at)
+);
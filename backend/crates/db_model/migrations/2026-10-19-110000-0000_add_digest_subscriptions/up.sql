@@ -0,0 +1,14 @@
+CREATE TABLE digest_subscriptions (
+    user_id BIGINT PRIMARY KEY,
+    enabled TINYINT(1) NOT NULL DEFAULT 1,
+    frequency VARCHAR(16) NOT NULL COMMENT 'daily or weekly',
+    send_time TIME NOT NULL COMMENT 'Local time of day the digest goes out',
+    time_zone VARCHAR(64) NOT NULL COMMENT 'IANA time zone, e.g. Europe/Berlin',
+    topic_ids TEXT NULL COMMENT 'JSON array of topic ids to include; NULL means every topic',
+    unsubscribe_token VARCHAR(64) NOT NULL,
+    last_period_start DATE NULL COMMENT 'First day of the last period a digest was queued for',
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME ON UPDATE CURRENT_TIMESTAMP,
+    CONSTRAINT fk_digest_subscriptions_user FOREIGN KEY (user_id) REFERENCES users(id),
+    UNIQUE KEY uq_digest_subscriptions_token (unsubscribe_token)
+);
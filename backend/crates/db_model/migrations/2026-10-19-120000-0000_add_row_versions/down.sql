@@ -0,0 +1,2 @@
+ALTER TABLE daily_track DROP COLUMN version;
+ALTER TABLE topic DROP COLUMN version;
@@ -0,0 +1,6 @@
+ALTER TABLE topic
+    ADD COLUMN version INT NOT NULL DEFAULT 1
+    COMMENT 'Bumped by every update; the ETag of the topic';
+ALTER TABLE daily_track
+    ADD COLUMN version INT NOT NULL DEFAULT 1
+    COMMENT 'Bumped by every update; the ETag of the track';
@@ -0,0 +1,12 @@
+CREATE TABLE idempotency_keys (
+    user_id BIGINT NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL COMMENT 'Idempotency-Key header sent by the client',
+    request_hash CHAR(64) NOT NULL COMMENT 'SHA-256 of method, path and body of the first request',
+    status_code INT NULL COMMENT 'NULL while the first request is still running',
+    content_type VARCHAR(255) NULL,
+    etag VARCHAR(128) NULL,
+    response_body MEDIUMTEXT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, idempotency_key),
+    CONSTRAINT fk_idempotency_keys_user FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE topic
+    DROP INDEX uq_topic_user_name,
+    ADD UNIQUE KEY topic_name (topic_name);
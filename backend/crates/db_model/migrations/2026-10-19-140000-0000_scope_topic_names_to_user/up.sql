@@ -0,0 +1,5 @@
+-- Topic names only need to be unique within one account, so a backup or CSV
+-- file can be restored into any account.
+ALTER TABLE topic
+    DROP INDEX topic_name,
+    ADD UNIQUE KEY uq_topic_user_name (user_id, topic_name);
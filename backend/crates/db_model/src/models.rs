@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use chrono::Utc;
 use diesel::prelude::*;
 
@@ -15,6 +15,8 @@ pub struct Topic {
     pub updated_at: Option<NaiveDateTime>,
     pub parent_topic_id: Option<i64>,
     pub user_id: Option<i64>,
+    /// Starts at 1 and is bumped by every update.
+    pub version: i32,
 }
 
 #[derive(Insertable)]
@@ -42,6 +44,8 @@ pub struct DailyTrack {
     pub comment: Option<String>,
     pub user_id: Option<i64>,
     pub duration_minutes: i32,
+    /// Starts at 1 and is bumped by every update.
+    pub version: i32,
 }
 
 #[derive(Insertable)]
@@ -131,6 +135,7 @@ mod tests {
             comment: comment.clone(),
             user_id: None,
             duration_minutes: 30,
+            version: 1,
         };
 
         let rendered = track.to_string();
@@ -153,6 +158,7 @@ mod tests {
             comment: None,
             user_id: None,
             duration_minutes: 30,
+            version: 1,
         };
         let rendered = track.to_string();
         assert!(rendered.contains("comment: "));
@@ -198,6 +204,7 @@ pub struct ApiKey {
     pub key_hash: String,
     pub key_prefix: String,
     pub name: String,
+    pub scope: String,
     pub created_at: NaiveDateTime,
     pub last_used_at: Option<NaiveDateTime>,
     pub revoked_at: Option<NaiveDateTime>,
@@ -212,6 +219,7 @@ pub struct NewApiKey {
     pub key_hash: String,
     pub key_prefix: String,
     pub name: String,
+    pub scope: String,
     pub created_at: NaiveDateTime,
     pub last_used_at: Option<NaiveDateTime>,
     pub revoked_at: Option<NaiveDateTime>,
@@ -226,6 +234,7 @@ pub struct User {
     pub email: String,
     pub password_hash: String,
     pub email_verified: bool,
+    pub locale: String,
     pub verification_code: Option<String>,
     pub verification_code_expires_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
@@ -241,8 +250,148 @@ pub struct NewUser {
     pub email: String,
     pub password_hash: String,
     pub email_verified: bool,
+    pub locale: String,
     pub verification_code: Option<String>,
     pub verification_code_expires_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
 }
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::topic_goals)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct TopicGoal {
+    pub id: i64,
+    pub user_id: i64,
+    pub topic_id: i64,
+    pub comparison: String,
+    pub target_minutes: i32,
+    pub include_subtopics: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::topic_goals)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct NewTopicGoal {
+    pub id: i64,
+    pub user_id: i64,
+    pub topic_id: i64,
+    pub comparison: String,
+    pub target_minutes: i32,
+    pub include_subtopics: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::calendar_import_rules)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct CalendarImportRule {
+    pub id: i64,
+    pub user_id: i64,
+    pub keyword: String,
+    pub topic_id: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::calendar_import_rules)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct NewCalendarImportRule {
+    pub id: i64,
+    pub user_id: i64,
+    pub keyword: String,
+    pub topic_id: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Clone, Debug)]
+#[diesel(table_name = crate::schema::email_outbox)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct EmailOutbox {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub recipient: String,
+    pub template: String,
+    pub locale: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::email_outbox)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct NewEmailOutbox {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub recipient: String,
+    pub template: String,
+    pub locale: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Clone, Debug)]
+#[diesel(table_name = crate::schema::digest_subscriptions)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct DigestSubscription {
+    pub user_id: i64,
+    pub enabled: bool,
+    pub frequency: String,
+    pub send_time: NaiveTime,
+    pub time_zone: String,
+    pub topic_ids: Option<String>,
+    pub unsubscribe_token: String,
+    pub last_period_start: Option<NaiveDate>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::digest_subscriptions)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct NewDigestSubscription {
+    pub user_id: i64,
+    pub enabled: bool,
+    pub frequency: String,
+    pub send_time: NaiveTime,
+    pub time_zone: String,
+    pub topic_ids: Option<String>,
+    pub unsubscribe_token: String,
+    pub last_period_start: Option<NaiveDate>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Clone, Debug)]
+#[diesel(table_name = crate::schema::idempotency_keys)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct IdempotencyKey {
+    pub user_id: i64,
+    pub idempotency_key: String,
+    pub request_hash: String,
+    pub status_code: Option<i32>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub response_body: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::idempotency_keys)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct NewIdempotencyKey {
+    pub user_id: i64,
+    pub idempotency_key: String,
+    pub request_hash: String,
+    pub created_at: NaiveDateTime,
+}
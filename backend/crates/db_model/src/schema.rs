@@ -10,12 +10,25 @@ diesel::table! {
         key_prefix -> Varchar,
         #[max_length = 255]
         name -> Varchar,
+        #[max_length = 32]
+        scope -> Varchar,
         created_at -> Datetime,
         last_used_at -> Nullable<Datetime>,
         revoked_at -> Nullable<Datetime>,
     }
 }
 
+diesel::table! {
+    calendar_import_rules (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        #[max_length = 255]
+        keyword -> Varchar,
+        topic_id -> Bigint,
+        created_at -> Datetime,
+    }
+}
+
 diesel::table! {
     daily_track (id) {
         id -> Bigint,
@@ -26,6 +39,63 @@ diesel::table! {
         comment -> Nullable<Text>,
         user_id -> Nullable<Bigint>,
         duration_minutes -> Integer,
+        version -> Integer,
+    }
+}
+
+diesel::table! {
+    digest_subscriptions (user_id) {
+        user_id -> Bigint,
+        enabled -> Bool,
+        #[max_length = 16]
+        frequency -> Varchar,
+        send_time -> Time,
+        #[max_length = 64]
+        time_zone -> Varchar,
+        topic_ids -> Nullable<Text>,
+        #[max_length = 64]
+        unsubscribe_token -> Varchar,
+        last_period_start -> Nullable<Date>,
+        created_at -> Datetime,
+        updated_at -> Nullable<Datetime>,
+    }
+}
+
+diesel::table! {
+    email_outbox (id) {
+        id -> Bigint,
+        user_id -> Nullable<Bigint>,
+        #[max_length = 255]
+        recipient -> Varchar,
+        #[max_length = 64]
+        template -> Varchar,
+        #[max_length = 16]
+        locale -> Varchar,
+        payload -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        attempts -> Integer,
+        next_attempt_at -> Datetime,
+        last_error -> Nullable<Text>,
+        created_at -> Datetime,
+        sent_at -> Nullable<Datetime>,
+    }
+}
+
+diesel::table! {
+    idempotency_keys (user_id, idempotency_key) {
+        user_id -> Bigint,
+        #[max_length = 255]
+        idempotency_key -> Varchar,
+        #[max_length = 64]
+        request_hash -> Char,
+        status_code -> Nullable<Integer>,
+        #[max_length = 255]
+        content_type -> Nullable<Varchar>,
+        #[max_length = 128]
+        etag -> Nullable<Varchar>,
+        response_body -> Nullable<Mediumtext>,
+        created_at -> Datetime,
     }
 }
 
@@ -40,6 +110,21 @@ diesel::table! {
         #[max_length = 7]
         display_color -> Varchar,
         user_id -> Nullable<Bigint>,
+        version -> Integer,
+    }
+}
+
+diesel::table! {
+    topic_goals (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        topic_id -> Bigint,
+        #[max_length = 16]
+        comparison -> Varchar,
+        target_minutes -> Integer,
+        include_subtopics -> Bool,
+        created_at -> Datetime,
+        updated_at -> Nullable<Datetime>,
     }
 }
 
@@ -53,6 +138,8 @@ diesel::table! {
         #[max_length = 255]
         password_hash -> Varchar,
         email_verified -> Bool,
+        #[max_length = 16]
+        locale -> Varchar,
         verification_code -> Nullable<Text>,
         verification_code_expires_at -> Nullable<Datetime>,
         created_at -> Datetime,
@@ -61,8 +148,25 @@ diesel::table! {
 }
 
 diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(calendar_import_rules -> topic (topic_id));
+diesel::joinable!(calendar_import_rules -> users (user_id));
 diesel::joinable!(daily_track -> topic (topic_id));
 diesel::joinable!(daily_track -> users (user_id));
+diesel::joinable!(digest_subscriptions -> users (user_id));
+diesel::joinable!(email_outbox -> users (user_id));
+diesel::joinable!(idempotency_keys -> users (user_id));
 diesel::joinable!(topic -> users (user_id));
+diesel::joinable!(topic_goals -> topic (topic_id));
+diesel::joinable!(topic_goals -> users (user_id));
 
-diesel::allow_tables_to_appear_in_same_query!(api_keys, daily_track, topic, users,);
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    calendar_import_rules,
+    daily_track,
+    digest_subscriptions,
+    email_outbox,
+    idempotency_keys,
+    topic,
+    topic_goals,
+    users,
+);
@@ -3,23 +3,134 @@ use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
 use std::os::unix::fs::symlink;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
+
+mod retention;
+pub use retention::Retention;
+
+/// Fields of the request being handled. Every line logged while handling it
+/// carries them; `user_id` and `route` are filled in once known. How the
+/// caller authenticated is kept for the access log.
+pub struct RequestContext {
+    pub log_id: String,
+    /// OpenTelemetry trace id, when tracing is on.
+    pub trace_id: Option<String>,
+    user_id: OnceLock<i64>,
+    route: OnceLock<String>,
+    auth: OnceLock<(&'static str, Option<String>)>,
+}
+
+impl RequestContext {
+    pub fn new(log_id: String) -> Self {
+        RequestContext {
+            log_id,
+            trace_id: None,
+            user_id: OnceLock::new(),
+            route: OnceLock::new(),
+            auth: OnceLock::new(),
+        }
+    }
+
+    pub fn with_trace_id(mut self, trace_id: Option<String>) -> Self {
+        self.trace_id = trace_id;
+        self
+    }
+
+    pub fn user_id(&self) -> Option<i64> {
+        self.user_id.get().copied()
+    }
+
+    pub fn route(&self) -> Option<&str> {
+        self.route.get().map(String::as_str)
+    }
+
+    /// `jwt` or `api_key`, once the caller authenticated.
+    pub fn auth_method(&self) -> Option<&'static str> {
+        self.auth.get().map(|(method, _)| *method)
+    }
+
+    /// Display prefix of the API key the caller used, e.g. `dt_a1b2c3d`.
+    pub fn api_key_prefix(&self) -> Option<&str> {
+        self.auth.get().and_then(|(_, prefix)| prefix.as_deref())
+    }
+}
 
 tokio::task_local! {
-    pub static LOG_ID: String;
+    pub static REQUEST_CONTEXT: RequestContext;
 }
 
 pub fn current_log_id() -> String {
-    LOG_ID
-        .try_with(|id| id.clone())
+    REQUEST_CONTEXT
+        .try_with(|ctx| ctx.log_id.clone())
         .unwrap_or_else(|_| "-".to_string())
 }
 
-const DEFAULT_LOG_DIR: &str = "/var/log/daily-tracker";
+/// Records the authenticated user for the rest of the request. Does nothing
+/// outside a request.
+pub fn set_user_id(user_id: i64) {
+    let _ = REQUEST_CONTEXT.try_with(|ctx| ctx.user_id.set(user_id));
+}
+
+/// Records how the caller authenticated, with the display prefix of the API
+/// key if one was used.
+pub fn set_auth(method: &'static str, api_key_prefix: Option<String>) {
+    let _ = REQUEST_CONTEXT.try_with(|ctx| ctx.auth.set((method, api_key_prefix)));
+}
+
+/// Records the matched route template, e.g. `/api/v1/topics/:id`.
+pub fn set_route(route: &str) {
+    let _ = REQUEST_CONTEXT.try_with(|ctx| ctx.route.set(route.to_string()));
+}
+
+/// The request fields as of now, or only `-` as `log_id` outside a request.
+struct LineContext {
+    log_id: String,
+    trace_id: Option<String>,
+    user_id: Option<i64>,
+    route: Option<String>,
+}
+
+fn line_context() -> LineContext {
+    REQUEST_CONTEXT
+        .try_with(|ctx| LineContext {
+            log_id: ctx.log_id.clone(),
+            trace_id: ctx.trace_id.clone(),
+            user_id: ctx.user_id(),
+            route: ctx.route().map(str::to_string),
+        })
+        .unwrap_or_else(|_| LineContext {
+            log_id: "-".to_string(),
+            trace_id: None,
+            user_id: None,
+            route: None,
+        })
+}
+
+/// Log directory used unless the configuration names another.
+pub const DEFAULT_LOG_DIR: &str = "/var/log/daily-tracker";
 const FILE_PREFIX: &str = "backend";
 const FILE_SUFFIX: &str = "log";
 const SYMLINK_NAME: &str = "current.log";
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `[ts level target] [log_id=...] message key=value`, for people.
+    #[default]
+    Text,
+    /// One JSON object per line, for log shippers.
+    Json,
+}
+
+/// Where and how to log. Lines always go to stderr.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogOptions {
+    pub format: LogFormat,
+    /// Directory for daily files; `None` logs to stderr only, e.g. when
+    /// journald collects it.
+    pub dir: Option<PathBuf>,
+    pub retention: Retention,
+}
+
 fn dated_filename(date: NaiveDate) -> String {
     format!("{}-{}.{}", FILE_PREFIX, date.format("%Y-%m-%d"), FILE_SUFFIX)
 }
@@ -44,6 +155,7 @@ fn update_symlink(dir: &Path, date: NaiveDate) -> io::Result<()> {
 
 struct DailyRollingFile {
     dir: PathBuf,
+    retention: Retention,
     state: Mutex<RollState>,
 }
 
@@ -53,13 +165,15 @@ struct RollState {
 }
 
 impl DailyRollingFile {
-    fn new(dir: PathBuf) -> io::Result<Self> {
+    fn new(dir: PathBuf, retention: Retention) -> io::Result<Self> {
         fs::create_dir_all(&dir)?;
         let today = Utc::now().date_naive();
         let file = open_dated(&dir, today)?;
         update_symlink(&dir, today)?;
+        retention.spawn_sweep(dir.clone(), today);
         Ok(Self {
             dir,
+            retention,
             state: Mutex::new(RollState { date: today, file }),
         })
     }
@@ -73,6 +187,7 @@ impl Write for DailyRollingFile {
             state.file = open_dated(&self.dir, today)?;
             update_symlink(&self.dir, today)?;
             state.date = today;
+            self.retention.spawn_sweep(self.dir.clone(), today);
         }
         state.file.write(buf)
     }
@@ -84,41 +199,121 @@ impl Write for DailyRollingFile {
 
 struct TeeWriter {
     stderr: io::Stderr,
-    file: DailyRollingFile,
+    file: Option<DailyRollingFile>,
 }
 
 impl Write for TeeWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.stderr.write_all(buf)?;
-        self.file.write_all(buf)?;
+        if let Some(file) = &mut self.file {
+            file.write_all(buf)?;
+        }
         Ok(buf.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
         self.stderr.flush()?;
-        self.file.flush()
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
     }
 }
 
-pub fn init_logging() {
-    let log_dir = std::env::var("DAILY_TRACKER_LOG_DIR")
-        .unwrap_or_else(|_| DEFAULT_LOG_DIR.to_string());
+/// Collects the key-value fields of a record, e.g. `status = 200`.
+struct Fields(Vec<(String, serde_json::Value)>);
 
-    let file = DailyRollingFile::new(PathBuf::from(&log_dir))
-        .expect("Failed to initialize log directory");
+impl<'kvs> log::kv::VisitSource<'kvs> for Fields {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let value = if let Some(v) = value.to_bool() {
+            v.into()
+        } else if let Some(v) = value.to_u64() {
+            v.into()
+        } else if let Some(v) = value.to_i64() {
+            v.into()
+        } else if let Some(v) = value.to_f64() {
+            v.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
 
+fn record_fields(record: &log::Record) -> Vec<(String, serde_json::Value)> {
+    let mut fields = Fields(Vec::new());
+    let _ = record.key_values().visit(&mut fields);
+    fields.0
+}
+
+fn text_line(ts: &str, record: &log::Record, ctx: &LineContext) -> String {
+    let mut line = format!(
+        "[{} {} {}] [log_id={}",
+        ts,
+        record.level(),
+        record.target(),
+        ctx.log_id
+    );
+    // Usually the log id is the trace id; only a caller-supplied
+    // x-request-id makes them differ.
+    if let Some(trace_id) = ctx.trace_id.as_ref().filter(|t| **t != ctx.log_id) {
+        line.push_str(&format!(" trace_id={}", trace_id));
+    }
+    if let Some(user_id) = ctx.user_id {
+        line.push_str(&format!(" user_id={}", user_id));
+    }
+    line.push_str(&format!("] {}", record.args()));
+    for (key, value) in record_fields(record) {
+        match value {
+            serde_json::Value::String(s) => line.push_str(&format!(" {}={}", key, s)),
+            other => line.push_str(&format!(" {}={}", key, other)),
+        }
+    }
+    line
+}
+
+fn json_line(ts: &str, record: &log::Record, ctx: &LineContext) -> String {
+    let mut object = serde_json::Map::new();
+    object.insert("ts".to_string(), ts.into());
+    object.insert("level".to_string(), record.level().as_str().into());
+    object.insert("target".to_string(), record.target().into());
+    object.insert("log_id".to_string(), ctx.log_id.clone().into());
+    if let Some(trace_id) = &ctx.trace_id {
+        object.insert("trace_id".to_string(), trace_id.clone().into());
+    }
+    if let Some(user_id) = ctx.user_id {
+        object.insert("user_id".to_string(), user_id.into());
+    }
+    if let Some(route) = &ctx.route {
+        object.insert("route".to_string(), route.clone().into());
+    }
+    object.insert("msg".to_string(), record.args().to_string().into());
+    for (key, value) in record_fields(record) {
+        object.entry(key).or_insert(value);
+    }
+    serde_json::Value::Object(object).to_string()
+}
+
+pub fn init_logging(options: &LogOptions) {
+    let file = options.dir.as_ref().map(|dir| {
+        DailyRollingFile::new(dir.clone(), options.retention.clone())
+            .expect("Failed to initialize log directory")
+    });
+
+    let format = options.format;
     env_logger::Builder::from_default_env()
-        .format(|buf, record| {
-            let ts = buf.timestamp();
-            writeln!(
-                buf,
-                "[{} {} {}] [log_id={}] {}",
-                ts,
-                record.level(),
-                record.target(),
-                current_log_id(),
-                record.args()
-            )
+        .format(move |buf, record| {
+            let ctx = line_context();
+            let line = match format {
+                LogFormat::Text => text_line(&buf.timestamp().to_string(), record, &ctx),
+                LogFormat::Json => json_line(&buf.timestamp_millis().to_string(), record, &ctx),
+            };
+            writeln!(buf, "{}", line)
         })
         .target(env_logger::Target::Pipe(Box::new(TeeWriter {
             stderr: io::stderr(),
@@ -126,3 +321,106 @@ pub fn init_logging() {
         })))
         .init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(user_id: Option<i64>, route: Option<&str>) -> LineContext {
+        LineContext {
+            log_id: "abc123".to_string(),
+            trace_id: None,
+            user_id,
+            route: route.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn json_line_carries_request_fields() {
+        let line = json_line(
+            "2026-10-19T08:00:00.000Z",
+            &log::Record::builder()
+                .args(format_args!("<-- GET /api/v1/topics/1"))
+                .level(log::Level::Info)
+                .target("backend::request_logger")
+                .key_values(&[("status", 200u64), ("latency_ms", 12u64)])
+                .build(),
+            &ctx(Some(7), Some("/api/v1/topics/:id")),
+        );
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "ts": "2026-10-19T08:00:00.000Z",
+                "level": "INFO",
+                "target": "backend::request_logger",
+                "log_id": "abc123",
+                "user_id": 7,
+                "route": "/api/v1/topics/:id",
+                "msg": "<-- GET /api/v1/topics/1",
+                "status": 200,
+                "latency_ms": 12,
+            })
+        );
+    }
+
+    #[test]
+    fn json_line_omits_unknown_request_fields() {
+        let line = json_line(
+            "ts",
+            &log::Record::builder()
+                .args(format_args!("started"))
+                .level(log::Level::Warn)
+                .target("backend")
+                .build(),
+            &ctx(None, None),
+        );
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert!(value.get("user_id").is_none());
+        assert!(value.get("route").is_none());
+        assert!(value.get("trace_id").is_none());
+        assert_eq!(value["level"], "WARN");
+    }
+
+    #[test]
+    fn trace_id_is_shown_when_it_differs_from_log_id() {
+        let record = log::Record::builder()
+            .args(format_args!("--> GET /api/v1/topics"))
+            .level(log::Level::Info)
+            .target("backend")
+            .build();
+        let mut context = ctx(None, None);
+        context.trace_id = Some("abc123".to_string());
+        assert_eq!(
+            text_line("ts", &record, &context),
+            "[ts INFO backend] [log_id=abc123] --> GET /api/v1/topics"
+        );
+        let value: serde_json::Value =
+            serde_json::from_str(&json_line("ts", &record, &context)).unwrap();
+        assert_eq!(value["trace_id"], "abc123");
+
+        context.trace_id = Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string());
+        assert_eq!(
+            text_line("ts", &record, &context),
+            "[ts INFO backend] [log_id=abc123 trace_id=4bf92f3577b34da6a3ce929d0e0e4736] --> GET /api/v1/topics"
+        );
+    }
+
+    #[test]
+    fn text_line_appends_fields() {
+        let line = text_line(
+            "2026-10-19T08:00:00Z",
+            &log::Record::builder()
+                .args(format_args!("<-- GET /api/v1/topics"))
+                .level(log::Level::Info)
+                .target("backend")
+                .key_values(&[("status", "200")])
+                .build(),
+            &ctx(Some(7), Some("/api/v1/topics")),
+        );
+        assert_eq!(
+            line,
+            "[2026-10-19T08:00:00Z INFO backend] [log_id=abc123 user_id=7] <-- GET /api/v1/topics status=200"
+        );
+    }
+}
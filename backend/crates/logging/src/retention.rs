@@ -0,0 +1,262 @@
+use chrono::NaiveDate;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{FILE_PREFIX, FILE_SUFFIX, dated_filename};
+
+const GZIP_SUFFIX: &str = "gz";
+
+/// What happens to daily files once their day is over. Today's file is never
+/// touched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Retention {
+    /// Delete files whose day lies more than this many days back.
+    pub max_age_days: Option<u32>,
+    /// Delete the oldest files while all of them, today's included, take up
+    /// more than this.
+    pub max_total_bytes: Option<u64>,
+    /// Gzip files once their day is over.
+    pub compress: bool,
+}
+
+/// A daily file from an earlier day.
+struct RotatedFile {
+    date: NaiveDate,
+    path: PathBuf,
+    size: u64,
+    compressed: bool,
+}
+
+impl Retention {
+    fn is_noop(&self) -> bool {
+        self.max_age_days.is_none() && self.max_total_bytes.is_none() && !self.compress
+    }
+
+    /// Sweeps `dir` on a background thread, so compressing a large file never
+    /// holds up logging.
+    pub(crate) fn spawn_sweep(&self, dir: PathBuf, today: NaiveDate) {
+        if self.is_noop() {
+            return;
+        }
+        let retention = self.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = retention.sweep(&dir, today) {
+                eprintln!("log retention in {} failed: {}", dir.display(), e);
+            }
+        });
+    }
+
+    pub(crate) fn sweep(&self, dir: &Path, today: NaiveDate) -> io::Result<()> {
+        let mut files = rotated_files(dir, today)?;
+        if self.compress {
+            for file in files.iter_mut().filter(|f| !f.compressed) {
+                compress(file)?;
+            }
+        }
+
+        if let Some(days) = self.max_age_days {
+            let cutoff = today - chrono::Duration::days(i64::from(days));
+            for file in files.iter().filter(|f| f.date < cutoff) {
+                fs::remove_file(&file.path)?;
+            }
+            files.retain(|f| f.date >= cutoff);
+        }
+
+        if let Some(max_total_bytes) = self.max_total_bytes {
+            let active = fs::metadata(dir.join(dated_filename(today))).map_or(0, |m| m.len());
+            let mut total = active + files.iter().map(|f| f.size).sum::<u64>();
+            files.sort_by_key(|f| f.date);
+            for file in &files {
+                if total <= max_total_bytes {
+                    break;
+                }
+                fs::remove_file(&file.path)?;
+                total -= file.size;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses `backend-YYYY-MM-DD.log` and `backend-YYYY-MM-DD.log.gz`.
+fn parse_filename(name: &str) -> Option<(NaiveDate, bool)> {
+    let rest = name.strip_prefix(FILE_PREFIX)?.strip_prefix('-')?;
+    let (date, suffix) = rest.split_once('.')?;
+    let compressed = if suffix == FILE_SUFFIX {
+        false
+    } else if suffix == format!("{}.{}", FILE_SUFFIX, GZIP_SUFFIX) {
+        true
+    } else {
+        return None;
+    };
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some((date, compressed))
+}
+
+fn rotated_files(dir: &Path, today: NaiveDate) -> io::Result<Vec<RotatedFile>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some((date, compressed)) = entry.file_name().to_str().and_then(parse_filename) else {
+            continue;
+        };
+        if date >= today || !entry.file_type()?.is_file() {
+            continue;
+        }
+        files.push(RotatedFile {
+            date,
+            path: entry.path(),
+            size: entry.metadata()?.len(),
+            compressed,
+        });
+    }
+    Ok(files)
+}
+
+/// Replaces `file` with a gzipped copy. The copy is written under a temporary
+/// name first, so an interrupted sweep never leaves a truncated archive.
+fn compress(file: &mut RotatedFile) -> io::Result<()> {
+    let mut target = file.path.clone().into_os_string();
+    target.push(format!(".{}", GZIP_SUFFIX));
+    let target = PathBuf::from(target);
+    let tmp = target.with_extension(format!("{}.tmp", GZIP_SUFFIX));
+
+    let mut encoder = GzEncoder::new(File::create(&tmp)?, Compression::default());
+    io::copy(&mut File::open(&file.path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::rename(&tmp, &target)?;
+    fs::remove_file(&file.path)?;
+
+    file.size = fs::metadata(&target)?.len();
+    file.path = target;
+    file.compressed = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "daily-tracker-logs-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    fn write_day(dir: &Path, date: NaiveDate, bytes: usize) {
+        fs::write(dir.join(dated_filename(date)), "x".repeat(bytes)).unwrap();
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn parses_plain_and_compressed_names() {
+        assert_eq!(
+            parse_filename("backend-2026-10-19.log"),
+            Some((day(19), false))
+        );
+        assert_eq!(
+            parse_filename("backend-2026-10-19.log.gz"),
+            Some((day(19), true))
+        );
+        assert_eq!(parse_filename("backend-2026-10-19.log.gz.tmp"), None);
+        assert_eq!(parse_filename("current.log"), None);
+    }
+
+    #[test]
+    fn deletes_files_older_than_max_age() {
+        let dir = temp_dir("age");
+        for d in [10, 16, 17, 19] {
+            write_day(&dir, day(d), 10);
+        }
+        fs::write(dir.join("notes.txt"), "keep").unwrap();
+        let retention = Retention {
+            max_age_days: Some(2),
+            ..Retention::default()
+        };
+        retention.sweep(&dir, day(19)).unwrap();
+        assert_eq!(
+            names(&dir),
+            [
+                "backend-2026-10-17.log",
+                "backend-2026-10-19.log",
+                "notes.txt"
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deletes_oldest_files_over_max_total_size_but_never_today() {
+        let dir = temp_dir("size");
+        for d in [16, 17, 18] {
+            write_day(&dir, day(d), 100);
+        }
+        write_day(&dir, day(19), 500);
+        let retention = Retention {
+            max_total_bytes: Some(650),
+            ..Retention::default()
+        };
+        retention.sweep(&dir, day(19)).unwrap();
+        assert_eq!(
+            names(&dir),
+            ["backend-2026-10-18.log", "backend-2026-10-19.log"]
+        );
+
+        let retention = Retention {
+            max_total_bytes: Some(10),
+            ..Retention::default()
+        };
+        retention.sweep(&dir, day(19)).unwrap();
+        assert_eq!(names(&dir), ["backend-2026-10-19.log"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compresses_rotated_files() {
+        let dir = temp_dir("gzip");
+        fs::write(dir.join(dated_filename(day(18))), "yesterday\n").unwrap();
+        write_day(&dir, day(19), 10);
+        let retention = Retention {
+            compress: true,
+            ..Retention::default()
+        };
+        retention.sweep(&dir, day(19)).unwrap();
+        assert_eq!(
+            names(&dir),
+            ["backend-2026-10-18.log.gz", "backend-2026-10-19.log"]
+        );
+
+        let mut text = String::new();
+        GzDecoder::new(File::open(dir.join("backend-2026-10-18.log.gz")).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "yesterday\n");
+
+        // A second sweep leaves the archive alone.
+        retention.sweep(&dir, day(19)).unwrap();
+        assert_eq!(names(&dir).len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
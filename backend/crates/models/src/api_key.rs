@@ -1,31 +1,65 @@
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Clone)]
+/// What an API key may be used for. `calendar_feed` keys are meant to be
+/// embedded in calendar subscription URLs and only unlock the read-only feed.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    #[default]
+    Full,
+    CalendarFeed,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Full => "full",
+            ApiKeyScope::CalendarFeed => "calendar_feed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "full" => Some(ApiKeyScope::Full),
+            "calendar_feed" => Some(ApiKeyScope::CalendarFeed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct CreateApiKeyRequest {
     pub name: String,
+    #[serde(default)]
+    pub scope: ApiKeyScope,
 }
 
 /// Returned only at creation time — the plaintext `token` is never readable
 /// again, so the caller must store it.
 #[serde_as]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct CreateApiKeyResponse {
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub id: i64,
     pub name: String,
     pub key_prefix: String,
+    pub scope: ApiKeyScope,
     pub token: String,
     pub created_at: String,
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct ApiKeyResponse {
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub id: i64,
     pub name: String,
     pub key_prefix: String,
+    pub scope: ApiKeyScope,
     pub created_at: String,
     pub last_used_at: Option<String>,
 }
@@ -39,6 +73,22 @@ mod tests {
         let json = r#"{"name":"ci-bot"}"#;
         let req: CreateApiKeyRequest = serde_json::from_str(json).unwrap();
         assert_eq!(req.name, "ci-bot");
+        assert_eq!(req.scope, ApiKeyScope::Full);
+    }
+
+    #[test]
+    fn create_api_key_request_accepts_calendar_feed_scope() {
+        let json = r#"{"name":"phone calendar","scope":"calendar_feed"}"#;
+        let req: CreateApiKeyRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.scope, ApiKeyScope::CalendarFeed);
+    }
+
+    #[test]
+    fn api_key_scope_parse_matches_as_str() {
+        for scope in [ApiKeyScope::Full, ApiKeyScope::CalendarFeed] {
+            assert_eq!(ApiKeyScope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(ApiKeyScope::parse("admin"), None);
     }
 
     #[test]
@@ -54,6 +104,7 @@ mod tests {
             id: 7,
             name: "ci-bot".to_string(),
             key_prefix: "dt_a1b2c3d4".to_string(),
+            scope: ApiKeyScope::Full,
             token: "dt_a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6".to_string(),
             created_at: "2026-04-25T10:00:00+00:00".to_string(),
         };
@@ -69,6 +120,7 @@ mod tests {
             id: 1,
             name: "k".to_string(),
             key_prefix: "dt_xxxx".to_string(),
+            scope: ApiKeyScope::Full,
             created_at: "2026-04-25T10:00:00+00:00".to_string(),
             last_used_at: Some("2026-04-25T11:00:00+00:00".to_string()),
         };
@@ -82,6 +134,7 @@ mod tests {
             id: 1,
             name: "k".to_string(),
             key_prefix: "dt_xxxx".to_string(),
+            scope: ApiKeyScope::Full,
             created_at: "2026-04-25T10:00:00+00:00".to_string(),
             last_used_at: None,
         };
@@ -95,6 +148,7 @@ mod tests {
             id: 42,
             name: "rt".to_string(),
             key_prefix: "dt_pfx".to_string(),
+            scope: ApiKeyScope::Full,
             created_at: "2026-04-25T10:00:00+00:00".to_string(),
             last_used_at: None,
         };
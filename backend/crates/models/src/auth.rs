@@ -1,27 +1,32 @@
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct RegisterRequest {
     pub username: String,
     pub email: String,
     pub password: String,
+    /// Language for emails, e.g. `de`. Falls back to `Accept-Language`.
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct TokenResponse {
     pub token: String,
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct UserResponse {
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub id: i64,
     pub username: String,
@@ -29,13 +34,13 @@ pub struct UserResponse {
     pub email_verified: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct VerifyEmailRequest {
     pub email: String,
     pub code: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct VerifyEmailResponse {
     pub message: String,
 }
@@ -124,12 +129,20 @@ mod tests {
         assert!(json.contains("\"message\":\"ok\""));
     }
 
+    #[test]
+    fn register_request_locale_is_optional() {
+        let req: RegisterRequest =
+            serde_json::from_str(r#"{"username":"a","email":"a@b.com","password":"p"}"#).unwrap();
+        assert_eq!(req.locale, None);
+    }
+
     #[test]
     fn register_request_clone() {
         let req = RegisterRequest {
             username: "alice".to_string(),
             email: "a@b.com".to_string(),
             password: "pass".to_string(),
+            locale: None,
         };
         let cloned = req.clone();
         assert_eq!(cloned.username, "alice");
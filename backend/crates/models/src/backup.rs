@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+
+use crate::api_key::ApiKeyResponse;
+use crate::goal::GoalComparison;
+use utoipa::ToSchema;
+
+/// Value of `format` in every backup document.
+pub const BACKUP_FORMAT: &str = "daily-tracker-backup";
+/// Current backup schema version. Bump it when the document shape changes in
+/// a way older restores cannot read.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Everything a user owns, with ids as they were in the source account.
+/// References between entries (`parent_topic_id`, `topic_id`) use those ids;
+/// a restore remaps them to fresh ones.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AccountBackup {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    pub topics: Vec<BackupTopic>,
+    pub daily_tracks: Vec<BackupDailyTrack>,
+    #[serde(default)]
+    pub goals: Vec<BackupGoal>,
+    #[serde(default)]
+    pub calendar_import_rules: Vec<BackupImportRule>,
+    /// Metadata only. Key hashes are never exported, so keys cannot be
+    /// restored and are listed for reference.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyResponse>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BackupTopic {
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub id: i64,
+    pub topic_name: String,
+    pub display_color: String,
+    #[schema(value_type = Option<String>)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub parent_topic_id: Option<i64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BackupDailyTrack {
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub id: i64,
+    pub start_time: chrono::DateTime<chrono::Utc>,
+    pub duration_minutes: i32,
+    #[schema(value_type = Option<String>)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub topic_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BackupGoal {
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub topic_id: i64,
+    pub comparison: GoalComparison,
+    pub target_minutes: i32,
+    pub include_subtopics: bool,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BackupImportRule {
+    pub keyword: String,
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub topic_id: i64,
+}
+
+/// What a restore did. `*_existing` counts entries that were already present,
+/// which is everything when the same backup is restored twice.
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, PartialEq)]
+pub struct RestoreBackupResponse {
+    pub topics_created: u32,
+    pub topics_existing: u32,
+    pub daily_tracks_created: u32,
+    pub daily_tracks_existing: u32,
+    pub goals_created: u32,
+    pub goals_existing: u32,
+    pub import_rules_created: u32,
+    pub import_rules_existing: u32,
+    /// API keys in the backup; they are never restored.
+    pub api_keys_skipped: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_backup_optional_sections_default_to_empty() {
+        let json = r##"{
+            "format": "daily-tracker-backup",
+            "version": 1,
+            "exported_at": "2026-05-20T08:00:00Z",
+            "topics": [{"id":"1","topic_name":"Work","display_color":"#3b82f6","created_at":"2026-01-01T00:00:00Z"}],
+            "daily_tracks": []
+        }"##;
+        let backup: AccountBackup = serde_json::from_str(json).unwrap();
+        assert_eq!(backup.version, BACKUP_VERSION);
+        assert_eq!(backup.topics[0].parent_topic_id, None);
+        assert!(backup.goals.is_empty());
+        assert!(backup.calendar_import_rules.is_empty());
+        assert!(backup.api_keys.is_empty());
+    }
+
+    #[test]
+    fn backup_ids_serialize_as_strings() {
+        let track = BackupDailyTrack {
+            id: 311777577381486600,
+            start_time: "2026-05-12T09:00:00Z".parse().unwrap(),
+            duration_minutes: 30,
+            topic_id: Some(311777577381486601),
+            comment: None,
+        };
+        let json = serde_json::to_string(&track).unwrap();
+        assert!(json.contains("\"id\":\"311777577381486600\""));
+        assert!(json.contains("\"topic_id\":\"311777577381486601\""));
+        assert!(!json.contains("comment"));
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CalendarExportParams {
+    /// First day to include (YYYY-MM-DD), inclusive.
+    pub start_date: Option<String>,
+    /// Last day to include (YYYY-MM-DD), inclusive.
+    pub end_date: Option<String>,
+}
+
+/// Maps calendar events whose summary (or, failing that, description)
+/// contains `keyword`, case-insensitively, to `topic_id`.
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportRule {
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub id: i64,
+    pub keyword: String,
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub topic_id: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateImportRuleRequest {
+    pub keyword: String,
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub topic_id: i64,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportCalendarRequest {
+    /// Raw iCalendar (.ics) document.
+    pub ics: String,
+    /// Topic for events that match no import rule. Without it such events
+    /// are skipped.
+    #[schema(value_type = Option<String>)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub default_topic_id: Option<i64>,
+    /// Report what would be imported without creating any tracks.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportEventStatus {
+    Created,
+    WouldCreate,
+    Conflict,
+    Skipped,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportedEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    pub status: ImportEventStatus,
+    /// Slot-aligned start time the event was snapped to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_minutes: Option<i32>,
+    #[schema(value_type = Option<String>)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_id: Option<i64>,
+    /// Id of the created track; absent on dry runs.
+    #[schema(value_type = Option<String>)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<i64>,
+    /// Why the event was skipped or conflicted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportCalendarResponse {
+    pub dry_run: bool,
+    pub created: u32,
+    pub conflicts: u32,
+    pub skipped: u32,
+    pub events: Vec<ImportedEvent>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calendar_export_params_optional_dates() {
+        let params: CalendarExportParams = serde_json::from_str("{}").unwrap();
+        assert_eq!(params.start_date, None);
+        assert_eq!(params.end_date, None);
+
+        let json = r#"{"start_date":"2026-05-01","end_date":"2026-05-31"}"#;
+        let params: CalendarExportParams = serde_json::from_str(json).unwrap();
+        assert_eq!(params.start_date.as_deref(), Some("2026-05-01"));
+        assert_eq!(params.end_date.as_deref(), Some("2026-05-31"));
+    }
+
+    #[test]
+    fn import_calendar_request_defaults() {
+        let json = r#"{"ics":"BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"}"#;
+        let req: ImportCalendarRequest = serde_json::from_str(json).unwrap();
+        assert!(req.ics.starts_with("BEGIN:VCALENDAR"));
+        assert_eq!(req.default_topic_id, None);
+        assert!(!req.dry_run);
+    }
+
+    #[test]
+    fn import_calendar_request_with_default_topic() {
+        let json = r#"{"ics":"","default_topic_id":"42","dry_run":true}"#;
+        let req: ImportCalendarRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.default_topic_id, Some(42));
+        assert!(req.dry_run);
+    }
+
+    #[test]
+    fn imported_event_skips_empty_fields() {
+        let event = ImportedEvent {
+            uid: None,
+            summary: Some("Standup".to_string()),
+            status: ImportEventStatus::Skipped,
+            start_time: None,
+            duration_minutes: None,
+            topic_id: None,
+            track_id: None,
+            reason: Some("all-day events are not imported".to_string()),
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains("\"status\":\"skipped\""));
+        assert!(!json.contains("track_id"));
+        assert!(!json.contains("uid"));
+    }
+
+    #[test]
+    fn create_import_rule_request_deserializes() {
+        let json = r#"{"keyword":"standup","topic_id":"7"}"#;
+        let req: CreateImportRuleRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.keyword, "standup");
+        assert_eq!(req.topic_id, 7);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::Topic;
-use crate::api_key::ApiKeyResponse;
+use crate::api_key::{ApiKeyResponse, ApiKeyScope};
+use crate::calendar::ImportRule;
 use crate::daily_track::DailyTrack;
+use crate::digest::{DigestFrequency, DigestSettings};
+use crate::goal::{Goal, GoalComparison};
 use chrono::{TimeZone, Utc};
 use db_model;
 
@@ -26,6 +29,7 @@ pub fn db_api_key_to_response(key: &db_model::models::ApiKey) -> ApiKeyResponse
         id: key.id,
         name: key.name.clone(),
         key_prefix: key.key_prefix.clone(),
+        scope: ApiKeyScope::parse(&key.scope).unwrap_or_default(),
         created_at: Utc.from_utc_datetime(&key.created_at).to_rfc3339(),
         last_used_at: key
             .last_used_at
@@ -52,6 +56,49 @@ pub fn db_daily_track_to_daily_track(track: &db_model::models::DailyTrack) -> Da
     }
 }
 
+pub fn db_goal_to_goal(goal: &db_model::models::TopicGoal) -> Goal {
+    let created_at = Utc.from_utc_datetime(&goal.created_at);
+    let updated_at = goal
+        .updated_at
+        .map(|dt| Utc.from_utc_datetime(&dt))
+        .unwrap_or(created_at);
+
+    Goal {
+        id: goal.id,
+        topic_id: goal.topic_id,
+        comparison: GoalComparison::parse(&goal.comparison).unwrap_or(GoalComparison::AtLeast),
+        target_minutes: goal.target_minutes,
+        include_subtopics: goal.include_subtopics,
+        created_at,
+        updated_at,
+    }
+}
+
+pub fn db_digest_to_settings(
+    subscription: &db_model::models::DigestSubscription,
+) -> DigestSettings {
+    DigestSettings {
+        enabled: subscription.enabled,
+        frequency: DigestFrequency::parse(&subscription.frequency).unwrap_or_default(),
+        send_time: subscription.send_time.format("%H:%M").to_string(),
+        time_zone: subscription.time_zone.clone(),
+        topic_ids: subscription
+            .topic_ids
+            .as_deref()
+            .and_then(|ids| serde_json::from_str(ids).ok()),
+        last_period_start: subscription.last_period_start,
+    }
+}
+
+pub fn db_import_rule_to_import_rule(rule: &db_model::models::CalendarImportRule) -> ImportRule {
+    ImportRule {
+        id: rule.id,
+        keyword: rule.keyword.clone(),
+        topic_id: rule.topic_id,
+        created_at: Utc.from_utc_datetime(&rule.created_at),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,6 +119,7 @@ mod tests {
             updated_at: Some(naive_updated),
             parent_topic_id: Some(456),
             user_id: Some(1),
+            version: 1,
         };
 
         let topic = db_topic_to_topic(&db_topic);
@@ -97,6 +145,7 @@ mod tests {
             updated_at: None,
             parent_topic_id: None,
             user_id: None,
+            version: 1,
         };
 
         let topic = db_topic_to_topic(&db_topic);
@@ -124,6 +173,7 @@ mod tests {
             comment: Some("test comment".to_string()),
             user_id: Some(1),
             duration_minutes: 90,
+            version: 1,
         };
 
         let track = db_daily_track_to_daily_track(&db_track);
@@ -150,6 +200,7 @@ mod tests {
             comment: None,
             user_id: None,
             duration_minutes: 30,
+            version: 1,
         };
 
         let track = db_daily_track_to_daily_track(&db_track);
@@ -173,6 +224,7 @@ mod tests {
             key_hash: "deadbeef".to_string(),
             key_prefix: "dt_a1b2c3d4".to_string(),
             name: "ci-bot".to_string(),
+            scope: "calendar_feed".to_string(),
             created_at: created,
             last_used_at: Some(last_used),
             revoked_at: None,
@@ -183,6 +235,7 @@ mod tests {
         assert_eq!(resp.id, 99);
         assert_eq!(resp.name, "ci-bot");
         assert_eq!(resp.key_prefix, "dt_a1b2c3d4");
+        assert_eq!(resp.scope, ApiKeyScope::CalendarFeed);
         assert_eq!(
             resp.created_at,
             Utc.from_utc_datetime(&created).to_rfc3339()
@@ -204,6 +257,7 @@ mod tests {
             key_hash: "h".to_string(),
             key_prefix: "dt_pfx".to_string(),
             name: "fresh".to_string(),
+            scope: "full".to_string(),
             created_at: created,
             last_used_at: None,
             revoked_at: None,
@@ -224,6 +278,7 @@ mod tests {
             key_hash: "SECRET_HASH_VALUE_DO_NOT_LEAK".to_string(),
             key_prefix: "dt_pfx".to_string(),
             name: "n".to_string(),
+            scope: "full".to_string(),
             created_at: created,
             last_used_at: None,
             revoked_at: None,
@@ -233,4 +288,55 @@ mod tests {
         let json = serde_json::to_string(&resp).unwrap();
         assert!(!json.contains("SECRET_HASH_VALUE_DO_NOT_LEAK"));
     }
+
+    #[test]
+    fn db_goal_to_goal_converts_all_fields() {
+        let created =
+            NaiveDateTime::parse_from_str("2026-05-12 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let goal = db_model::models::TopicGoal {
+            id: 11,
+            user_id: 1,
+            topic_id: 22,
+            comparison: "at_most".to_string(),
+            target_minutes: 60,
+            include_subtopics: true,
+            created_at: created,
+            updated_at: None,
+        };
+
+        let resp = db_goal_to_goal(&goal);
+        assert_eq!(resp.id, 11);
+        assert_eq!(resp.topic_id, 22);
+        assert_eq!(resp.comparison, GoalComparison::AtMost);
+        assert_eq!(resp.target_minutes, 60);
+        assert!(resp.include_subtopics);
+        assert_eq!(resp.updated_at, Utc.from_utc_datetime(&created));
+    }
+
+    #[test]
+    fn db_digest_to_settings_reads_topic_ids() {
+        let created =
+            NaiveDateTime::parse_from_str("2026-10-19 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let mut subscription = db_model::models::DigestSubscription {
+            user_id: 1,
+            enabled: true,
+            frequency: "daily".to_string(),
+            send_time: chrono::NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+            time_zone: "Europe/Berlin".to_string(),
+            topic_ids: Some("[5,6]".to_string()),
+            unsubscribe_token: "secret".to_string(),
+            last_period_start: None,
+            created_at: created,
+            updated_at: None,
+        };
+
+        let settings = db_digest_to_settings(&subscription);
+        assert_eq!(settings.frequency, DigestFrequency::Daily);
+        assert_eq!(settings.send_time, "07:30");
+        assert_eq!(settings.topic_ids, Some(vec![5, 6]));
+
+        subscription.topic_ids = None;
+        assert_eq!(db_digest_to_settings(&subscription).topic_ids, None);
+    }
 }
@@ -1,14 +1,17 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
+use utoipa::{IntoParams, ToSchema};
 
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct DailyTrack {
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub id: i64,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub topic_id: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -17,9 +20,10 @@ pub struct DailyTrack {
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CreateDailyTrackRequest {
     pub start_time: chrono::DateTime<chrono::Utc>,
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub topic_id: i64,
     pub comment: Option<String>,
@@ -27,24 +31,71 @@ pub struct CreateDailyTrackRequest {
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct UpdateDailyTrackRequest {
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub topic_id: i64,
     pub comment: Option<String>,
     pub duration_minutes: i32,
 }
 
+/// Partial update of a track. Fields left out keep their value; `comment`
+/// may be `null` to clear it, the other fields may not.
+#[derive(Deserialize, ToSchema, Default)]
+pub struct PatchDailyTrackRequest {
+    #[schema(value_type = String, required = false)]
+    #[serde(default, deserialize_with = "present_id")]
+    pub topic_id: Option<Option<i64>>,
+    #[schema(value_type = Option<String>)]
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub comment: Option<Option<String>>,
+    #[schema(value_type = i32, required = false)]
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub duration_minutes: Option<Option<i32>>,
+}
+
+/// Reads a string id or `null` that is present in the JSON. With
+/// `#[serde(default)]` an absent field stays `None`.
+fn present_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<i64>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|id| id.parse().map_err(serde::de::Error::custom))
+        .transpose()
+        .map(Some)
+}
+
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GetDailyTracksParams {
+    /// First day to include (YYYY-MM-DD), inclusive.
     pub start_date: Option<String>,
+    /// Last day to include (YYYY-MM-DD), inclusive.
     pub end_date: Option<String>,
+    /// Only return tracks of this topic.
+    #[param(value_type = Option<String>)]
     #[serde_as(as = "Option<DisplayFromStr>")]
     #[serde(default)]
     pub topic_id: Option<i64>,
 }
 
+/// A CSV row that could not be imported. `line` is the 1-based line in the
+/// uploaded file; the header is line 1.
+#[derive(Serialize, Deserialize, ToSchema, Debug, PartialEq)]
+pub struct CsvLineError {
+    pub line: u64,
+    pub message: String,
+}
+
+/// Result of a CSV import. The import is atomic: when `errors` is non-empty
+/// nothing was stored and both counts are zero.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CsvImportResponse {
+    pub tracks_created: u32,
+    pub topics_created: u32,
+    pub errors: Vec<CsvLineError>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,4 +214,32 @@ mod tests {
         assert_eq!(params.end_date, None);
         assert_eq!(params.topic_id, None);
     }
+
+    #[test]
+    fn csv_import_response_serializes_line_errors() {
+        let response = CsvImportResponse {
+            tracks_created: 0,
+            topics_created: 0,
+            errors: vec![CsvLineError {
+                line: 3,
+                message: "duration_minutes must be a positive multiple of 30".to_string(),
+            }],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains("\"tracks_created\":0"));
+        assert!(json.contains("\"line\":3"));
+    }
+
+    #[test]
+    fn patch_daily_track_request_tells_absent_from_null() {
+        let req: PatchDailyTrackRequest =
+            serde_json::from_str(r#"{"comment":null,"topic_id":"7"}"#).unwrap();
+        assert_eq!(req.comment, Some(None));
+        assert_eq!(req.topic_id, Some(Some(7)));
+        assert_eq!(req.duration_minutes, None);
+
+        let req: PatchDailyTrackRequest = serde_json::from_str(r#"{"topic_id":null}"#).unwrap();
+        assert_eq!(req.topic_id, Some(None));
+        assert_eq!(req.comment, None);
+    }
 }
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use utoipa::ToSchema;
+
+/// How often a digest is sent. A daily digest covers the previous day, a
+/// weekly one the previous ISO week (Monday to Sunday).
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestFrequency {
+    Daily,
+    #[default]
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "daily" => Some(DigestFrequency::Daily),
+            "weekly" => Some(DigestFrequency::Weekly),
+            _ => None,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema, Debug, PartialEq)]
+pub struct DigestSettings {
+    pub enabled: bool,
+    pub frequency: DigestFrequency,
+    /// Local time of day (HH:MM) the digest is sent at.
+    pub send_time: String,
+    /// IANA time zone, e.g. `Europe/Berlin`.
+    pub time_zone: String,
+    /// Topics to report on, each with its subtopics; `null` means all.
+    #[schema(value_type = Option<Vec<String>>)]
+    #[serde_as(as = "Option<Vec<DisplayFromStr>>")]
+    pub topic_ids: Option<Vec<i64>>,
+    /// First day of the last period a digest was sent for.
+    pub last_period_start: Option<chrono::NaiveDate>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UpdateDigestSettingsRequest {
+    pub enabled: bool,
+    pub frequency: DigestFrequency,
+    pub send_time: String,
+    pub time_zone: String,
+    #[schema(value_type = Option<Vec<String>>)]
+    #[serde_as(as = "Option<Vec<DisplayFromStr>>")]
+    #[serde(default)]
+    pub topic_ids: Option<Vec<i64>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_frequency_parse_matches_as_str() {
+        for f in [DigestFrequency::Daily, DigestFrequency::Weekly] {
+            assert_eq!(DigestFrequency::parse(f.as_str()), Some(f));
+        }
+        assert_eq!(DigestFrequency::parse("monthly"), None);
+    }
+
+    #[test]
+    fn update_request_takes_topic_ids_as_strings() {
+        let json = r#"{"enabled":true,"frequency":"daily","send_time":"07:30","time_zone":"Europe/Berlin","topic_ids":["5","6"]}"#;
+        let req: UpdateDigestSettingsRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.frequency, DigestFrequency::Daily);
+        assert_eq!(req.topic_ids, Some(vec![5, 6]));
+    }
+
+    #[test]
+    fn update_request_defaults_to_all_topics() {
+        let json = r#"{"enabled":true,"frequency":"weekly","send_time":"08:00","time_zone":"UTC"}"#;
+        let req: UpdateDigestSettingsRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.topic_ids, None);
+    }
+}
@@ -1,26 +1,213 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+/// Why a request field was rejected.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FieldErrorCode {
+    /// The field is missing or empty.
+    Required,
+    /// The value does not parse, e.g. a malformed email, color or date.
+    InvalidFormat,
+    /// The JSON value has the wrong type, e.g. a string for a number.
+    InvalidType,
+    /// The value parses but is not allowed, e.g. a start time off the
+    /// half-hour grid.
+    InvalidValue,
+    /// The value is too small or too large.
+    OutOfRange,
+}
+
+/// One invalid field of a request.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    /// Name of the field, e.g. `duration_minutes`. A JSON path such as
+    /// `tracks[0].start_time` for nested fields, or `body`, `query` or
+    /// `path` when the field cannot be told.
+    pub field: String,
+    pub code: FieldErrorCode,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: FieldErrorCode, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Collects the invalid fields of a request, so every one of them is
+/// reported instead of only the first.
+#[derive(Default)]
+pub struct ValidationErrors(Vec<FieldError>);
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, error: FieldError) {
+        self.0.push(error);
+    }
+
+    /// Records the error of a failed check; the value of one that passed is
+    /// returned.
+    pub fn check<T>(&mut self, result: Result<T, FieldError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.0.push(error);
+                None
+            }
+        }
+    }
+
+    /// `ApiError::Validation` with every recorded error, if there is one.
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(self.0))
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct ErrorResponse {
+    /// Machine-readable error code, e.g. `TRACK_OVERLAP` or `DUPLICATE_NAME`.
     pub error: String,
     pub message: String,
+    /// Set with `TRACK_OVERLAP`: the existing track the request overlaps.
+    #[schema(value_type = Option<String>)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflicting_track_id: Option<i64>,
+    /// The invalid fields, with `VALIDATION_ERROR`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
 }
 
 pub enum ApiError {
     BadRequest(String),
+    /// One or more request fields are invalid.
+    Validation(Vec<FieldError>),
+    /// An extractor rejected the request before the handler ran, e.g. on
+    /// malformed JSON. Keeps the rejection's status.
+    Rejected {
+        status: StatusCode,
+        message: String,
+        details: Vec<FieldError>,
+    },
     NotFound(String),
     Conflict(String),
     Unauthorized(String),
     Forbidden(String),
     InternalServerError(String),
+    /// The database is out of reach or has no free connection; the client
+    /// may retry.
+    ServiceUnavailable(String),
+    /// The track's time range overlaps an existing track.
+    Overlap {
+        conflicting_track_id: i64,
+    },
+    /// A name that must be unique is already taken.
+    DuplicateName(String),
+    /// The write refers to a missing record, or removes one still in use.
+    ForeignKey(String),
+    /// `If-Match` names none of the record's current ETags.
+    PreconditionFailed(String),
+    /// An `Idempotency-Key` was sent again with a different request.
+    IdempotencyKeyReused(String),
+    /// The first request with this `Idempotency-Key` is still running.
+    IdempotencyKeyInUse(String),
+    /// The first request with this `Idempotency-Key` succeeded, but its
+    /// response held a secret and was not stored for replay.
+    IdempotencyKeyCompleted(String),
+}
+
+impl From<FieldError> for ApiError {
+    fn from(error: FieldError) -> Self {
+        ApiError::Validation(vec![error])
+    }
+}
+
+/// The single mapping from storage failures to responses. Conflicts keep
+/// their own codes; unexpected failures are logged here and answered with a
+/// generic message.
+impl From<db::StoreError> for ApiError {
+    fn from(e: db::StoreError) -> Self {
+        match e {
+            db::StoreError::Overlap {
+                conflicting_track_id,
+            } => ApiError::Overlap {
+                conflicting_track_id,
+            },
+            db::StoreError::NotFound => ApiError::NotFound("Record not found".to_string()),
+            db::StoreError::VersionMismatch => ApiError::PreconditionFailed(
+                "The record was changed since it was read; fetch it again and retry".to_string(),
+            ),
+            db::StoreError::DuplicateName(message) => ApiError::DuplicateName(message),
+            db::StoreError::Conflict(reason) => {
+                log::warn!("Unique violation: {}", reason);
+                ApiError::Conflict("The change conflicts with an existing record".to_string())
+            }
+            db::StoreError::ForeignKey(reason) => {
+                log::warn!("Foreign key violation: {}", reason);
+                ApiError::ForeignKey(
+                    "The change refers to a record that does not exist or is still in use"
+                        .to_string(),
+                )
+            }
+            db::StoreError::Unavailable(reason) => {
+                log::warn!("Database unavailable: {}", reason);
+                ApiError::ServiceUnavailable(
+                    "The database is busy or unreachable; retry shortly".to_string(),
+                )
+            }
+            db::StoreError::Database(e) => {
+                log::error!("Database error: {}", e);
+                ApiError::InternalServerError("Internal server error".to_string())
+            }
+        }
+    }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let mut conflicting_track_id = None;
+        let mut details = Vec::new();
         let (status, error_code, message) = match self {
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "VALIDATION_ERROR", msg),
+            ApiError::Validation(errors) => {
+                let message = errors
+                    .iter()
+                    .map(|e| e.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                details = errors;
+                (StatusCode::BAD_REQUEST, "VALIDATION_ERROR", message)
+            }
+            ApiError::Rejected {
+                status,
+                message,
+                details: errors,
+            } => {
+                details = errors;
+                let code = match status {
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE => "UNSUPPORTED_MEDIA_TYPE",
+                    StatusCode::PAYLOAD_TOO_LARGE => "PAYLOAD_TOO_LARGE",
+                    s if s.is_server_error() => "INTERNAL_ERROR",
+                    _ => "VALIDATION_ERROR",
+                };
+                (status, code, message)
+            }
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, "NOT_FOUND", msg),
             ApiError::Conflict(msg) => (StatusCode::CONFLICT, "CONFLICT", msg),
             ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED", msg),
@@ -28,11 +215,42 @@ impl IntoResponse for ApiError {
             ApiError::InternalServerError(msg) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", msg)
             }
+            ApiError::ServiceUnavailable(msg) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "SERVICE_UNAVAILABLE", msg)
+            }
+            ApiError::Overlap {
+                conflicting_track_id: id,
+            } => {
+                conflicting_track_id = Some(id);
+                (
+                    StatusCode::CONFLICT,
+                    "TRACK_OVERLAP",
+                    format!("The time range overlaps track {}", id),
+                )
+            }
+            ApiError::DuplicateName(msg) => (StatusCode::CONFLICT, "DUPLICATE_NAME", msg),
+            ApiError::ForeignKey(msg) => (StatusCode::CONFLICT, "FOREIGN_KEY_VIOLATION", msg),
+            ApiError::PreconditionFailed(msg) => {
+                (StatusCode::PRECONDITION_FAILED, "PRECONDITION_FAILED", msg)
+            }
+            ApiError::IdempotencyKeyReused(msg) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "IDEMPOTENCY_KEY_REUSED",
+                msg,
+            ),
+            ApiError::IdempotencyKeyInUse(msg) => {
+                (StatusCode::CONFLICT, "IDEMPOTENCY_KEY_IN_USE", msg)
+            }
+            ApiError::IdempotencyKeyCompleted(msg) => {
+                (StatusCode::CONFLICT, "IDEMPOTENCY_KEY_COMPLETED", msg)
+            }
         };
 
         let body = ErrorResponse {
             error: error_code.to_string(),
             message,
+            conflicting_track_id,
+            details,
         };
 
         (status, axum::Json(body)).into_response()
@@ -113,15 +331,152 @@ mod tests {
         assert_eq!(body.message, "something broke");
     }
 
+    #[tokio::test]
+    async fn service_unavailable_returns_503() {
+        let (status, body) =
+            extract_error_response(ApiError::ServiceUnavailable("try later".to_string())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.error, "SERVICE_UNAVAILABLE");
+        assert_eq!(body.message, "try later");
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_errors() {
+        let (status, body) = extract_error_response(ApiError::IdempotencyKeyReused(
+            "different payload".to_string(),
+        ))
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body.error, "IDEMPOTENCY_KEY_REUSED");
+
+        let (status, body) =
+            extract_error_response(ApiError::IdempotencyKeyInUse("still running".to_string()))
+                .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body.error, "IDEMPOTENCY_KEY_IN_USE");
+
+        let (status, body) = extract_error_response(ApiError::IdempotencyKeyCompleted(
+            "already created".to_string(),
+        ))
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body.error, "IDEMPOTENCY_KEY_COMPLETED");
+    }
+
+    #[tokio::test]
+    async fn validation_reports_every_field() {
+        let mut errors = ValidationErrors::new();
+        assert_eq!(errors.check(Ok::<_, FieldError>(5)), Some(5));
+        errors.add(FieldError::new(
+            "start_time",
+            FieldErrorCode::InvalidValue,
+            "start_time must be at :00 or :30 minutes",
+        ));
+        errors.check::<()>(Err(FieldError::new(
+            "duration_minutes",
+            FieldErrorCode::OutOfRange,
+            "duration_minutes must not exceed 1440 (24 hours)",
+        )));
+        let (status, body) = extract_error_response(errors.finish().unwrap_err()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error, "VALIDATION_ERROR");
+        assert_eq!(body.details.len(), 2);
+        assert_eq!(body.details[0].field, "start_time");
+        assert_eq!(body.details[1].code, FieldErrorCode::OutOfRange);
+        assert!(body.message.contains("start_time") && body.message.contains("duration_minutes"));
+        assert!(ValidationErrors::new().finish().is_ok());
+    }
+
+    #[test]
+    fn field_error_codes_are_screaming_snake_case() {
+        let json = serde_json::to_string(&FieldError::new(
+            "email",
+            FieldErrorCode::InvalidFormat,
+            "A valid email address is required",
+        ))
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"field":"email","code":"INVALID_FORMAT","message":"A valid email address is required"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn rejections_keep_their_status() {
+        let (status, body) = extract_error_response(ApiError::Rejected {
+            status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            message: "Expected request with `Content-Type: application/json`".to_string(),
+            details: Vec::new(),
+        })
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body.error, "UNSUPPORTED_MEDIA_TYPE");
+    }
+
+    #[tokio::test]
+    async fn overlap_returns_409_with_the_conflicting_track() {
+        let (status, body) = extract_error_response(ApiError::from(db::StoreError::Overlap {
+            conflicting_track_id: 42,
+        }))
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body.error, "TRACK_OVERLAP");
+        assert_eq!(body.conflicting_track_id, Some(42));
+    }
+
+    #[tokio::test]
+    async fn store_errors_get_their_own_codes() {
+        let cases = [
+            (
+                db::StoreError::DuplicateName("Topic with name 'x' already exists".to_string()),
+                StatusCode::CONFLICT,
+                "DUPLICATE_NAME",
+            ),
+            (
+                db::StoreError::ForeignKey("Cannot add or update a child row".to_string()),
+                StatusCode::CONFLICT,
+                "FOREIGN_KEY_VIOLATION",
+            ),
+            (
+                db::StoreError::Conflict(
+                    "Duplicate entry '1' for key 'api_keys.key_hash'".to_string(),
+                ),
+                StatusCode::CONFLICT,
+                "CONFLICT",
+            ),
+            (db::StoreError::NotFound, StatusCode::NOT_FOUND, "NOT_FOUND"),
+            (
+                db::StoreError::VersionMismatch,
+                StatusCode::PRECONDITION_FAILED,
+                "PRECONDITION_FAILED",
+            ),
+            (
+                db::StoreError::Unavailable("timed out".to_string()),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "SERVICE_UNAVAILABLE",
+            ),
+        ];
+        for (store_error, expected_status, expected_code) in cases {
+            let (status, body) = extract_error_response(store_error.into()).await;
+            assert_eq!(status, expected_status);
+            assert_eq!(body.error, expected_code);
+            assert_eq!(body.conflicting_track_id, None);
+        }
+    }
+
     #[test]
     fn error_response_serializes_to_json() {
         let resp = ErrorResponse {
             error: "TEST".to_string(),
             message: "test message".to_string(),
+            conflicting_track_id: None,
+            details: Vec::new(),
         };
         let json = serde_json::to_string(&resp).unwrap();
         assert!(json.contains("\"error\":\"TEST\""));
         assert!(json.contains("\"message\":\"test message\""));
+        assert!(!json.contains("conflicting_track_id"));
+        assert!(!json.contains("details"));
     }
 
     #[test]
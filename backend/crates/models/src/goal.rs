@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use utoipa::{IntoParams, ToSchema};
+
+/// Whether a goal is a target to reach (`at_least`) or a budget to stay
+/// under (`at_most`).
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalComparison {
+    AtLeast,
+    AtMost,
+}
+
+impl GoalComparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalComparison::AtLeast => "at_least",
+            GoalComparison::AtMost => "at_most",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "at_least" => Some(GoalComparison::AtLeast),
+            "at_most" => Some(GoalComparison::AtMost),
+            _ => None,
+        }
+    }
+
+    pub fn is_met(&self, actual_minutes: i64, target_minutes: i64) -> bool {
+        match self {
+            GoalComparison::AtLeast => actual_minutes >= target_minutes,
+            GoalComparison::AtMost => actual_minutes <= target_minutes,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalPeriod {
+    #[default]
+    Day,
+    Week,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Goal {
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub id: i64,
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub topic_id: i64,
+    pub comparison: GoalComparison,
+    /// Minutes per day.
+    pub target_minutes: i32,
+    pub include_subtopics: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateGoalRequest {
+    #[schema(value_type = String)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub topic_id: i64,
+    pub comparison: GoalComparison,
+    pub target_minutes: i32,
+    #[serde(default)]
+    pub include_subtopics: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UpdateGoalRequest {
+    pub comparison: GoalComparison,
+    pub target_minutes: i32,
+    #[serde(default)]
+    pub include_subtopics: bool,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetGoalProgressParams {
+    /// Day to report on (YYYY-MM-DD); defaults to today (UTC). For weekly
+    /// reports, any day of the ISO week.
+    pub date: Option<String>,
+    /// Reporting period; defaults to `day`.
+    #[serde(default)]
+    pub period: GoalPeriod,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub period: GoalPeriod,
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    /// The daily target scaled to the length of the period.
+    pub target_minutes: i64,
+    pub actual_minutes: i64,
+    pub met: bool,
+    pub days_met: u32,
+    pub days_in_period: u32,
+    /// Consecutive days, ending at the period end (or today if earlier), on
+    /// which the daily goal was met.
+    pub current_streak_days: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goal_comparison_roundtrips_as_snake_case() {
+        let json = serde_json::to_string(&GoalComparison::AtLeast).unwrap();
+        assert_eq!(json, "\"at_least\"");
+        let parsed: GoalComparison = serde_json::from_str("\"at_most\"").unwrap();
+        assert_eq!(parsed, GoalComparison::AtMost);
+    }
+
+    #[test]
+    fn goal_comparison_parse_matches_as_str() {
+        for c in [GoalComparison::AtLeast, GoalComparison::AtMost] {
+            assert_eq!(GoalComparison::parse(c.as_str()), Some(c));
+        }
+        assert_eq!(GoalComparison::parse("exactly"), None);
+    }
+
+    #[test]
+    fn goal_comparison_is_met_is_inclusive() {
+        assert!(GoalComparison::AtLeast.is_met(120, 120));
+        assert!(!GoalComparison::AtLeast.is_met(90, 120));
+        assert!(GoalComparison::AtMost.is_met(60, 60));
+        assert!(!GoalComparison::AtMost.is_met(90, 60));
+    }
+
+    #[test]
+    fn create_goal_request_defaults_include_subtopics() {
+        let json = r#"{"topic_id":"5","comparison":"at_least","target_minutes":120}"#;
+        let req: CreateGoalRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.topic_id, 5);
+        assert_eq!(req.comparison, GoalComparison::AtLeast);
+        assert_eq!(req.target_minutes, 120);
+        assert!(!req.include_subtopics);
+    }
+
+    #[test]
+    fn create_goal_request_rejects_unknown_comparison() {
+        let json = r#"{"topic_id":"5","comparison":"exactly","target_minutes":120}"#;
+        let res: Result<CreateGoalRequest, _> = serde_json::from_str(json);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn get_goal_progress_params_default_to_day() {
+        let params: GetGoalProgressParams = serde_json::from_str("{}").unwrap();
+        assert_eq!(params.date, None);
+        assert_eq!(params.period, GoalPeriod::Day);
+    }
+
+    #[test]
+    fn get_goal_progress_params_week() {
+        let json = r#"{"date":"2026-05-12","period":"week"}"#;
+        let params: GetGoalProgressParams = serde_json::from_str(json).unwrap();
+        assert_eq!(params.date.as_deref(), Some("2026-05-12"));
+        assert_eq!(params.period, GoalPeriod::Week);
+    }
+}
@@ -4,9 +4,19 @@ pub mod error;
 pub mod convert;
 pub mod auth;
 pub mod api_key;
+pub mod goal;
+pub mod calendar;
+pub mod backup;
+pub mod health;
+pub mod digest;
 pub use topic::*;
 pub use daily_track::*;
 pub use error::*;
 pub use convert::*;
 pub use auth::*;
 pub use api_key::*;
+pub use goal::*;
+pub use calendar::*;
+pub use backup::*;
+pub use health::*;
+pub use digest::*;
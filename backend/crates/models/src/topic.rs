@@ -1,39 +1,58 @@
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
+use utoipa::{IntoParams, ToSchema};
 
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Topic {
+    #[schema(value_type = String)]
     #[serde_as(as = "DisplayFromStr")]
     pub id: i64,
     pub topic_name: String,
     pub display_color: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[schema(value_type = Option<String>)]
     #[serde_as(as = "Option<DisplayFromStr>")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub parent_topic_id: Option<i64>,
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CreateTopicRequest {
     pub topic_name: String,
+    #[schema(value_type = Option<String>)]
     #[serde_as(as = "Option<DisplayFromStr>")]
     #[serde(default)]
     pub parent_topic_id: Option<i64>,
     pub display_color: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct UpdateTopicRequest {
     pub topic_name: String,
     pub display_color: String,
 }
 
+/// Partial update of a topic. Fields left out keep their value; neither may
+/// be `null`.
+#[derive(Deserialize, ToSchema, Default)]
+pub struct PatchTopicRequest {
+    #[schema(value_type = String, required = false)]
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub topic_name: Option<Option<String>>,
+    #[schema(value_type = String, required = false)]
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub display_color: Option<Option<String>>,
+}
+
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GetTopicsParams {
+    /// Only return the children of this topic.
+    #[param(value_type = Option<String>)]
     #[serde_as(as = "Option<DisplayFromStr>")]
     #[serde(default)]
     pub parent_topic_id: Option<i64>,
@@ -132,6 +151,17 @@ mod tests {
         assert_eq!(req.display_color, "#abcdef");
     }
 
+    #[test]
+    fn patch_topic_request_tells_absent_from_null() {
+        let req: PatchTopicRequest = serde_json::from_str(r#"{"topic_name":null}"#).unwrap();
+        assert_eq!(req.topic_name, Some(None));
+        assert_eq!(req.display_color, None);
+
+        let req: PatchTopicRequest =
+            serde_json::from_str(r##"{"display_color":"#abcdef"}"##).unwrap();
+        assert_eq!(req.display_color, Some(Some("#abcdef".to_string())));
+    }
+
     #[test]
     fn get_topics_params_deserializes_with_parent() {
         let json = r#"{"parent_topic_id":"7"}"#;
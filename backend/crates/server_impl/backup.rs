@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use db::{AccountRestore, RestoreGoal, RestoreImportRule, RestoreTopic, RestoreTrack};
+use db_model::models::{ApiKey, CalendarImportRule, DailyTrack, Topic, TopicGoal};
+use models::{
+    AccountBackup, BACKUP_FORMAT, BACKUP_VERSION, BackupDailyTrack, BackupGoal, BackupImportRule,
+    BackupTopic, db_api_key_to_response, db_goal_to_goal,
+};
+
+use crate::handler::{
+    MAX_IMPORT_RULE_KEYWORD_LEN, is_valid_hex_color, validate_duration_minutes,
+    validate_goal_target_minutes, validate_start_time,
+};
+
+/// Assembles the backup document for one account. API keys are exported
+/// as metadata only; their hashes never leave the database.
+pub(crate) fn build_backup(
+    topics: &[Topic],
+    tracks: &[DailyTrack],
+    goals: &[TopicGoal],
+    rules: &[CalendarImportRule],
+    api_keys: &[ApiKey],
+    exported_at: DateTime<Utc>,
+) -> AccountBackup {
+    AccountBackup {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        exported_at,
+        topics: topics
+            .iter()
+            .map(|t| BackupTopic {
+                id: t.id,
+                topic_name: t.topic_name.clone(),
+                display_color: t.display_color.clone(),
+                parent_topic_id: t.parent_topic_id,
+                created_at: t.created_at.and_utc(),
+            })
+            .collect(),
+        daily_tracks: tracks
+            .iter()
+            .map(|t| BackupDailyTrack {
+                id: t.id,
+                start_time: t.start_time.and_utc(),
+                duration_minutes: t.duration_minutes,
+                topic_id: t.topic_id,
+                comment: t.comment.clone(),
+            })
+            .collect(),
+        goals: goals
+            .iter()
+            .map(|g| BackupGoal {
+                topic_id: g.topic_id,
+                comparison: db_goal_to_goal(g).comparison,
+                target_minutes: g.target_minutes,
+                include_subtopics: g.include_subtopics,
+            })
+            .collect(),
+        calendar_import_rules: rules
+            .iter()
+            .map(|r| BackupImportRule {
+                keyword: r.keyword.clone(),
+                topic_id: r.topic_id,
+            })
+            .collect(),
+        api_keys: api_keys.iter().map(db_api_key_to_response).collect(),
+    }
+}
+
+/// Orders topics so every parent precedes its children. Fails on unknown
+/// parents and on parent cycles.
+fn order_topics(topics: Vec<BackupTopic>) -> Result<Vec<BackupTopic>, String> {
+    let ids: HashSet<i64> = topics.iter().map(|t| t.id).collect();
+    if let Some(t) = topics
+        .iter()
+        .find(|t| t.parent_topic_id.is_some_and(|p| !ids.contains(&p)))
+    {
+        return Err(format!(
+            "topic '{}' references unknown parent {}",
+            t.topic_name,
+            t.parent_topic_id.unwrap_or_default()
+        ));
+    }
+
+    let mut placed: HashSet<i64> = HashSet::new();
+    let mut ordered = Vec::with_capacity(topics.len());
+    let mut pending = topics;
+    while !pending.is_empty() {
+        let (ready, rest): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|t| t.parent_topic_id.is_none_or(|p| placed.contains(&p)));
+        if ready.is_empty() {
+            return Err(format!(
+                "topic '{}' is part of a parent cycle",
+                rest[0].topic_name
+            ));
+        }
+        placed.extend(ready.iter().map(|t| t.id));
+        ordered.extend(ready);
+        pending = rest;
+    }
+    Ok(ordered)
+}
+
+/// Validates a backup and converts it into a restore plan. Entries are held
+/// to the same rules as when they are created through the API, so a restore
+/// cannot smuggle in data the API would refuse.
+pub(crate) fn plan_restore(backup: AccountBackup) -> Result<AccountRestore, String> {
+    if backup.format != BACKUP_FORMAT {
+        return Err(format!("format must be '{}'", BACKUP_FORMAT));
+    }
+    if backup.version == 0 || backup.version > BACKUP_VERSION {
+        return Err(format!(
+            "unsupported backup version {}; this server reads versions 1 to {}",
+            backup.version, BACKUP_VERSION
+        ));
+    }
+
+    let mut ids = HashSet::new();
+    let mut names: HashMap<&str, i64> = HashMap::new();
+    for topic in &backup.topics {
+        if !ids.insert(topic.id) {
+            return Err(format!("duplicate topic id {}", topic.id));
+        }
+        if topic.topic_name.trim().is_empty() {
+            return Err(format!("topic {} has an empty topic_name", topic.id));
+        }
+        if names.insert(topic.topic_name.as_str(), topic.id).is_some() {
+            return Err(format!("duplicate topic name '{}'", topic.topic_name));
+        }
+        if !is_valid_hex_color(&topic.display_color) {
+            return Err(format!(
+                "topic '{}' has invalid display_color '{}'",
+                topic.topic_name, topic.display_color
+            ));
+        }
+    }
+    let known_topic = |id: i64, what: &str| {
+        if ids.contains(&id) {
+            Ok(id)
+        } else {
+            Err(format!("{} references unknown topic {}", what, id))
+        }
+    };
+
+    let mut tracks = Vec::with_capacity(backup.daily_tracks.len());
+    for track in backup.daily_tracks {
+        let what = format!("daily track {}", track.id);
+        validate_start_time(&track.start_time)
+            .and_then(|_| validate_duration_minutes(track.duration_minutes))
+            .map_err(|e| format!("{}: {}", what, e.message))?;
+        tracks.push(RestoreTrack {
+            start_time: track.start_time.naive_utc(),
+            duration_minutes: track.duration_minutes,
+            topic_source_id: track
+                .topic_id
+                .map(|id| known_topic(id, &what))
+                .transpose()?,
+            comment: track.comment,
+        });
+    }
+
+    let mut goals = Vec::with_capacity(backup.goals.len());
+    for goal in backup.goals {
+        let topic_source_id = known_topic(goal.topic_id, "goal")?;
+        validate_goal_target_minutes(goal.target_minutes)
+            .map_err(|e| format!("goal on topic {}: {}", goal.topic_id, e.message))?;
+        goals.push(RestoreGoal {
+            topic_source_id,
+            comparison: goal.comparison.as_str().to_string(),
+            target_minutes: goal.target_minutes,
+            include_subtopics: goal.include_subtopics,
+        });
+    }
+
+    let mut import_rules = Vec::with_capacity(backup.calendar_import_rules.len());
+    for rule in backup.calendar_import_rules {
+        let keyword = rule.keyword.trim();
+        if keyword.is_empty() || keyword.chars().count() > MAX_IMPORT_RULE_KEYWORD_LEN {
+            return Err(format!(
+                "calendar import rule keyword must be 1 to {} characters",
+                MAX_IMPORT_RULE_KEYWORD_LEN
+            ));
+        }
+        import_rules.push(RestoreImportRule {
+            keyword: keyword.to_string(),
+            topic_source_id: known_topic(rule.topic_id, "calendar import rule")?,
+        });
+    }
+
+    let topics = order_topics(backup.topics)?
+        .into_iter()
+        .map(|t| RestoreTopic {
+            source_id: t.id,
+            topic_name: t.topic_name,
+            display_color: t.display_color,
+            parent_source_id: t.parent_topic_id,
+            created_at: t.created_at.naive_utc(),
+        })
+        .collect();
+
+    Ok(AccountRestore {
+        topics,
+        tracks,
+        goals,
+        import_rules,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use models::GoalComparison;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn topic(id: i64, name: &str, parent: Option<i64>) -> Topic {
+        Topic {
+            id,
+            topic_name: name.to_string(),
+            display_color: "#14b8a6".to_string(),
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+            parent_topic_id: parent,
+            user_id: Some(1),
+            version: 1,
+        }
+    }
+
+    fn track(id: i64, start: &str, topic_id: Option<i64>) -> DailyTrack {
+        DailyTrack {
+            id,
+            start_time: at(start),
+            created_at: at(start),
+            updated_at: None,
+            topic_id,
+            comment: Some("notes".to_string()),
+            user_id: Some(1),
+            duration_minutes: 60,
+            version: 1,
+        }
+    }
+
+    fn api_key() -> ApiKey {
+        ApiKey {
+            id: 7,
+            user_id: 1,
+            key_hash: "secret-hash".to_string(),
+            key_prefix: "dt_abcd".to_string(),
+            name: "ci".to_string(),
+            scope: "full".to_string(),
+            created_at: at("2026-01-01 00:00"),
+            last_used_at: None,
+            revoked_at: None,
+        }
+    }
+
+    fn sample_backup() -> AccountBackup {
+        // The child is listed before its parent on purpose.
+        let topics = vec![topic(2, "Backend", Some(1)), topic(1, "Work", None)];
+        let tracks = vec![track(10, "2026-05-12 09:00", Some(2))];
+        let goals = vec![TopicGoal {
+            id: 20,
+            user_id: 1,
+            topic_id: 1,
+            comparison: "at_least".to_string(),
+            target_minutes: 120,
+            include_subtopics: true,
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+        }];
+        let rules = vec![CalendarImportRule {
+            id: 30,
+            user_id: 1,
+            keyword: "standup".to_string(),
+            topic_id: 2,
+            created_at: at("2026-01-01 00:00"),
+        }];
+        build_backup(
+            &topics,
+            &tracks,
+            &goals,
+            &rules,
+            &[api_key()],
+            at("2026-05-20 08:00").and_utc(),
+        )
+    }
+
+    #[test]
+    fn build_backup_omits_key_hashes() {
+        let json = serde_json::to_string(&sample_backup()).unwrap();
+        assert!(json.contains("\"format\":\"daily-tracker-backup\""));
+        assert!(json.contains("dt_abcd"));
+        assert!(!json.contains("secret-hash"));
+    }
+
+    #[test]
+    fn plan_restore_orders_parents_first_and_keeps_links() {
+        let plan = plan_restore(sample_backup()).unwrap();
+        let order: Vec<i64> = plan.topics.iter().map(|t| t.source_id).collect();
+        assert_eq!(order, vec![1, 2]);
+        assert_eq!(plan.topics[1].parent_source_id, Some(1));
+        assert_eq!(plan.tracks[0].topic_source_id, Some(2));
+        assert_eq!(plan.goals[0].comparison, GoalComparison::AtLeast.as_str());
+        assert_eq!(plan.import_rules[0].topic_source_id, 2);
+    }
+
+    #[test]
+    fn plan_restore_survives_json_round_trip() {
+        let json = serde_json::to_string(&sample_backup()).unwrap();
+        let backup: AccountBackup = serde_json::from_str(&json).unwrap();
+        assert_eq!(plan_restore(backup).unwrap().tracks.len(), 1);
+    }
+
+    #[test]
+    fn plan_restore_rejects_newer_versions() {
+        let mut backup = sample_backup();
+        backup.version = BACKUP_VERSION + 1;
+        let err = plan_restore(backup).err().unwrap();
+        assert!(err.contains("unsupported backup version"));
+    }
+
+    #[test]
+    fn plan_restore_rejects_parent_cycles() {
+        let mut backup = sample_backup();
+        backup.topics[1].parent_topic_id = Some(2);
+        let err = plan_restore(backup).err().unwrap();
+        assert!(err.contains("cycle"));
+    }
+
+    #[test]
+    fn plan_restore_rejects_unknown_topic_references() {
+        let mut backup = sample_backup();
+        backup.daily_tracks[0].topic_id = Some(99);
+        let err = plan_restore(backup).err().unwrap();
+        assert!(err.contains("unknown topic 99"));
+    }
+
+    #[test]
+    fn plan_restore_validates_tracks_like_the_api() {
+        let mut backup = sample_backup();
+        backup.daily_tracks[0].duration_minutes = 45;
+        let err = plan_restore(backup).err().unwrap();
+        assert!(err.contains("multiple of 30"));
+    }
+
+    #[test]
+    fn plan_restore_rejects_duplicate_topic_names() {
+        let mut backup = sample_backup();
+        backup.topics[0].topic_name = "Work".to_string();
+        let err = plan_restore(backup).err().unwrap();
+        assert!(err.contains("duplicate topic name"));
+    }
+}
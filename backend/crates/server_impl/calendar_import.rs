@@ -0,0 +1,242 @@
+use chrono::{Duration, NaiveDateTime, Timelike};
+use db::TrackDraft;
+use db_model::models::CalendarImportRule;
+use models::{ImportEventStatus, ImportedEvent};
+
+use crate::handler::{validate_duration_minutes, validate_start_time};
+use crate::ical::{CalendarEvent, EventTiming};
+
+/// Upper bound on events per import, so a whole calendar history cannot be
+/// pushed through one request and one transaction.
+pub(crate) const MAX_IMPORT_EVENTS: usize = 500;
+
+const SLOT_MINUTES: i64 = 30;
+
+/// Rounds `start` down and `end` up to the 30-minute grid that tracks live
+/// on, so a 09:10-09:50 meeting becomes 09:00-10:00.
+pub(crate) fn snap_to_slots(start: NaiveDateTime, end: NaiveDateTime) -> (NaiveDateTime, i32) {
+    let floor = |t: NaiveDateTime| {
+        let past_slot = i64::from(t.minute()) % SLOT_MINUTES;
+        t.with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .expect("valid time")
+            - Duration::minutes(past_slot)
+    };
+
+    let snapped_start = floor(start);
+    let mut snapped_end = floor(end);
+    if snapped_end < end {
+        snapped_end += Duration::minutes(SLOT_MINUTES);
+    }
+    let minutes = (snapped_end - snapped_start).num_minutes();
+    (snapped_start, i32::try_from(minutes).unwrap_or(i32::MAX))
+}
+
+/// Picks the topic for an event: the longest rule keyword found in the
+/// summary, then the longest found in the description, then `default_topic_id`.
+/// Matching is case-insensitive.
+pub(crate) fn match_topic(
+    event: &CalendarEvent,
+    rules: &[CalendarImportRule],
+    default_topic_id: Option<i64>,
+) -> Option<i64> {
+    let best_in = |text: Option<&str>| {
+        let text = text?.to_lowercase();
+        rules
+            .iter()
+            .filter(|r| text.contains(&r.keyword.to_lowercase()))
+            .max_by_key(|r| r.keyword.chars().count())
+            .map(|r| r.topic_id)
+    };
+
+    best_in(event.summary.as_deref())
+        .or_else(|| best_in(event.description.as_deref()))
+        .or(default_topic_id)
+}
+
+fn skipped(
+    event: &CalendarEvent,
+    reason: impl Into<String>,
+) -> (ImportedEvent, Option<TrackDraft>) {
+    (
+        ImportedEvent {
+            uid: event.uid.clone(),
+            summary: event.summary.clone(),
+            status: ImportEventStatus::Skipped,
+            start_time: None,
+            duration_minutes: None,
+            topic_id: None,
+            track_id: None,
+            reason: Some(reason.into()),
+        },
+        None,
+    )
+}
+
+/// Turns one parsed event into a track draft, or explains why it is skipped.
+/// The draft passes the same start-time and duration checks as
+/// `create_daily_track`; overlaps are left to the database.
+pub(crate) fn plan_event(
+    event: &CalendarEvent,
+    rules: &[CalendarImportRule],
+    default_topic_id: Option<i64>,
+) -> (ImportedEvent, Option<TrackDraft>) {
+    if event.recurring {
+        return skipped(event, "recurring events are not imported");
+    }
+    let (start, end) = match &event.timing {
+        Ok(EventTiming::Timed { start, end }) => (*start, *end),
+        Ok(EventTiming::AllDay) => return skipped(event, "all-day events are not imported"),
+        Err(reason) => return skipped(event, reason.clone()),
+    };
+    let Some(topic_id) = match_topic(event, rules, default_topic_id) else {
+        return skipped(
+            event,
+            "no import rule matched and no default_topic_id was given",
+        );
+    };
+
+    let (start_time, duration_minutes) = snap_to_slots(start, end);
+    let start_time_utc = start_time.and_utc();
+    if let Err(e) = validate_start_time(&start_time_utc)
+        .and_then(|_| validate_duration_minutes(duration_minutes))
+    {
+        return skipped(event, e.message);
+    }
+
+    let imported = ImportedEvent {
+        uid: event.uid.clone(),
+        summary: event.summary.clone(),
+        status: ImportEventStatus::WouldCreate,
+        start_time: Some(start_time_utc),
+        duration_minutes: Some(duration_minutes),
+        topic_id: Some(topic_id),
+        track_id: None,
+        reason: None,
+    };
+    let draft = TrackDraft {
+        start_time,
+        topic_id,
+        comment: event.summary.clone().filter(|s| !s.trim().is_empty()),
+        duration_minutes,
+    };
+    (imported, Some(draft))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn rule(keyword: &str, topic_id: i64) -> CalendarImportRule {
+        CalendarImportRule {
+            id: topic_id * 10,
+            user_id: 1,
+            keyword: keyword.to_string(),
+            topic_id,
+            created_at: at("2026-01-01 00:00:00"),
+        }
+    }
+
+    fn event(summary: &str, start: &str, end: &str) -> CalendarEvent {
+        CalendarEvent {
+            uid: Some("e1".to_string()),
+            summary: Some(summary.to_string()),
+            description: None,
+            recurring: false,
+            timing: Ok(EventTiming::Timed {
+                start: at(start),
+                end: at(end),
+            }),
+        }
+    }
+
+    #[test]
+    fn snap_to_slots_widens_to_half_hours() {
+        let (start, minutes) = snap_to_slots(at("2026-05-12 09:10:30"), at("2026-05-12 09:50:00"));
+        assert_eq!(start, at("2026-05-12 09:00:00"));
+        assert_eq!(minutes, 60);
+    }
+
+    #[test]
+    fn snap_to_slots_keeps_aligned_events() {
+        let (start, minutes) = snap_to_slots(at("2026-05-12 09:30:00"), at("2026-05-12 10:00:00"));
+        assert_eq!(start, at("2026-05-12 09:30:00"));
+        assert_eq!(minutes, 30);
+    }
+
+    #[test]
+    fn snap_to_slots_short_event_fills_one_slot() {
+        let (_, minutes) = snap_to_slots(at("2026-05-12 09:35:00"), at("2026-05-12 09:45:00"));
+        assert_eq!(minutes, 30);
+    }
+
+    #[test]
+    fn match_topic_prefers_longest_keyword_case_insensitively() {
+        let rules = vec![rule("sync", 1), rule("Team Sync", 2)];
+        let e = event(
+            "team sync: backend",
+            "2026-05-12 09:00:00",
+            "2026-05-12 09:30:00",
+        );
+        assert_eq!(match_topic(&e, &rules, None), Some(2));
+    }
+
+    #[test]
+    fn match_topic_falls_back_to_description_then_default() {
+        let rules = vec![rule("interview", 3)];
+        let mut e = event("Call", "2026-05-12 09:00:00", "2026-05-12 09:30:00");
+        assert_eq!(match_topic(&e, &rules, Some(9)), Some(9));
+        e.description = Some("Candidate interview loop".to_string());
+        assert_eq!(match_topic(&e, &rules, Some(9)), Some(3));
+        e.description = None;
+        assert_eq!(match_topic(&e, &rules, None), None);
+    }
+
+    #[test]
+    fn plan_event_creates_snapped_draft() {
+        let e = event("Standup", "2026-05-12 09:05:00", "2026-05-12 09:20:00");
+        let (imported, draft) = plan_event(&e, &[rule("standup", 4)], None);
+        let draft = draft.unwrap();
+        assert_eq!(imported.status, ImportEventStatus::WouldCreate);
+        assert_eq!(draft.start_time, at("2026-05-12 09:00:00"));
+        assert_eq!(draft.duration_minutes, 30);
+        assert_eq!(draft.topic_id, 4);
+        assert_eq!(draft.comment.as_deref(), Some("Standup"));
+    }
+
+    #[test]
+    fn plan_event_skips_events_without_topic() {
+        let e = event("Lunch", "2026-05-12 12:00:00", "2026-05-12 13:00:00");
+        let (imported, draft) = plan_event(&e, &[], None);
+        assert!(draft.is_none());
+        assert_eq!(imported.status, ImportEventStatus::Skipped);
+    }
+
+    #[test]
+    fn plan_event_skips_events_longer_than_a_day() {
+        let e = event("Offsite", "2026-05-12 09:00:00", "2026-05-14 09:00:00");
+        let (imported, draft) = plan_event(&e, &[], Some(1));
+        assert!(draft.is_none());
+        assert!(imported.reason.unwrap().contains("must not exceed"));
+    }
+
+    #[test]
+    fn plan_event_skips_recurring_and_all_day_events() {
+        let mut e = event("Standup", "2026-05-12 09:00:00", "2026-05-12 09:30:00");
+        e.recurring = true;
+        assert!(plan_event(&e, &[], Some(1)).1.is_none());
+
+        e.recurring = false;
+        e.timing = Ok(EventTiming::AllDay);
+        let (imported, draft) = plan_event(&e, &[], Some(1));
+        assert!(draft.is_none());
+        assert_eq!(
+            imported.reason.as_deref(),
+            Some("all-day events are not imported")
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::config::{Config, ConfigOverrides};
+use crate::email_templates::{DEFAULT_LOCALE, EmailTemplate, Templates};
+use crate::openapi::SpecVersion;
+
+/// Command line of the backend binary. Without a subcommand it serves, so
+/// existing `ExecStart=` lines keep working.
+#[derive(Parser, Debug)]
+#[command(
+    name = "daily-tracker-backend",
+    version,
+    about = "Daily Tracker backend"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Run the HTTP server (the default).
+    Serve,
+    /// Apply pending database migrations.
+    Migrate {
+        /// List the migrations that would run without applying them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show applied and pending database migrations.
+    Status,
+    /// Inspect the configuration.
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Work with email templates.
+    Email {
+        #[command(subcommand)]
+        action: EmailCommand,
+    },
+    /// Print the OpenAPI document, e.g. `openapi > ../openapi.json`.
+    Openapi {
+        /// Version to print; 3.0 is what the frontend's client generator reads.
+        #[arg(long, value_enum, default_value_t = SpecVersion::V3_1)]
+        spec_version: SpecVersion,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ConfigCommand {
+    /// Validate the configuration and print it with secrets masked.
+    Check,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum EmailCommand {
+    /// Render a template with sample data, using `email.template_dir`.
+    Preview {
+        #[arg(value_enum)]
+        template: EmailTemplate,
+        /// Locale or Accept-Language list to render for.
+        #[arg(long, default_value = DEFAULT_LOCALE)]
+        locale: String,
+        /// Print only one part, e.g. `--part html > preview.html`.
+        #[arg(long, value_enum)]
+        part: Option<EmailPart>,
+    },
+    /// Show queued email counts and every email not yet delivered.
+    Outbox {
+        /// Undelivered emails to list, newest first.
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Queue every failed email again with a fresh attempt budget.
+    RetryFailed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EmailPart {
+    Subject,
+    Text,
+    Html,
+}
+
+fn schema_newer_error(status: &db::MigrationStatus) -> String {
+    format!(
+        "database schema is newer than this binary (unknown migrations: {}); deploy a newer build",
+        status.unknown.join(", ")
+    )
+}
+
+/// `migrate [--dry-run]`.
+pub fn run_migrate(config: &Config, dry_run: bool) -> Result<(), String> {
+    let mut connection = db::migration_connection(config.database_url()?)?;
+    let status = db::migration_status(&mut connection)?;
+    if status.schema_is_newer() {
+        return Err(schema_newer_error(&status));
+    }
+    if status.pending.is_empty() {
+        println!("Database schema is up to date");
+        return Ok(());
+    }
+    if dry_run {
+        for version in &status.pending {
+            println!("Would apply {}", version);
+        }
+        return Ok(());
+    }
+    for version in db::run_pending_migrations(&mut connection)? {
+        println!("Applied {}", version);
+    }
+    Ok(())
+}
+
+/// `status`.
+pub fn run_status(config: &Config) -> Result<(), String> {
+    let mut connection = db::migration_connection(config.database_url()?)?;
+    let status = db::migration_status(&mut connection)?;
+    for version in &status.applied {
+        println!("applied  {}", version);
+    }
+    for version in &status.pending {
+        println!("pending  {}", version);
+    }
+    for version in &status.unknown {
+        println!("unknown  {} (applied, not in this binary)", version);
+    }
+    println!(
+        "{} applied, {} pending, {} unknown",
+        status.applied.len(),
+        status.pending.len(),
+        status.unknown.len()
+    );
+    Ok(())
+}
+
+/// `config check`.
+pub fn run_config_check(config: &Config) -> Result<(), String> {
+    print!("{}", config.to_redacted_toml());
+    match config.validate() {
+        Ok(()) => {
+            println!("# Configuration OK");
+            Ok(())
+        }
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("invalid: {}", error);
+            }
+            Err(format!("{} configuration error(s)", errors.len()))
+        }
+    }
+}
+
+/// `openapi`.
+pub fn run_openapi(spec_version: SpecVersion) -> Result<(), String> {
+    match spec_version {
+        SpecVersion::V3_1 => print!("{}", crate::openapi::spec_json()),
+        SpecVersion::V3_0 => print!("{}", crate::openapi::spec_json_3_0()),
+    }
+    Ok(())
+}
+
+/// `email preview`.
+pub fn run_email_preview(
+    config: &Config,
+    template: EmailTemplate,
+    locale: &str,
+    part: Option<EmailPart>,
+) -> Result<(), String> {
+    let templates = Templates::load(config.email.template_dir.as_deref())?;
+    let email = templates.render(template, locale, template.sample_context())?;
+    match part {
+        Some(EmailPart::Subject) => println!("{}", email.subject),
+        Some(EmailPart::Text) => print!("{}", email.text),
+        Some(EmailPart::Html) => print!("{}", email.html),
+        None => {
+            println!("Locale: {}", email.locale);
+            println!("Subject: {}", email.subject);
+            println!("\n--- text/plain ---\n{}", email.text);
+            println!("--- text/html ---\n{}", email.html);
+        }
+    }
+    Ok(())
+}
+
+/// `email outbox`.
+pub async fn run_email_outbox(config: &Config, limit: i64) -> Result<(), String> {
+    db::configure_pool(config.pool_config()?)?;
+    let counts = db::outbox_counts()
+        .await
+        .map_err(|e| format!("failed to read outbox: {}", e))?;
+    println!(
+        "{} pending, {} failed, {} sent",
+        counts.pending, counts.failed, counts.sent
+    );
+    let undelivered = db::list_undelivered_emails(limit)
+        .await
+        .map_err(|e| format!("failed to read outbox: {}", e))?;
+    for email in undelivered {
+        println!(
+            "{}  {:<7}  {}  {}  attempts={}  next={}  {}",
+            email.id,
+            email.status,
+            email.template,
+            email.recipient,
+            email.attempts,
+            email.next_attempt_at.format("%Y-%m-%d %H:%M:%S"),
+            email.last_error.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+/// `email retry-failed`.
+pub async fn run_email_retry_failed(config: &Config) -> Result<(), String> {
+    db::configure_pool(config.pool_config()?)?;
+    let requeued = db::retry_failed_emails()
+        .await
+        .map_err(|e| format!("failed to requeue emails: {}", e))?;
+    println!("Requeued {} failed email(s)", requeued);
+    Ok(())
+}
+
+/// Schema check before serving. A schema newer than the binary always stops
+/// startup. Pending migrations are applied with `database.auto_migrate` and
+/// only logged otherwise; `/readyz` keeps reporting them until they are
+/// applied.
+pub fn prepare_schema(config: &Config) -> Result<(), String> {
+    let auto_migrate = config.database.auto_migrate;
+    let mut connection = match db::migration_connection(config.database_url()?) {
+        Ok(connection) => connection,
+        Err(e) if !auto_migrate => {
+            log::warn!("Skipping startup schema check: {}", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let status = db::migration_status(&mut connection)?;
+    if status.schema_is_newer() {
+        return Err(schema_newer_error(&status));
+    }
+    if status.pending.is_empty() {
+        return Ok(());
+    }
+    if auto_migrate {
+        for version in db::run_pending_migrations(&mut connection)? {
+            log::info!("Applied migration {}", version);
+        }
+    } else {
+        log::warn!(
+            "{} pending migration(s): {}; run `migrate` or enable auto-migrate",
+            status.pending.len(),
+            status.pending.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_subcommand_serves() {
+        let cli = Cli::try_parse_from(["daily-tracker-backend"]).unwrap();
+        assert_eq!(cli.command, None);
+        assert!(!cli.overrides.auto_migrate);
+    }
+
+    #[test]
+    fn serve_accepts_auto_migrate() {
+        let cli =
+            Cli::try_parse_from(["daily-tracker-backend", "serve", "--auto-migrate"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Serve));
+        assert!(cli.overrides.auto_migrate);
+    }
+
+    #[test]
+    fn migrate_accepts_dry_run() {
+        let cli = Cli::try_parse_from(["daily-tracker-backend", "migrate", "--dry-run"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Migrate { dry_run: true }));
+    }
+
+    #[test]
+    fn config_flags_are_accepted_around_subcommands() {
+        let cli = Cli::try_parse_from([
+            "daily-tracker-backend",
+            "--config",
+            "/etc/daily-tracker/backend.toml",
+            "config",
+            "check",
+            "--bind-addr",
+            "0.0.0.0:9000",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Config {
+                action: ConfigCommand::Check
+            })
+        );
+        assert_eq!(cli.overrides.bind_addr.as_deref(), Some("0.0.0.0:9000"));
+        assert!(cli.overrides.config_path.is_some());
+    }
+
+    #[test]
+    fn config_flags_are_accepted_without_subcommand() {
+        let cli =
+            Cli::try_parse_from(["daily-tracker-backend", "--bind-addr", "0.0.0.0:9000"]).unwrap();
+        assert_eq!(cli.command, None);
+        assert_eq!(cli.overrides.bind_addr.as_deref(), Some("0.0.0.0:9000"));
+    }
+
+    #[test]
+    fn email_preview_parses_template_and_options() {
+        let cli = Cli::try_parse_from([
+            "daily-tracker-backend",
+            "email",
+            "preview",
+            "verification",
+            "--locale",
+            "de",
+            "--part",
+            "html",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Email {
+                action: EmailCommand::Preview {
+                    template: EmailTemplate::Verification,
+                    locale: "de".to_string(),
+                    part: Some(EmailPart::Html),
+                }
+            })
+        );
+        assert!(
+            Cli::try_parse_from(["daily-tracker-backend", "email", "preview", "welcome"]).is_err()
+        );
+    }
+
+    #[test]
+    fn email_outbox_has_a_default_limit() {
+        let cli = Cli::try_parse_from(["daily-tracker-backend", "email", "outbox"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Email {
+                action: EmailCommand::Outbox { limit: 50 }
+            })
+        );
+    }
+
+    #[test]
+    fn openapi_is_a_subcommand() {
+        let cli = Cli::try_parse_from(["daily-tracker-backend", "openapi"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Openapi {
+                spec_version: SpecVersion::V3_1
+            })
+        );
+        let cli =
+            Cli::try_parse_from(["daily-tracker-backend", "openapi", "--spec-version", "3.0"])
+                .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Openapi {
+                spec_version: SpecVersion::V3_0
+            })
+        );
+    }
+
+    #[test]
+    fn status_takes_no_arguments() {
+        assert!(Cli::try_parse_from(["daily-tracker-backend", "status", "--dry-run"]).is_err());
+    }
+}
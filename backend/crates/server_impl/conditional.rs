@@ -0,0 +1,156 @@
+//! ETags and conditional requests. Topics and tracks are tagged with their
+//! row version, which every update bumps. A list has no version of its own,
+//! so list responses are tagged with a hash of the body.
+
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use models::ApiError;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Strong ETag of a row at `version`.
+pub(crate) fn version_etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// The comma-separated entity tags of every `name` header. `None` when the
+/// header is absent.
+fn listed_tags(headers: &HeaderMap, name: header::HeaderName) -> Option<Vec<String>> {
+    if !headers.contains_key(&name) {
+        return None;
+    }
+    Some(
+        headers
+            .get_all(&name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+    )
+}
+
+/// The versions a write is conditional on, from `If-Match`. `None` when the
+/// header is absent or `*`, which only asks that the record exists. `If-Match`
+/// compares strongly, so weak and unknown tags name no version, and a header
+/// made only of them never matches.
+pub(crate) fn if_match(headers: &HeaderMap) -> Option<Vec<i32>> {
+    let tags = listed_tags(headers, header::IF_MATCH)?;
+    if tags.iter().any(|tag| tag == "*") {
+        return None;
+    }
+    Some(
+        tags.iter()
+            .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+            .collect(),
+    )
+}
+
+/// Whether `If-None-Match` names `etag`. Compares weakly, as RFC 9110 asks.
+fn none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.strip_prefix("W/").unwrap_or(tag).to_string();
+    listed_tags(headers, header::IF_NONE_MATCH)
+        .unwrap_or_default()
+        .iter()
+        .any(|tag| tag == "*" || opaque(tag) == opaque(etag))
+}
+
+fn respond(headers: &HeaderMap, etag: String, body: Vec<u8>) -> Response {
+    if none_match(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (
+        [
+            (header::ETAG, etag),
+            (header::CONTENT_TYPE, "application/json".to_string()),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+fn to_json<T: Serialize>(body: &T) -> Result<Vec<u8>, ApiError> {
+    serde_json::to_vec(body).map_err(|e| {
+        log::error!("Failed to serialize response: {}", e);
+        ApiError::InternalServerError("Internal server error".to_string())
+    })
+}
+
+/// Answers a GET with `body` tagged `etag`, or with `304 Not Modified` when
+/// the client already holds it.
+pub(crate) fn tagged_json<T: Serialize>(
+    headers: &HeaderMap,
+    etag: String,
+    body: &T,
+) -> Result<Response, ApiError> {
+    Ok(respond(headers, etag, to_json(body)?))
+}
+
+/// Like `tagged_json`, with a weak ETag hashed from the serialized body.
+pub(crate) fn hashed_json<T: Serialize>(
+    headers: &HeaderMap,
+    body: &T,
+) -> Result<Response, ApiError> {
+    let body = to_json(body)?;
+    let etag = format!("W/\"{}\"", hex::encode(&Sha256::digest(&body)[..16]));
+    Ok(respond(headers, etag, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn ok(result: Result<Response, ApiError>) -> Response {
+        result.unwrap_or_else(|_| panic!("the body should serialize"))
+    }
+
+    fn headers(name: header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn if_match_lists_strong_versions() {
+        assert_eq!(if_match(&HeaderMap::new()), None);
+        assert_eq!(if_match(&headers(header::IF_MATCH, "*")), None);
+        assert_eq!(
+            if_match(&headers(header::IF_MATCH, "\"3\", \"4\"")),
+            Some(vec![3, 4])
+        );
+        // A weak or foreign tag names no version, so the write must fail.
+        assert_eq!(
+            if_match(&headers(header::IF_MATCH, "W/\"3\", \"abc\"")),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn unchanged_bodies_answer_304() {
+        let body = vec!["a", "b"];
+        let first = ok(hashed_json(&HeaderMap::new(), &body));
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].clone();
+
+        let mut conditional = HeaderMap::new();
+        conditional.insert(header::IF_NONE_MATCH, etag.clone());
+        let second = ok(hashed_json(&conditional, &body));
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(second.headers()[header::ETAG], etag);
+
+        let changed = ok(hashed_json(&conditional, &vec!["a"]));
+        assert_eq!(changed.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        let response = ok(tagged_json(
+            &headers(header::IF_NONE_MATCH, "\"1\", W/\"2\""),
+            version_etag(2),
+            &"topic",
+        ));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+}
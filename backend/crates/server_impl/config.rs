@@ -0,0 +1,897 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use axum::http::HeaderValue;
+use clap::Args;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+/// Shown instead of secrets by `config check`.
+const REDACTED: &str = "<redacted>";
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// Backend configuration. Every field has a default except the database URL
+/// and the JWT secret; a TOML file, environment variables and command-line
+/// flags override the defaults in that order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub email: EmailConfig,
+    pub metrics: MetricsConfig,
+    pub tls: TlsConfig,
+    pub logging: LoggingConfig,
+    pub tracing: TracingConfig,
+    pub ids: IdsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_addr: String,
+    pub cors_origin: String,
+    /// Address users reach the backend at, used for links in emails.
+    pub public_url: String,
+    /// Upper bound on draining requests and background tasks at shutdown.
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_addr: "127.0.0.1:8080".to_string(),
+            cors_origin: "http://localhost:5173".to_string(),
+            public_url: "http://localhost:8080".to_string(),
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_idle: u32,
+    pub connection_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub max_lifetime_secs: u64,
+    /// Apply pending migrations when `serve` starts.
+    pub auto_migrate: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: None,
+            max_connections: 8,
+            min_idle: 1,
+            connection_timeout_secs: 10,
+            idle_timeout_secs: 300,
+            max_lifetime_secs: 1800,
+            auto_migrate: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub jwt_secret: Option<String>,
+    pub jwt_lifetime_hours: u32,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: None,
+            jwt_lifetime_hours: 7 * 24,
+        }
+    }
+}
+
+/// How outgoing mail leaves the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTransportKind {
+    /// Look up the recipient's MX host and deliver to it directly.
+    Direct,
+    /// Send everything through `smtp_host` with STARTTLS.
+    Relay,
+    /// Write messages into the maildir at `file_dir` instead of sending them.
+    File,
+    /// Keep messages in memory; nothing is delivered.
+    Memory,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    pub sender: String,
+    pub transport: EmailTransportKind,
+    /// Port on the MX host (`direct`) or the relay (`relay`, usually 587).
+    pub smtp_port: u16,
+    pub smtp_host: Option<String>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub file_dir: Option<PathBuf>,
+    /// Overrides for the built-in email templates, laid out like
+    /// `templates/email`.
+    pub template_dir: Option<PathBuf>,
+    /// Delivery attempts before a queued email is marked failed.
+    pub max_attempts: u32,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            sender: "noreply@dailytracker.app".to_string(),
+            transport: EmailTransportKind::Direct,
+            smtp_port: 25,
+            smtp_host: None,
+            smtp_username: None,
+            smtp_password: None,
+            file_dir: None,
+            template_dir: None,
+            max_attempts: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Private listener for `/metrics`.
+    pub bind_addr: Option<String>,
+    /// Bearer token required to scrape `/metrics`.
+    pub token: Option<String>,
+}
+
+/// TLS termination in the backend. Without a certificate and key the server
+/// speaks plain HTTP and expects a proxy in front of it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain, reread on SIGHUP.
+    pub cert_path: Option<PathBuf>,
+    /// PEM PKCS#8 private key, reread on SIGHUP.
+    pub key_path: Option<PathBuf>,
+    /// Plain-HTTP listener that redirects every request to HTTPS.
+    pub redirect_http_addr: Option<String>,
+}
+
+/// Shape of each log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormatKind {
+    /// Human-readable text.
+    Text,
+    /// One JSON object per line with the request fields as keys.
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub dir: String,
+    pub format: LogFormatKind,
+    /// Write daily files into `dir`. Off logs to stderr only, for
+    /// deployments where journald keeps the logs.
+    pub file: bool,
+    /// Delete daily files older than this; 0 keeps them forever.
+    pub max_age_days: u32,
+    /// Delete the oldest daily files while all of them take up more than
+    /// this; 0 means no limit.
+    pub max_total_size_mb: u64,
+    /// Gzip daily files once their day is over.
+    pub compress: bool,
+    /// Requests taking at least this long are logged as warnings; 0 never.
+    pub slow_request_ms: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            dir: logging::DEFAULT_LOG_DIR.to_string(),
+            format: LogFormatKind::Text,
+            file: true,
+            max_age_days: 30,
+            max_total_size_mb: 0,
+            compress: false,
+            slow_request_ms: 1000,
+        }
+    }
+}
+
+/// OpenTelemetry tracing. Off, `traceparent` headers are ignored and no spans
+/// are recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingConfig {
+    pub enabled: bool,
+    /// Base URL of the OTLP/HTTP collector; spans go to `/v1/traces` below it.
+    pub otlp_endpoint: String,
+    pub service_name: String,
+    /// Share of new traces to record, from 0 to 1. Requests arriving with a
+    /// `traceparent` follow the caller's decision.
+    pub sample_ratio: f64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        TracingConfig {
+            enabled: false,
+            otlp_endpoint: "http://localhost:4318".to_string(),
+            service_name: "daily-tracker-backend".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdsConfig {
+    /// Node id mixed into snowflake ids; random when unset. Give every
+    /// instance sharing a database its own value.
+    pub snowflake_node_id: Option<u16>,
+}
+
+/// Command-line flags, each also readable from the environment variable the
+/// backend has always used. Set values win over the config file.
+#[derive(Args, Debug, Default, Clone, PartialEq)]
+pub struct ConfigOverrides {
+    /// TOML configuration file.
+    #[arg(
+        long = "config",
+        global = true,
+        env = "DAILY_TRACKER_CONFIG",
+        value_name = "PATH"
+    )]
+    pub config_path: Option<PathBuf>,
+    #[arg(long, global = true, env = "BIND_ADDR")]
+    pub bind_addr: Option<String>,
+    #[arg(long, global = true, env = "CORS_ORIGIN")]
+    pub cors_origin: Option<String>,
+    #[arg(long, global = true, env = "PUBLIC_URL")]
+    pub public_url: Option<String>,
+    #[arg(long, global = true, env = "SHUTDOWN_TIMEOUT_SECS")]
+    pub shutdown_timeout_secs: Option<u64>,
+    #[arg(long, global = true, env = "DATABASE_URL", hide_env_values = true)]
+    pub database_url: Option<String>,
+    /// Apply pending migrations when `serve` starts.
+    #[arg(long, global = true, env = "AUTO_MIGRATE")]
+    pub auto_migrate: bool,
+    #[arg(long, global = true, env = "DB_MAX_CONNECTIONS")]
+    pub db_max_connections: Option<u32>,
+    #[arg(long, global = true, env = "DB_MIN_IDLE")]
+    pub db_min_idle: Option<u32>,
+    #[arg(long, global = true, env = "DB_CONNECTION_TIMEOUT_SECS")]
+    pub db_connection_timeout_secs: Option<u64>,
+    #[arg(long, global = true, env = "JWT_SECRET", hide_env_values = true)]
+    pub jwt_secret: Option<String>,
+    #[arg(long, global = true, env = "JWT_LIFETIME_HOURS")]
+    pub jwt_lifetime_hours: Option<u32>,
+    #[arg(long, global = true, env = "SENDER_EMAIL")]
+    pub sender_email: Option<String>,
+    #[arg(long, global = true, env = "EMAIL_TRANSPORT", value_enum)]
+    pub email_transport: Option<EmailTransportKind>,
+    #[arg(long, global = true, env = "SMTP_PORT")]
+    pub smtp_port: Option<u16>,
+    #[arg(long, global = true, env = "SMTP_HOST")]
+    pub smtp_host: Option<String>,
+    #[arg(long, global = true, env = "SMTP_USERNAME")]
+    pub smtp_username: Option<String>,
+    #[arg(long, global = true, env = "SMTP_PASSWORD", hide_env_values = true)]
+    pub smtp_password: Option<String>,
+    #[arg(long, global = true, env = "EMAIL_FILE_DIR")]
+    pub email_file_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "EMAIL_TEMPLATE_DIR")]
+    pub email_template_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "EMAIL_MAX_ATTEMPTS")]
+    pub email_max_attempts: Option<u32>,
+    #[arg(long, global = true, env = "METRICS_BIND_ADDR")]
+    pub metrics_bind_addr: Option<String>,
+    #[arg(long, global = true, env = "METRICS_TOKEN", hide_env_values = true)]
+    pub metrics_token: Option<String>,
+    #[arg(long, global = true, env = "TLS_CERT_PATH")]
+    pub tls_cert_path: Option<PathBuf>,
+    #[arg(long, global = true, env = "TLS_KEY_PATH")]
+    pub tls_key_path: Option<PathBuf>,
+    #[arg(long, global = true, env = "TLS_REDIRECT_HTTP_ADDR")]
+    pub tls_redirect_http_addr: Option<String>,
+    #[arg(long, global = true, env = "DAILY_TRACKER_LOG_DIR")]
+    pub log_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "LOG_FORMAT", value_enum)]
+    pub log_format: Option<LogFormatKind>,
+    /// `false` logs to stderr only.
+    #[arg(long, global = true, env = "LOG_FILE", value_name = "BOOL")]
+    pub log_file: Option<bool>,
+    #[arg(long, global = true, env = "LOG_MAX_AGE_DAYS")]
+    pub log_max_age_days: Option<u32>,
+    #[arg(long, global = true, env = "LOG_MAX_TOTAL_SIZE_MB")]
+    pub log_max_total_size_mb: Option<u64>,
+    #[arg(long, global = true, env = "LOG_COMPRESS", value_name = "BOOL")]
+    pub log_compress: Option<bool>,
+    #[arg(long, global = true, env = "LOG_SLOW_REQUEST_MS")]
+    pub log_slow_request_ms: Option<u64>,
+    #[arg(long, global = true, env = "TRACING_ENABLED", value_name = "BOOL")]
+    pub tracing_enabled: Option<bool>,
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+    #[arg(long, global = true, env = "OTEL_SERVICE_NAME")]
+    pub otel_service_name: Option<String>,
+    #[arg(long, global = true, env = "TRACING_SAMPLE_RATIO")]
+    pub tracing_sample_ratio: Option<f64>,
+    #[arg(long, global = true, env = "SNOWFLAKE_NODE_ID")]
+    pub snowflake_node_id: Option<u16>,
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+fn set_some<T>(target: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *target = value;
+    }
+}
+
+impl Config {
+    /// Reads the config file named in `overrides`, if any, and applies the
+    /// overrides on top. Does not validate.
+    pub fn load(overrides: &ConfigOverrides) -> Result<Config, String> {
+        let mut config = match &overrides.config_path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+                Config::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            None => Config::default(),
+        };
+        config.apply(overrides.clone());
+        Ok(config)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    fn apply(&mut self, o: ConfigOverrides) {
+        set(&mut self.server.bind_addr, o.bind_addr);
+        set(&mut self.server.cors_origin, o.cors_origin);
+        set(&mut self.server.public_url, o.public_url);
+        set(
+            &mut self.server.shutdown_timeout_secs,
+            o.shutdown_timeout_secs,
+        );
+        set_some(&mut self.database.url, o.database_url);
+        set(&mut self.database.max_connections, o.db_max_connections);
+        set(&mut self.database.min_idle, o.db_min_idle);
+        if o.auto_migrate {
+            self.database.auto_migrate = true;
+        }
+        set(
+            &mut self.database.connection_timeout_secs,
+            o.db_connection_timeout_secs,
+        );
+        set_some(&mut self.auth.jwt_secret, o.jwt_secret);
+        set(&mut self.auth.jwt_lifetime_hours, o.jwt_lifetime_hours);
+        set(&mut self.email.sender, o.sender_email);
+        set(&mut self.email.transport, o.email_transport);
+        set(&mut self.email.smtp_port, o.smtp_port);
+        set_some(&mut self.email.smtp_host, o.smtp_host);
+        set_some(&mut self.email.smtp_username, o.smtp_username);
+        set_some(&mut self.email.smtp_password, o.smtp_password);
+        set_some(&mut self.email.file_dir, o.email_file_dir);
+        set_some(&mut self.email.template_dir, o.email_template_dir);
+        set(&mut self.email.max_attempts, o.email_max_attempts);
+        set_some(&mut self.metrics.bind_addr, o.metrics_bind_addr);
+        set_some(&mut self.metrics.token, o.metrics_token);
+        set_some(&mut self.tls.cert_path, o.tls_cert_path);
+        set_some(&mut self.tls.key_path, o.tls_key_path);
+        set_some(&mut self.tls.redirect_http_addr, o.tls_redirect_http_addr);
+        set(
+            &mut self.logging.dir,
+            o.log_dir.map(|d| d.to_string_lossy().into_owned()),
+        );
+        set(&mut self.logging.format, o.log_format);
+        set(&mut self.logging.file, o.log_file);
+        set(&mut self.logging.max_age_days, o.log_max_age_days);
+        set(&mut self.logging.max_total_size_mb, o.log_max_total_size_mb);
+        set(&mut self.logging.compress, o.log_compress);
+        set(&mut self.logging.slow_request_ms, o.log_slow_request_ms);
+        set(&mut self.tracing.enabled, o.tracing_enabled);
+        set(&mut self.tracing.otlp_endpoint, o.otlp_endpoint);
+        set(&mut self.tracing.service_name, o.otel_service_name);
+        set(&mut self.tracing.sample_ratio, o.tracing_sample_ratio);
+        set_some(&mut self.ids.snowflake_node_id, o.snowflake_node_id);
+    }
+
+    /// The database URL, which every subcommand needs.
+    pub fn database_url(&self) -> Result<&str, String> {
+        self.database
+            .url
+            .as_deref()
+            .filter(|u| !u.trim().is_empty())
+            .ok_or_else(|| "database.url (DATABASE_URL) must be set".to_string())
+    }
+
+    /// Checks everything `serve` relies on and returns every problem found,
+    /// so one `config check` run lists them all.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.server.bind_addr.parse::<SocketAddr>().is_err() {
+            errors.push(format!(
+                "server.bind_addr '{}' is not a socket address such as 127.0.0.1:8080",
+                self.server.bind_addr
+            ));
+        }
+        if self.server.cors_origin.is_empty()
+            || self.server.cors_origin.parse::<HeaderValue>().is_err()
+        {
+            errors.push(format!(
+                "server.cors_origin '{}' is not a valid origin",
+                self.server.cors_origin
+            ));
+        }
+        let public_url = self.server.public_url.as_str();
+        if !(public_url.starts_with("http://") || public_url.starts_with("https://"))
+            || public_url.contains(char::is_whitespace)
+        {
+            errors.push(format!(
+                "server.public_url '{}' must be an http:// or https:// URL",
+                public_url
+            ));
+        }
+
+        match self.database_url() {
+            Ok(url) if !url.starts_with("mysql://") => {
+                errors.push("database.url must start with mysql://".to_string())
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(e),
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.min_idle > self.database.max_connections {
+            errors.push(format!(
+                "database.min_idle ({}) must not exceed database.max_connections ({})",
+                self.database.min_idle, self.database.max_connections
+            ));
+        }
+        if self.database.connection_timeout_secs == 0 {
+            errors.push("database.connection_timeout_secs must be at least 1".to_string());
+        }
+
+        if self
+            .auth
+            .jwt_secret
+            .as_deref()
+            .is_none_or(|s| s.trim().is_empty())
+        {
+            errors.push("auth.jwt_secret (JWT_SECRET) must be set".to_string());
+        }
+        if self.auth.jwt_lifetime_hours == 0 {
+            errors.push("auth.jwt_lifetime_hours must be at least 1".to_string());
+        }
+
+        if self
+            .email
+            .sender
+            .parse::<lettre::message::Mailbox>()
+            .is_err()
+        {
+            errors.push(format!(
+                "email.sender '{}' is not a valid address",
+                self.email.sender
+            ));
+        }
+        if self.email.smtp_port == 0 {
+            errors.push("email.smtp_port must not be 0".to_string());
+        }
+        match self.email.transport {
+            EmailTransportKind::Relay
+                if self
+                    .email
+                    .smtp_host
+                    .as_deref()
+                    .is_none_or(|h| h.trim().is_empty()) =>
+            {
+                errors.push(
+                    "email.smtp_host (SMTP_HOST) must be set for the relay transport".to_string(),
+                );
+            }
+            EmailTransportKind::File if self.email.file_dir.is_none() => {
+                errors.push(
+                    "email.file_dir (EMAIL_FILE_DIR) must be set for the file transport"
+                        .to_string(),
+                );
+            }
+            _ => {}
+        }
+        if self.email.smtp_username.is_some() != self.email.smtp_password.is_some() {
+            errors.push(
+                "email.smtp_username and email.smtp_password must be set together".to_string(),
+            );
+        }
+
+        if self.email.max_attempts == 0 {
+            errors.push("email.max_attempts must be at least 1".to_string());
+        }
+        if let Some(dir) = &self.email.template_dir
+            && let Err(e) = crate::email_templates::Templates::load(Some(dir))
+        {
+            errors.push(format!("email.template_dir: {}", e));
+        }
+
+        if let Some(addr) = &self.metrics.bind_addr
+            && addr.parse::<SocketAddr>().is_err()
+        {
+            errors.push(format!(
+                "metrics.bind_addr '{}' is not a socket address",
+                addr
+            ));
+        }
+        if self
+            .metrics
+            .token
+            .as_deref()
+            .is_some_and(|t| t.trim().is_empty())
+        {
+            errors.push("metrics.token must not be empty when set".to_string());
+        }
+
+        match (&self.tls.cert_path, &self.tls.key_path) {
+            (Some(cert), Some(key)) => {
+                if let Err(e) = crate::tls::load_acceptor(cert, key) {
+                    errors.push(format!("tls: {}", e));
+                }
+            }
+            (None, None) => {
+                if self.tls.redirect_http_addr.is_some() {
+                    errors.push(
+                        "tls.redirect_http_addr needs tls.cert_path and tls.key_path".to_string(),
+                    );
+                }
+            }
+            _ => errors.push("tls.cert_path and tls.key_path must be set together".to_string()),
+        }
+        if let Some(addr) = &self.tls.redirect_http_addr
+            && addr.parse::<SocketAddr>().is_err()
+        {
+            errors.push(format!(
+                "tls.redirect_http_addr '{}' is not a socket address",
+                addr
+            ));
+        }
+
+        if self.logging.file && self.logging.dir.trim().is_empty() {
+            errors.push("logging.dir must not be empty when logging.file is on".to_string());
+        }
+        if self.tracing.enabled {
+            let endpoint = self.tracing.otlp_endpoint.as_str();
+            if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+                errors.push(format!(
+                    "tracing.otlp_endpoint '{}' must be an http:// or https:// URL",
+                    endpoint
+                ));
+            }
+            if self.tracing.service_name.trim().is_empty() {
+                errors.push("tracing.service_name must not be empty".to_string());
+            }
+        }
+        if !(0.0..=1.0).contains(&self.tracing.sample_ratio) {
+            errors.push(format!(
+                "tracing.sample_ratio {} must be between 0 and 1",
+                self.tracing.sample_ratio
+            ));
+        }
+        if self
+            .ids
+            .snowflake_node_id
+            .is_some_and(|id| id >= utils::snowflake::MAX_NODE_ID_EXCLUSIVE)
+        {
+            errors.push(format!(
+                "ids.snowflake_node_id must be below {}",
+                utils::snowflake::MAX_NODE_ID_EXCLUSIVE
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// `server.public_url` joined with an absolute `path`.
+    pub fn public_link(&self, path: &str) -> String {
+        format!("{}{}", self.server.public_url.trim_end_matches('/'), path)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.shutdown_timeout_secs)
+    }
+
+    pub fn log_options(&self) -> logging::LogOptions {
+        let logging = &self.logging;
+        logging::LogOptions {
+            format: match logging.format {
+                LogFormatKind::Text => logging::LogFormat::Text,
+                LogFormatKind::Json => logging::LogFormat::Json,
+            },
+            dir: logging.file.then(|| PathBuf::from(&logging.dir)),
+            retention: logging::Retention {
+                max_age_days: Some(logging.max_age_days).filter(|&d| d > 0),
+                max_total_bytes: Some(logging.max_total_size_mb)
+                    .filter(|&mb| mb > 0)
+                    .map(|mb| mb * 1024 * 1024),
+                compress: logging.compress,
+            },
+        }
+    }
+
+    /// Threshold above which a request is logged as slow.
+    pub fn slow_request_threshold(&self) -> Option<Duration> {
+        Some(self.logging.slow_request_ms)
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    pub fn pool_config(&self) -> Result<db::PoolConfig, String> {
+        Ok(db::PoolConfig {
+            database_url: self.database_url()?.to_string(),
+            max_connections: self.database.max_connections,
+            min_idle: self.database.min_idle,
+            connection_timeout: Duration::from_secs(self.database.connection_timeout_secs),
+            idle_timeout: Duration::from_secs(self.database.idle_timeout_secs),
+            max_lifetime: Duration::from_secs(self.database.max_lifetime_secs),
+        })
+    }
+
+    /// The effective configuration as TOML, with secrets masked.
+    pub fn to_redacted_toml(&self) -> String {
+        let mut shown = self.clone();
+        let mask = |value: &mut Option<String>| {
+            if value.is_some() {
+                *value = Some(REDACTED.to_string());
+            }
+        };
+        mask(&mut shown.database.url);
+        mask(&mut shown.auth.jwt_secret);
+        mask(&mut shown.email.smtp_password);
+        mask(&mut shown.metrics.token);
+        toml::to_string(&shown).unwrap_or_else(|e| format!("# failed to render: {}\n", e))
+    }
+
+    #[cfg(test)]
+    fn for_tests() -> Config {
+        let mut config = Config::default();
+        config.auth.jwt_secret = Some("test_secret_key_for_unit_tests".to_string());
+        config
+    }
+}
+
+/// Installs the configuration for the rest of the process. Called once from
+/// `main` after validation.
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        log::warn!("Configuration was already initialised; keeping the first one");
+    }
+}
+
+/// The configuration installed by `init`. Tests get a default configuration
+/// with a test JWT secret.
+pub fn get() -> &'static Config {
+    #[cfg(test)]
+    {
+        CONFIG.get_or_init(Config::for_tests)
+    }
+    #[cfg(not(test))]
+    {
+        CONFIG
+            .get()
+            .expect("configuration is initialised in main before it is read")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid() -> Config {
+        let mut config = Config::for_tests();
+        config.database.url = Some("mysql://u:p@localhost/dailytracker".to_string());
+        config
+    }
+
+    #[test]
+    fn defaults_match_previous_behaviour() {
+        let config = Config::default();
+        assert_eq!(config.server.bind_addr, "127.0.0.1:8080");
+        assert_eq!(config.server.cors_origin, "http://localhost:5173");
+        assert_eq!(config.database.max_connections, 8);
+        assert_eq!(config.auth.jwt_lifetime_hours, 168);
+        assert_eq!(config.email.smtp_port, 25);
+    }
+
+    #[test]
+    fn from_toml_reads_partial_files() {
+        let config = Config::from_toml(
+            r#"
+            [server]
+            bind_addr = "0.0.0.0:9000"
+
+            [database]
+            url = "mysql://u:p@db/dailytracker"
+            max_connections = 16
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.bind_addr, "0.0.0.0:9000");
+        assert_eq!(config.server.cors_origin, "http://localhost:5173");
+        assert_eq!(config.database.max_connections, 16);
+        assert_eq!(config.database.min_idle, 1);
+    }
+
+    #[test]
+    fn from_toml_rejects_unknown_keys() {
+        let err = Config::from_toml("[server]\nbind_adr = \"x\"\n").unwrap_err();
+        assert!(err.contains("bind_adr"));
+    }
+
+    #[test]
+    fn overrides_win_over_file_values() {
+        let mut config = Config::from_toml("[server]\nbind_addr = \"0.0.0.0:9000\"\n").unwrap();
+        config.apply(ConfigOverrides {
+            bind_addr: Some("127.0.0.1:7000".to_string()),
+            jwt_secret: Some("s3cret".to_string()),
+            ..ConfigOverrides::default()
+        });
+        assert_eq!(config.server.bind_addr, "127.0.0.1:7000");
+        assert_eq!(config.auth.jwt_secret.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn validate_accepts_complete_config() {
+        assert_eq!(valid().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let mut config = Config::default();
+        config.server.bind_addr = "localhost".to_string();
+        config.database.min_idle = 9;
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("server.bind_addr")));
+        assert!(errors.iter().any(|e| e.contains("database.url")));
+        assert!(errors.iter().any(|e| e.contains("database.min_idle")));
+        assert!(errors.iter().any(|e| e.contains("auth.jwt_secret")));
+    }
+
+    #[test]
+    fn public_link_joins_without_double_slashes() {
+        let mut config = valid();
+        config.server.public_url = "https://api.example.com/".to_string();
+        assert_eq!(
+            config.public_link("/api/v1/digest/unsubscribe/abc"),
+            "https://api.example.com/api/v1/digest/unsubscribe/abc"
+        );
+        config.server.public_url = "api.example.com".to_string();
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("server.public_url")));
+    }
+
+    #[test]
+    fn log_options_follow_logging_settings() {
+        let mut config = Config::from_toml(
+            "[logging]\nformat = \"json\"\nmax_total_size_mb = 2\ncompress = true\n",
+        )
+        .unwrap();
+        let options = config.log_options();
+        assert_eq!(options.format, logging::LogFormat::Json);
+        assert_eq!(options.dir, Some(PathBuf::from(logging::DEFAULT_LOG_DIR)));
+        assert_eq!(
+            options.retention,
+            logging::Retention {
+                max_age_days: Some(30),
+                max_total_bytes: Some(2 * 1024 * 1024),
+                compress: true,
+            }
+        );
+
+        config.apply(ConfigOverrides {
+            log_file: Some(false),
+            log_max_age_days: Some(0),
+            ..ConfigOverrides::default()
+        });
+        let options = config.log_options();
+        assert_eq!(options.dir, None);
+        assert_eq!(options.retention.max_age_days, None);
+    }
+
+    #[test]
+    fn slow_request_threshold_is_off_at_zero() {
+        let mut config = Config::from_toml("").unwrap();
+        assert_eq!(
+            config.slow_request_threshold(),
+            Some(Duration::from_millis(1000))
+        );
+        config.apply(ConfigOverrides {
+            log_slow_request_ms: Some(0),
+            ..ConfigOverrides::default()
+        });
+        assert_eq!(config.slow_request_threshold(), None);
+    }
+
+    #[test]
+    fn validate_checks_tracing_only_when_enabled() {
+        let mut config = valid();
+        config.tracing.otlp_endpoint = "collector:4318".to_string();
+        assert_eq!(config.validate(), Ok(()));
+
+        config.apply(ConfigOverrides {
+            tracing_enabled: Some(true),
+            tracing_sample_ratio: Some(1.5),
+            ..ConfigOverrides::default()
+        });
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("tracing.otlp_endpoint")));
+        assert!(errors.iter().any(|e| e.contains("tracing.sample_ratio")));
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_node_id() {
+        let mut config = valid();
+        config.ids.snowflake_node_id = Some(1024);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_checks_email_transport_settings() {
+        let mut config = valid();
+        config.email.transport = EmailTransportKind::Relay;
+        config.email.smtp_username = Some("mailer".to_string());
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("email.smtp_host")));
+        assert!(errors.iter().any(|e| e.contains("email.smtp_password")));
+
+        config.email.smtp_host = Some("smtp.example.com".to_string());
+        config.email.smtp_password = Some("hunter2".to_string());
+        assert_eq!(config.validate(), Ok(()));
+        assert!(!config.to_redacted_toml().contains("hunter2"));
+    }
+
+    #[test]
+    fn validate_requires_tls_cert_and_key_together() {
+        let mut config = valid();
+        config.tls.cert_path = Some(PathBuf::from("/etc/daily-tracker/cert.pem"));
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("must be set together")));
+
+        let mut config = valid();
+        config.tls.redirect_http_addr = Some("0.0.0.0:80".to_string());
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("tls.redirect_http_addr")));
+    }
+
+    #[test]
+    fn redacted_toml_hides_secrets() {
+        let mut config = valid();
+        config.metrics.token = Some("scrape-me".to_string());
+        let text = config.to_redacted_toml();
+        assert!(!text.contains("test_secret_key_for_unit_tests"));
+        assert!(!text.contains("mysql://"));
+        assert!(!text.contains("scrape-me"));
+        assert!(text.contains(REDACTED));
+        assert!(Config::from_toml(&text).is_ok());
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use db::OutboxDraft;
+use db_model::models::{DailyTrack, DigestSubscription, Topic, User};
+use models::{DigestFrequency, GoalPeriod};
+use tokio_util::sync::CancellationToken;
+
+use crate::email_templates::{DigestEmail, DigestTopicLine, EmailTemplate};
+
+/// How often the scheduler looks for digests that are due.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Settings shown to users who have never saved any.
+pub(crate) const DEFAULT_SEND_TIME: &str = "08:00";
+pub(crate) const DEFAULT_TIME_ZONE: &str = "UTC";
+
+pub(crate) fn parse_time_zone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|_| format!("Unknown time zone '{}'", name))
+}
+
+pub(crate) fn parse_send_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| format!("Invalid send_time '{}'. Expected HH:MM", value))
+}
+
+/// Inclusive range of local days a digest reports on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Period {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Period {
+    /// The period of the same length right before this one.
+    pub(crate) fn previous(&self) -> Period {
+        let len = (self.end - self.start) + Duration::days(1);
+        Period {
+            start: self.start - len,
+            end: self.end - len,
+        }
+    }
+}
+
+/// When and where a user's digest goes out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Schedule {
+    pub frequency: DigestFrequency,
+    pub send_time: NaiveTime,
+    pub time_zone: Tz,
+}
+
+impl Schedule {
+    pub(crate) fn of(subscription: &DigestSubscription) -> Result<Schedule, String> {
+        Ok(Schedule {
+            frequency: DigestFrequency::parse(&subscription.frequency)
+                .ok_or_else(|| format!("Unknown digest frequency '{}'", subscription.frequency))?,
+            send_time: subscription.send_time,
+            time_zone: parse_time_zone(&subscription.time_zone)?,
+        })
+    }
+
+    /// The last complete period before the local day `today`: yesterday, or
+    /// the previous ISO week.
+    fn period_before(&self, today: NaiveDate) -> Period {
+        match self.frequency {
+            DigestFrequency::Daily => {
+                let yesterday = today - Duration::days(1);
+                Period {
+                    start: yesterday,
+                    end: yesterday,
+                }
+            }
+            DigestFrequency::Weekly => {
+                let (monday, _) = crate::goals::period_bounds(today, GoalPeriod::Week);
+                Period {
+                    start: monday - Duration::days(7),
+                    end: monday - Duration::days(1),
+                }
+            }
+        }
+    }
+
+    /// The most recent period whose digest is due at `now`. A period's
+    /// digest is due from `send_time` on the local day after it ends.
+    pub(crate) fn due_period(&self, now: DateTime<Utc>) -> Period {
+        let local_now = now.with_timezone(&self.time_zone).naive_local();
+        let period = self.period_before(local_now.date());
+        let send_at = (period.end + Duration::days(1)).and_time(self.send_time);
+        if local_now >= send_at {
+            period
+        } else {
+            period.previous()
+        }
+    }
+
+    /// UTC instant at which the local day `day` starts. Where a DST change
+    /// skips midnight, the day starts at the first local time that exists.
+    fn day_start_utc(&self, day: NaiveDate) -> NaiveDateTime {
+        let midnight = day.and_hms_opt(0, 0, 0).expect("valid midnight");
+        (0..=2)
+            .find_map(|hours| {
+                self.time_zone
+                    .from_local_datetime(&(midnight + Duration::hours(hours)))
+                    .earliest()
+            })
+            .map(|start| start.naive_utc())
+            .unwrap_or(midnight)
+    }
+
+    /// The period as a half-open UTC range, for comparing with track times.
+    pub(crate) fn utc_range(&self, period: Period) -> (NaiveDateTime, NaiveDateTime) {
+        (
+            self.day_start_utc(period.start),
+            self.day_start_utc(period.end + Duration::days(1)),
+        )
+    }
+}
+
+/// Sums tracked minutes per topic within `[start, end)`. Tracks crossing
+/// either edge only count the part inside.
+pub(crate) fn minutes_by_topic(
+    tracks: &[DailyTrack],
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> HashMap<i64, i64> {
+    let mut totals: HashMap<i64, i64> = HashMap::new();
+    for track in tracks {
+        let Some(topic_id) = track.topic_id else {
+            continue;
+        };
+        let track_end = track.start_time + Duration::minutes(i64::from(track.duration_minutes));
+        let overlap = (track_end.min(end) - track.start_time.max(start)).num_minutes();
+        if overlap > 0 {
+            *totals.entry(topic_id).or_default() += overlap;
+        }
+    }
+    totals
+}
+
+/// `135` becomes `2h 15m`.
+pub(crate) fn format_minutes(minutes: i64) -> String {
+    let (hours, rest) = (minutes.abs() / 60, minutes.abs() % 60);
+    match (hours, rest) {
+        (0, rest) => format!("{}m", rest),
+        (hours, 0) => format!("{}h", hours),
+        (hours, rest) => format!("{}h {}m", hours, rest),
+    }
+}
+
+/// Signed difference, e.g. `+1h 30m` or `-45m`.
+pub(crate) fn format_change(minutes: i64) -> String {
+    match minutes.signum() {
+        1 => format!("+{}", format_minutes(minutes)),
+        -1 => format!("-{}", format_minutes(minutes)),
+        _ => "±0m".to_string(),
+    }
+}
+
+/// The chosen topics with all their subtopics, or every topic when the user
+/// did not pick any.
+pub(crate) fn included_topics(topics: &[Topic], chosen: Option<&[i64]>) -> HashSet<i64> {
+    match chosen {
+        Some(chosen) => chosen
+            .iter()
+            .flat_map(|&id| crate::goals::goal_topic_ids(topics, id, true))
+            .collect(),
+        None => topics.iter().map(|t| t.id).collect(),
+    }
+}
+
+/// Builds the digest email for `period`, compared with the period before.
+/// Returns `None` when nothing was tracked on the included topics in either
+/// period; an empty summary is not worth an email.
+pub(crate) fn build_digest(
+    user: &User,
+    subscription: &DigestSubscription,
+    schedule: &Schedule,
+    period: Period,
+    topics: &[Topic],
+    tracks: &[DailyTrack],
+) -> Option<OutboxDraft> {
+    let chosen: Option<Vec<i64>> = subscription
+        .topic_ids
+        .as_deref()
+        .and_then(|ids| serde_json::from_str(ids).ok());
+    let included = included_topics(topics, chosen.as_deref());
+
+    let (start, end) = schedule.utc_range(period);
+    let current = minutes_by_topic(tracks, start, end);
+    let (previous_start, previous_end) = schedule.utc_range(period.previous());
+    let previous = minutes_by_topic(tracks, previous_start, previous_end);
+
+    let mut rows: Vec<(&Topic, i64, i64)> = topics
+        .iter()
+        .filter(|t| included.contains(&t.id))
+        .map(|t| {
+            let minutes = current.get(&t.id).copied().unwrap_or(0);
+            let previous_minutes = previous.get(&t.id).copied().unwrap_or(0);
+            (t, minutes, previous_minutes)
+        })
+        .filter(|(_, minutes, previous_minutes)| *minutes > 0 || *previous_minutes > 0)
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    rows.sort_by(|a, b| {
+        b.1.cmp(&a.1)
+            .then_with(|| a.0.topic_name.cmp(&b.0.topic_name))
+    });
+
+    let total: i64 = rows.iter().map(|r| r.1).sum();
+    let previous_total: i64 = rows.iter().map(|r| r.2).sum();
+    let email = DigestEmail {
+        username: user.username.clone(),
+        frequency: schedule.frequency.as_str(),
+        period_start: period.start.to_string(),
+        period_end: period.end.to_string(),
+        total: format_minutes(total),
+        total_change: format_change(total - previous_total),
+        topics: rows
+            .into_iter()
+            .map(|(topic, minutes, previous_minutes)| DigestTopicLine {
+                name: topic.topic_name.clone(),
+                duration: format_minutes(minutes),
+                previous_duration: format_minutes(previous_minutes),
+                change: format_change(minutes - previous_minutes),
+            })
+            .collect(),
+        unsubscribe_url: crate::config::get().public_link(&format!(
+            "/api/v1/digest/unsubscribe/{}",
+            subscription.unsubscribe_token
+        )),
+    };
+    Some(OutboxDraft {
+        recipient: user.email.clone(),
+        template: EmailTemplate::Digest.name().to_string(),
+        locale: user.locale.clone(),
+        payload: serde_json::to_string(&email).expect("digest values serialize"),
+    })
+}
+
+/// Queues the user's digest if one is due. Returns whether an email was
+/// queued.
+async fn send_due_digest(
+    subscription: DigestSubscription,
+    user: User,
+    now: DateTime<Utc>,
+) -> Result<bool, String> {
+    let schedule = Schedule::of(&subscription)?;
+    let period = schedule.due_period(now);
+    if subscription
+        .last_period_start
+        .is_some_and(|last| last >= period.start)
+    {
+        return Ok(false);
+    }
+
+    let topics = db::get_topics(None, Some(user.id))
+        .await
+        .map_err(|e| format!("failed to load topics: {}", e))?;
+    // A track may start the evening before the earlier period and run into
+    // it, so load one extra UTC day.
+    let (load_start, _) = schedule.utc_range(period.previous());
+    let (_, load_end) = schedule.utc_range(period);
+    let tracks = db::get_daily_tracks(
+        Some(load_start.date() - Duration::days(1)),
+        Some(load_end.date()),
+        None,
+        Some(user.id),
+    )
+    .await
+    .map_err(|e| format!("failed to load tracks: {}", e))?;
+
+    let email = build_digest(&user, &subscription, &schedule, period, &topics, &tracks);
+    let queued = email.is_some();
+    let claimed = db::claim_digest_period(user.id, period.start, email)
+        .await
+        .map_err(|e| format!("failed to queue digest: {}", e))?;
+    Ok(claimed && queued)
+}
+
+/// One pass over every active subscription. Returns how many digests were
+/// queued.
+async fn run_once() -> Result<usize, String> {
+    let subscriptions = db::list_active_digest_subscriptions()
+        .await
+        .map_err(|e| format!("failed to list digest subscriptions: {}", e))?;
+    let now = Utc::now();
+    let mut queued = 0;
+    for (subscription, user) in subscriptions {
+        let user_id = user.id;
+        match send_due_digest(subscription, user, now).await {
+            Ok(true) => queued += 1,
+            Ok(false) => {}
+            Err(e) => log::error!("Digest for user {} failed: {}", user_id, e),
+        }
+    }
+    if queued > 0 {
+        log::info!("Queued {} digest email(s)", queued);
+        crate::email_outbox::wake();
+    }
+    Ok(queued)
+}
+
+/// Starts the digest scheduler. Digests are claimed per period in the
+/// database, so several instances can run it without sending twice.
+pub(crate) fn spawn_scheduler(shutdown: CancellationToken) {
+    crate::shutdown::spawn_background(async move {
+        loop {
+            if let Err(e) = run_once().await {
+                log::error!("Digest pass failed: {}", e);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        log::info!("Digest scheduler stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&at(s))
+    }
+
+    fn schedule(frequency: DigestFrequency, send_time: &str, time_zone: &str) -> Schedule {
+        Schedule {
+            frequency,
+            send_time: parse_send_time(send_time).unwrap(),
+            time_zone: parse_time_zone(time_zone).unwrap(),
+        }
+    }
+
+    fn topic(id: i64, name: &str, parent: Option<i64>) -> Topic {
+        Topic {
+            id,
+            topic_name: name.to_string(),
+            display_color: "#3b82f6".to_string(),
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+            parent_topic_id: parent,
+            user_id: Some(1),
+            version: 1,
+        }
+    }
+
+    fn track(start: &str, topic_id: i64, duration_minutes: i32) -> DailyTrack {
+        DailyTrack {
+            id: 0,
+            start_time: at(start),
+            created_at: at(start),
+            updated_at: None,
+            topic_id: Some(topic_id),
+            comment: None,
+            user_id: Some(1),
+            duration_minutes,
+            version: 1,
+        }
+    }
+
+    fn subscription(topic_ids: Option<&str>) -> DigestSubscription {
+        DigestSubscription {
+            user_id: 1,
+            enabled: true,
+            frequency: "daily".to_string(),
+            send_time: parse_send_time("08:00").unwrap(),
+            time_zone: "Europe/Berlin".to_string(),
+            topic_ids: topic_ids.map(str::to_string),
+            unsubscribe_token: "tok123".to_string(),
+            last_period_start: None,
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+        }
+    }
+
+    fn user() -> User {
+        User {
+            id: 1,
+            username: "alice".to_string(),
+            email: "alice@example.com".to_string(),
+            password_hash: String::new(),
+            email_verified: true,
+            locale: "en".to_string(),
+            verification_code: None,
+            verification_code_expires_at: None,
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn daily_digest_is_due_after_send_time_in_local_time() {
+        let s = schedule(DigestFrequency::Daily, "08:00", "Europe/Berlin");
+        // 06:30 UTC is 08:30 in Berlin (CEST): yesterday's digest is due.
+        let period = s.due_period(utc("2026-07-15 06:30"));
+        assert_eq!(period.start, day("2026-07-14"));
+        // 05:30 UTC is 07:30 in Berlin: only the day before yesterday.
+        let period = s.due_period(utc("2026-07-15 05:30"));
+        assert_eq!(period.start, day("2026-07-13"));
+    }
+
+    #[test]
+    fn weekly_digest_covers_the_previous_iso_week() {
+        let s = schedule(DigestFrequency::Weekly, "09:00", "UTC");
+        // 2026-10-21 is a Wednesday.
+        let period = s.due_period(utc("2026-10-21 12:00"));
+        assert_eq!(
+            period,
+            Period {
+                start: day("2026-10-12"),
+                end: day("2026-10-18")
+            }
+        );
+        // Monday before the send time still points at the week before.
+        let period = s.due_period(utc("2026-10-19 08:00"));
+        assert_eq!(period.start, day("2026-10-05"));
+    }
+
+    #[test]
+    fn utc_range_follows_dst_changes() {
+        let s = schedule(DigestFrequency::Daily, "08:00", "Europe/Berlin");
+        // 2026-03-29 is 23 hours long in Berlin.
+        let (start, end) = s.utc_range(Period {
+            start: day("2026-03-29"),
+            end: day("2026-03-29"),
+        });
+        assert_eq!(start, at("2026-03-28 23:00"));
+        assert_eq!(end, at("2026-03-29 22:00"));
+    }
+
+    #[test]
+    fn minutes_by_topic_clips_tracks_to_the_range() {
+        let tracks = vec![
+            track("2026-05-11 23:30", 1, 60),
+            track("2026-05-12 09:00", 2, 90),
+            track("2026-05-13 09:00", 2, 30),
+        ];
+        let totals = minutes_by_topic(&tracks, at("2026-05-12 00:00"), at("2026-05-13 00:00"));
+        assert_eq!(totals.get(&1), Some(&30));
+        assert_eq!(totals.get(&2), Some(&90));
+    }
+
+    #[test]
+    fn durations_are_formatted_compactly() {
+        assert_eq!(format_minutes(0), "0m");
+        assert_eq!(format_minutes(45), "45m");
+        assert_eq!(format_minutes(120), "2h");
+        assert_eq!(format_minutes(135), "2h 15m");
+        assert_eq!(format_change(90), "+1h 30m");
+        assert_eq!(format_change(-45), "-45m");
+        assert_eq!(format_change(0), "±0m");
+    }
+
+    #[test]
+    fn included_topics_expand_subtopics() {
+        let topics = vec![
+            topic(1, "Work", None),
+            topic(2, "Meetings", Some(1)),
+            topic(3, "Sport", None),
+        ];
+        assert_eq!(included_topics(&topics, Some(&[1])), HashSet::from([1, 2]));
+        assert_eq!(included_topics(&topics, None), HashSet::from([1, 2, 3]));
+    }
+
+    #[test]
+    fn build_digest_compares_with_the_previous_period() {
+        let topics = vec![topic(1, "Work", None), topic(2, "Sport", None)];
+        // Local days in Berlin (CEST, UTC+2).
+        let tracks = vec![
+            track("2026-07-13 07:00", 1, 120),
+            track("2026-07-14 07:00", 1, 180),
+            track("2026-07-14 16:00", 2, 60),
+        ];
+        let s = schedule(DigestFrequency::Daily, "08:00", "Europe/Berlin");
+        let period = Period {
+            start: day("2026-07-14"),
+            end: day("2026-07-14"),
+        };
+
+        let draft =
+            build_digest(&user(), &subscription(None), &s, period, &topics, &tracks).unwrap();
+        assert_eq!(draft.recipient, "alice@example.com");
+        assert_eq!(draft.template, "digest");
+        let values: serde_json::Value = serde_json::from_str(&draft.payload).unwrap();
+        assert_eq!(values["total"], "4h");
+        assert_eq!(values["total_change"], "+2h");
+        assert_eq!(values["topics"][0]["name"], "Work");
+        assert_eq!(values["topics"][0]["change"], "+1h");
+        assert!(
+            values["unsubscribe_url"]
+                .as_str()
+                .unwrap()
+                .ends_with("/api/v1/digest/unsubscribe/tok123")
+        );
+
+        let only_sport = build_digest(
+            &user(),
+            &subscription(Some("[2]")),
+            &s,
+            period,
+            &topics,
+            &tracks,
+        )
+        .unwrap();
+        let values: serde_json::Value = serde_json::from_str(&only_sport.payload).unwrap();
+        assert_eq!(values["topics"].as_array().unwrap().len(), 1);
+        assert_eq!(values["total"], "1h");
+    }
+
+    #[test]
+    fn build_digest_skips_empty_periods() {
+        let topics = vec![topic(1, "Work", None)];
+        let s = schedule(DigestFrequency::Daily, "08:00", "UTC");
+        let period = Period {
+            start: day("2026-07-14"),
+            end: day("2026-07-14"),
+        };
+        assert!(build_digest(&user(), &subscription(None), &s, period, &topics, &[]).is_none());
+    }
+}
@@ -1,60 +1,520 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use db_model::models::EmailOutbox;
 use hickory_resolver::TokioResolver;
 use hickory_resolver::config::ResolverConfig;
 use hickory_resolver::name_server::TokioConnectionProvider;
 use hickory_resolver::proto::rr::rdata::MX;
-use lettre::AsyncTransport;
-use lettre::Message;
-use lettre::AsyncSmtpTransport;
-use lettre::Tokio1Executor;
-
-pub async fn send_verification_email(to: &str, username: &str, code: &str) -> Result<(), String> {
-    let sender = std::env::var("SENDER_EMAIL")
-        .unwrap_or_else(|_| "noreply@dailytracker.app".to_string());
-
-    let domain = to
-        .rsplit_once('@')
-        .map(|(_, d)| d)
-        .ok_or_else(|| format!("Invalid email address: {}", to))?;
-
-    // Resolve MX records
-    let resolver = TokioResolver::builder_with_config(
-        ResolverConfig::default(),
-        TokioConnectionProvider::default(),
-    )
-    .build();
-    let mx_lookup = resolver
-        .mx_lookup(domain)
-        .await
-        .map_err(|e| format!("MX lookup failed for {}: {}", domain, e))?;
-
-    let mx_host = mx_lookup
-        .iter()
-        .min_by_key(|mx: &&MX| mx.preference())
-        .ok_or_else(|| format!("No MX records found for {}", domain))?
-        .exchange()
-        .to_ascii();
-
-    // Build the email
-    let email = Message::builder()
-        .from(sender.parse().map_err(|e| format!("Invalid sender address: {}", e))?)
-        .to(to.parse().map_err(|e| format!("Invalid recipient address: {}", e))?)
-        .subject("Your verification code")
-        .body(format!(
-            "Hi {},\n\nYour email verification code is: {}\n\nThis code expires in 30 minutes.\n",
-            username, code
+use lettre::address::Envelope;
+use lettre::message::MultiPart;
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use once_cell::sync::OnceCell;
+
+use crate::config::{EmailConfig, EmailTransportKind};
+use crate::email_templates::{self, EmailTemplate, RenderedEmail};
+
+static TRANSPORT: OnceCell<Box<dyn EmailTransport>> = OnceCell::new();
+
+/// Why a message was not delivered. The outbox retries transient errors
+/// and gives up on permanent ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SendError {
+    Transient(String),
+    Permanent(String),
+}
+
+impl SendError {
+    fn smtp(context: String, e: lettre::transport::smtp::Error) -> SendError {
+        let message = format!("{}: {}", context, e);
+        if e.is_permanent() {
+            SendError::Permanent(message)
+        } else {
+            SendError::Transient(message)
+        }
+    }
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Transient(message) => write!(f, "{}", message),
+            SendError::Permanent(message) => write!(f, "{} (permanent)", message),
+        }
+    }
+}
+
+/// Delivers fully built messages. Implementations are picked by
+/// `email.transport`.
+#[async_trait]
+pub(crate) trait EmailTransport: Send + Sync {
+    async fn send(&self, message: Message) -> Result<(), SendError>;
+}
+
+/// Looks up each recipient domain's MX host and delivers to it without
+/// authentication. Needs outbound port 25, which many hosts block.
+pub(crate) struct DirectMxTransport {
+    port: u16,
+}
+
+impl DirectMxTransport {
+    async fn mx_host(domain: &str) -> Result<String, SendError> {
+        let resolver = TokioResolver::builder_with_config(
+            ResolverConfig::default(),
+            TokioConnectionProvider::default(),
+        )
+        .build();
+        let mx_lookup = resolver.mx_lookup(domain).await.map_err(|e| {
+            let message = format!("MX lookup failed for {}: {}", domain, e);
+            // The domain cannot receive mail; asking again will not help.
+            if e.is_nx_domain() || e.is_no_records_found() {
+                SendError::Permanent(message)
+            } else {
+                SendError::Transient(message)
+            }
+        })?;
+        Ok(mx_lookup
+            .iter()
+            .min_by_key(|mx: &&MX| mx.preference())
+            .ok_or_else(|| SendError::Permanent(format!("No MX records found for {}", domain)))?
+            .exchange()
+            .to_ascii())
+    }
+}
+
+/// Splits recipients by domain, keeping the order they were given in.
+fn recipients_by_domain(recipients: &[Address]) -> Vec<(&str, Vec<Address>)> {
+    let mut groups: Vec<(&str, Vec<Address>)> = Vec::new();
+    for address in recipients {
+        match groups.iter_mut().find(|(d, _)| *d == address.domain()) {
+            Some((_, group)) => group.push(address.clone()),
+            None => groups.push((address.domain(), vec![address.clone()])),
+        }
+    }
+    groups
+}
+
+#[async_trait]
+impl EmailTransport for DirectMxTransport {
+    async fn send(&self, message: Message) -> Result<(), SendError> {
+        let envelope = message.envelope();
+        let raw = message.formatted();
+        for (domain, recipients) in recipients_by_domain(envelope.to()) {
+            let mx_host = Self::mx_host(domain).await?;
+            let domain_envelope = Envelope::new(envelope.from().cloned(), recipients)
+                .map_err(|e| SendError::Permanent(format!("Invalid envelope: {}", e)))?;
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&mx_host)
+                .port(self.port)
+                .build()
+                .send_raw(&domain_envelope, &raw)
+                .await
+                .map_err(|e| SendError::smtp(format!("Failed to send email to {}", mx_host), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Hands every message to one relay, upgrading with STARTTLS and logging in
+/// when credentials are configured.
+pub(crate) struct RelayTransport {
+    host: String,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl RelayTransport {
+    fn new(config: &EmailConfig) -> Result<Self, String> {
+        let host = config
+            .smtp_host
+            .clone()
+            .ok_or_else(|| "email.smtp_host must be set for the relay transport".to_string())?;
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+            .map_err(|e| format!("Invalid SMTP relay {}: {}", host, e))?
+            .port(config.smtp_port);
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(RelayTransport {
+            host,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for RelayTransport {
+    async fn send(&self, message: Message) -> Result<(), SendError> {
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| SendError::smtp(format!("Failed to send email through {}", self.host), e))
+    }
+}
+
+/// Writes each message into a maildir (`tmp/`, `new/`, `cur/`) so it can be
+/// read with any mail client during development.
+pub(crate) struct MaildirTransport {
+    dir: PathBuf,
+}
+
+impl MaildirTransport {
+    fn new(dir: &Path) -> Result<Self, String> {
+        for sub in ["tmp", "new", "cur"] {
+            let path = dir.join(sub);
+            std::fs::create_dir_all(&path)
+                .map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+        }
+        Ok(MaildirTransport {
+            dir: dir.to_path_buf(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for MaildirTransport {
+    async fn send(&self, message: Message) -> Result<(), SendError> {
+        // Maildir readers only look at new/, so the file is written in tmp/
+        // and renamed once complete.
+        let name = format!(
+            "{}.{}.daily-tracker.eml",
+            chrono::Utc::now().timestamp(),
+            utils::snowflake::generate_snowflake_id()
+        );
+        let tmp = self.dir.join("tmp").join(&name);
+        let new = self.dir.join("new").join(&name);
+        tokio::fs::write(&tmp, message.formatted())
+            .await
+            .map_err(|e| {
+                SendError::Transient(format!("failed to write {}: {}", tmp.display(), e))
+            })?;
+        tokio::fs::rename(&tmp, &new).await.map_err(|e| {
+            SendError::Transient(format!("failed to move mail to {}: {}", new.display(), e))
+        })
+    }
+}
+
+/// A message captured by `MemoryMailbox`. Only tests read it back.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) struct SentEmail {
+    pub to: Vec<String>,
+    pub subject: String,
+    /// The full RFC 5322 message, headers included.
+    pub raw: String,
+}
+
+/// Keeps messages in memory instead of sending them. Clones share the same
+/// mailbox.
+#[derive(Clone, Default)]
+pub(crate) struct MemoryMailbox {
+    sent: Arc<Mutex<Vec<SentEmail>>>,
+}
+
+impl MemoryMailbox {
+    #[cfg(test)]
+    pub(crate) fn sent_to(&self, recipient: &str) -> Vec<SentEmail> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|m| m.to.iter().any(|to| to == recipient))
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl EmailTransport for MemoryMailbox {
+    async fn send(&self, message: Message) -> Result<(), SendError> {
+        let sent = SentEmail {
+            to: message
+                .envelope()
+                .to()
+                .iter()
+                .map(|a| a.to_string())
+                .collect(),
+            subject: message
+                .headers()
+                .get_raw("Subject")
+                .unwrap_or_default()
+                .to_string(),
+            raw: String::from_utf8_lossy(&message.formatted()).into_owned(),
+        };
+        log::info!(
+            "Captured email to {} in memory: {}",
+            sent.to.join(", "),
+            sent.subject
+        );
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sent);
+        Ok(())
+    }
+}
+
+/// Builds the transport selected by `email.transport`.
+pub(crate) fn build_transport(config: &EmailConfig) -> Result<Box<dyn EmailTransport>, String> {
+    Ok(match config.transport {
+        EmailTransportKind::Direct => Box::new(DirectMxTransport {
+            port: config.smtp_port,
+        }),
+        EmailTransportKind::Relay => Box::new(RelayTransport::new(config)?),
+        EmailTransportKind::File => {
+            let dir = config
+                .file_dir
+                .as_deref()
+                .ok_or_else(|| "email.file_dir must be set for the file transport".to_string())?;
+            Box::new(MaildirTransport::new(dir)?)
+        }
+        EmailTransportKind::Memory => {
+            log::warn!("Email transport is 'memory'; no email will leave this process");
+            Box::new(MemoryMailbox::default())
+        }
+    })
+}
+
+/// Installs the transport used for the rest of the process. Called once from
+/// `main` after the configuration is validated.
+pub fn init(config: &EmailConfig) -> Result<(), String> {
+    let transport = build_transport(config)?;
+    if TRANSPORT.set(transport).is_err() {
+        log::warn!("Email transport was already initialised; keeping the first one");
+    }
+    Ok(())
+}
+
+/// Shared by every test in the crate, so assertions should filter by
+/// recipient.
+#[cfg(test)]
+pub(crate) static TEST_MAILBOX: once_cell::sync::Lazy<MemoryMailbox> =
+    once_cell::sync::Lazy::new(MemoryMailbox::default);
+
+/// The transport installed by `init`. Tests get `TEST_MAILBOX`.
+fn transport() -> &'static dyn EmailTransport {
+    #[cfg(test)]
+    return TRANSPORT
+        .get_or_init(|| Box::new(TEST_MAILBOX.clone()))
+        .as_ref();
+    #[cfg(not(test))]
+    TRANSPORT
+        .get()
+        .expect("email::init must be called before sending email")
+        .as_ref()
+}
+
+/// `List-Unsubscribe` (RFC 2369) with a single HTTPS link.
+#[derive(Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ListUnsubscribe(
+            s.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string(),
         ))
-        .map_err(|e| format!("Failed to build email: {}", e))?;
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), format!("<{}>", self.0))
+    }
+}
+
+/// `List-Unsubscribe-Post` (RFC 8058): mail clients may unsubscribe with a
+/// POST to the link instead of opening it.
+#[derive(Clone)]
+struct ListUnsubscribePost;
 
-    // Connect directly to the MX host on port 25
-    let transport: AsyncSmtpTransport<Tokio1Executor> =
-        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&mx_host)
-            .port(25)
-            .build();
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
 
-    transport
-        .send(email)
+    fn parse(_: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ListUnsubscribePost)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
+
+/// Builds a multipart message with the text and HTML parts of `email`. With
+/// an `unsubscribe_url`, mail clients get a one-click unsubscribe button.
+fn build_message(
+    to: &str,
+    email: RenderedEmail,
+    unsubscribe_url: Option<String>,
+) -> Result<Message, String> {
+    let sender = &crate::config::get().email.sender;
+    let mut builder = Message::builder();
+    if let Some(url) = unsubscribe_url {
+        builder = builder
+            .header(ListUnsubscribe(url))
+            .header(ListUnsubscribePost);
+    }
+    builder
+        .from(
+            sender
+                .parse()
+                .map_err(|e| format!("Invalid sender address: {}", e))?,
+        )
+        .to(to
+            .parse()
+            .map_err(|e| format!("Invalid recipient address: {}", e))?)
+        .subject(email.subject)
+        .multipart(MultiPart::alternative_plain_html(email.text, email.html))
+        .map_err(|e| format!("Failed to build email: {}", e))
+}
+
+/// Renders a queued email and hands it to the transport. Rendering and
+/// address errors are permanent: retrying the same row cannot fix them.
+pub(crate) async fn deliver(email: &EmailOutbox) -> Result<(), SendError> {
+    let template = EmailTemplate::from_name(&email.template).ok_or_else(|| {
+        SendError::Permanent(format!("unknown email template '{}'", email.template))
+    })?;
+    let values: serde_json::Value = serde_json::from_str(&email.payload)
+        .map_err(|e| SendError::Permanent(format!("invalid email payload: {}", e)))?;
+    let unsubscribe_url = values
+        .get("unsubscribe_url")
+        .and_then(|url| url.as_str())
+        .map(str::to_string);
+    let rendered = email_templates::get()
+        .render(template, &email.locale, values)
+        .map_err(SendError::Permanent)?;
+    let message =
+        build_message(&email.recipient, rendered, unsubscribe_url).map_err(SendError::Permanent)?;
+    transport().send(message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(recipient: &str, template: &str, payload: &str) -> EmailOutbox {
+        let now = chrono::Utc::now().naive_utc();
+        EmailOutbox {
+            id: 1,
+            user_id: None,
+            recipient: recipient.to_string(),
+            template: template.to_string(),
+            locale: "en".to_string(),
+            payload: payload.to_string(),
+            status: "pending".to_string(),
+            attempts: 1,
+            next_attempt_at: now,
+            last_error: None,
+            created_at: now,
+            sent_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn deliver_renders_into_the_test_mailbox() {
+        let email = queued(
+            "verify-test@example.com",
+            "verification",
+            r#"{"username":"alice","code":"123456","expires_minutes":30}"#,
+        );
+        deliver(&email).await.unwrap();
+        let sent = TEST_MAILBOX.sent_to("verify-test@example.com");
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].subject, "Your verification code");
+        assert!(sent[0].raw.contains("multipart/alternative"));
+        assert!(sent[0].raw.contains("text/html"));
+        assert!(sent[0].raw.contains("123456"));
+    }
+
+    #[tokio::test]
+    async fn deliver_adds_one_click_unsubscribe_headers() {
+        let payload = serde_json::to_string(&EmailTemplate::Digest.sample_context()).unwrap();
+        deliver(&queued("digest-test@example.com", "digest", &payload))
+            .await
+            .unwrap();
+        let sent = TEST_MAILBOX.sent_to("digest-test@example.com");
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].raw.contains(
+            "List-Unsubscribe: <https://tracker.example.com/api/v1/digest/unsubscribe/0123abcd>"
+        ));
+        assert!(
+            sent[0]
+                .raw
+                .contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click")
+        );
+
+        deliver(&queued(
+            "no-unsubscribe@example.com",
+            "verification",
+            r#"{"username":"a","code":"1","expires_minutes":30}"#,
+        ))
         .await
-        .map_err(|e| format!("Failed to send email to {}: {}", mx_host, e))?;
+        .unwrap();
+        let sent = TEST_MAILBOX.sent_to("no-unsubscribe@example.com");
+        assert!(!sent[0].raw.contains("List-Unsubscribe"));
+    }
 
-    Ok(())
+    #[tokio::test]
+    async fn deliver_fails_permanently_on_bad_rows() {
+        let unknown = queued("x@example.com", "newsletter", "{}");
+        assert!(matches!(
+            deliver(&unknown).await,
+            Err(SendError::Permanent(_))
+        ));
+        let bad_address = queued("not an address", "verification", r#"{"code":"1"}"#);
+        assert!(matches!(
+            deliver(&bad_address).await,
+            Err(SendError::Permanent(_))
+        ));
+    }
+
+    #[test]
+    fn recipients_are_grouped_by_domain() {
+        let addresses: Vec<Address> = ["a@one.test", "b@two.test", "c@one.test"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        let groups = recipients_by_domain(&addresses);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "one.test");
+        assert_eq!(groups[0].1.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn maildir_transport_writes_into_new() {
+        let dir = std::env::temp_dir().join(format!(
+            "daily-tracker-maildir-{}",
+            utils::snowflake::generate_snowflake_id()
+        ));
+        let transport = MaildirTransport::new(&dir).unwrap();
+        let message = Message::builder()
+            .from("noreply@example.com".parse().unwrap())
+            .to("someone@example.com".parse().unwrap())
+            .subject("Hello")
+            .body("Body".to_string())
+            .unwrap();
+        transport.send(message).await.unwrap();
+
+        let written: Vec<_> = std::fs::read_dir(dir.join("new")).unwrap().collect();
+        assert_eq!(written.len(), 1);
+        assert_eq!(std::fs::read_dir(dir.join("tmp")).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relay_transport_needs_a_host() {
+        let config = EmailConfig {
+            transport: EmailTransportKind::Relay,
+            ..EmailConfig::default()
+        };
+        assert!(build_transport(&config).is_err());
+    }
 }
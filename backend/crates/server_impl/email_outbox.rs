@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use db::OutboxDraft;
+use db_model::models::{EmailOutbox, User};
+use once_cell::sync::Lazy;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::email::{self, SendError};
+use crate::email_templates::{EmailTemplate, VerificationEmail};
+
+/// Emails claimed per pass.
+const BATCH_SIZE: i64 = 20;
+
+/// How often the queue is checked when nothing wakes the worker.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a claimed email is hidden from other workers. Longer than any
+/// SMTP exchange, so only a crashed worker lets it lapse.
+const CLAIM_LEASE_MINUTES: i64 = 5;
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// The queued form of a verification email.
+pub(crate) fn verification_email(user: &User, code: &str) -> OutboxDraft {
+    OutboxDraft {
+        recipient: user.email.clone(),
+        template: EmailTemplate::Verification.name().to_string(),
+        locale: user.locale.clone(),
+        payload: serde_json::to_string(&VerificationEmail {
+            username: &user.username,
+            code,
+            expires_minutes: db::VERIFICATION_CODE_TTL_MINUTES,
+        })
+        .expect("verification email values serialize"),
+    }
+}
+
+/// Tells the worker that new email is queued, so it does not wait for the
+/// next poll.
+pub(crate) fn wake() {
+    WAKE.notify_one();
+}
+
+/// Delay before the next try after `attempts` failed ones: one minute,
+/// doubling up to six hours.
+pub(crate) fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    FIRST_RETRY_DELAY
+        .saturating_mul(2u32.pow(doublings))
+        .min(MAX_RETRY_DELAY)
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Sent,
+    Retry(Duration),
+    Failed,
+}
+
+fn outcome(result: &Result<(), SendError>, attempts: i32, max_attempts: u32) -> Outcome {
+    match result {
+        Ok(()) => Outcome::Sent,
+        Err(SendError::Permanent(_)) => Outcome::Failed,
+        Err(SendError::Transient(_)) if attempts >= max_attempts as i32 => Outcome::Failed,
+        Err(SendError::Transient(_)) => Outcome::Retry(retry_delay(attempts)),
+    }
+}
+
+fn query_failed(e: db::StoreError) -> String {
+    format!("outbox query failed: {}", e)
+}
+
+async fn process(email: EmailOutbox, max_attempts: u32) -> Result<(), String> {
+    let result = email::deliver(&email).await;
+    let id = email.id;
+    match outcome(&result, email.attempts, max_attempts) {
+        Outcome::Sent => {
+            log::info!(
+                "Sent {} email {} to {}",
+                email.template,
+                id,
+                email.recipient
+            );
+            db::mark_email_sent(id).await.map_err(query_failed)
+        }
+        Outcome::Retry(delay) => {
+            let error = result.err().map(|e| e.to_string()).unwrap_or_default();
+            crate::metrics::record_email_send_failure();
+            log::warn!(
+                "Email {} to {} failed (attempt {}), retrying in {}s: {}",
+                id,
+                email.recipient,
+                email.attempts,
+                delay.as_secs(),
+                error
+            );
+            let next_attempt_at = chrono::Utc::now().naive_utc()
+                + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::zero());
+            db::schedule_email_retry(id, next_attempt_at, &error)
+                .await
+                .map_err(query_failed)
+        }
+        Outcome::Failed => {
+            let error = result.err().map(|e| e.to_string()).unwrap_or_default();
+            crate::metrics::record_email_send_failure();
+            log::error!(
+                "Giving up on email {} to {} after {} attempt(s): {}",
+                id,
+                email.recipient,
+                email.attempts,
+                error
+            );
+            db::mark_email_failed(id, &error)
+                .await
+                .map_err(query_failed)
+        }
+    }
+}
+
+/// One pass: sends every due email. Returns how many were claimed.
+async fn run_once(max_attempts: u32) -> Result<usize, String> {
+    let due = db::claim_due_emails(BATCH_SIZE, chrono::Duration::minutes(CLAIM_LEASE_MINUTES))
+        .await
+        .map_err(query_failed)?;
+    let claimed = due.len();
+    for email in due {
+        process(email, max_attempts).await?;
+    }
+    if let Ok(counts) = db::outbox_counts().await {
+        crate::metrics::set_email_outbox(counts.pending, counts.failed);
+    }
+    Ok(claimed)
+}
+
+/// Starts the delivery worker. It stops at shutdown once the email in hand
+/// is done; anything claimed but unsent is picked up again after the lease.
+pub(crate) fn spawn_worker(max_attempts: u32, shutdown: CancellationToken) {
+    crate::shutdown::spawn_background(async move {
+        loop {
+            let full_batch = match run_once(max_attempts).await {
+                Ok(claimed) => claimed as i64 == BATCH_SIZE,
+                Err(e) => {
+                    log::error!("Email outbox pass failed: {}", e);
+                    false
+                }
+            };
+            if shutdown.is_cancelled() {
+                break;
+            }
+            if full_batch {
+                continue;
+            }
+            tokio::select! {
+                _ = WAKE.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        log::info!("Email outbox worker stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_and_caps() {
+        assert_eq!(retry_delay(1), Duration::from_secs(60));
+        assert_eq!(retry_delay(2), Duration::from_secs(120));
+        assert_eq!(retry_delay(4), Duration::from_secs(480));
+        assert_eq!(retry_delay(12), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(i32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn outcome_retries_transient_errors_until_the_limit() {
+        let transient = Err(SendError::Transient("421 try later".to_string()));
+        assert_eq!(
+            outcome(&transient, 1, 10),
+            Outcome::Retry(Duration::from_secs(60))
+        );
+        assert_eq!(outcome(&transient, 10, 10), Outcome::Failed);
+        assert_eq!(outcome(&Ok(()), 10, 10), Outcome::Sent);
+    }
+
+    #[test]
+    fn outcome_gives_up_on_permanent_errors() {
+        let permanent = Err(SendError::Permanent("550 no such user".to_string()));
+        assert_eq!(outcome(&permanent, 1, 10), Outcome::Failed);
+    }
+
+    #[test]
+    fn verification_email_payload_renders() {
+        let user = User {
+            id: 1,
+            username: "alice".to_string(),
+            email: "alice@example.com".to_string(),
+            password_hash: String::new(),
+            email_verified: false,
+            locale: "de".to_string(),
+            verification_code: None,
+            verification_code_expires_at: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: None,
+        };
+        let draft = verification_email(&user, "654321");
+        assert_eq!(draft.recipient, "alice@example.com");
+        assert_eq!(draft.locale, "de");
+        let values: serde_json::Value = serde_json::from_str(&draft.payload).unwrap();
+        let rendered = crate::email_templates::get()
+            .render(EmailTemplate::Verification, &draft.locale, values)
+            .unwrap();
+        assert!(rendered.text.contains("654321"));
+    }
+}
@@ -0,0 +1,450 @@
+use std::path::{Path, PathBuf};
+
+use minijinja::{Environment, ErrorKind, context};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+
+/// Locale used when nothing better matches. Every template exists in it.
+pub(crate) const DEFAULT_LOCALE: &str = "en";
+
+const APP_NAME: &str = "Daily Tracker";
+
+/// Templates compiled into the binary. A template directory passed to
+/// `Templates::load` uses the same layout and wins file by file.
+const BUILTIN: &[(&str, &str)] = &[
+    (
+        "layout.html",
+        include_str!("../../templates/email/layout.html"),
+    ),
+    (
+        "en/verification.subject.txt",
+        include_str!("../../templates/email/en/verification.subject.txt"),
+    ),
+    (
+        "en/verification.txt",
+        include_str!("../../templates/email/en/verification.txt"),
+    ),
+    (
+        "en/verification.html",
+        include_str!("../../templates/email/en/verification.html"),
+    ),
+    (
+        "en/digest.subject.txt",
+        include_str!("../../templates/email/en/digest.subject.txt"),
+    ),
+    (
+        "en/digest.txt",
+        include_str!("../../templates/email/en/digest.txt"),
+    ),
+    (
+        "en/digest.html",
+        include_str!("../../templates/email/en/digest.html"),
+    ),
+    (
+        "de/verification.subject.txt",
+        include_str!("../../templates/email/de/verification.subject.txt"),
+    ),
+    (
+        "de/verification.txt",
+        include_str!("../../templates/email/de/verification.txt"),
+    ),
+    (
+        "de/verification.html",
+        include_str!("../../templates/email/de/verification.html"),
+    ),
+    (
+        "de/digest.subject.txt",
+        include_str!("../../templates/email/de/digest.subject.txt"),
+    ),
+    (
+        "de/digest.txt",
+        include_str!("../../templates/email/de/digest.txt"),
+    ),
+    (
+        "de/digest.html",
+        include_str!("../../templates/email/de/digest.html"),
+    ),
+];
+
+static TEMPLATES: OnceCell<Templates> = OnceCell::new();
+
+/// The emails the backend sends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum EmailTemplate {
+    Verification,
+    Digest,
+}
+
+impl EmailTemplate {
+    const ALL: [EmailTemplate; 2] = [EmailTemplate::Verification, EmailTemplate::Digest];
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Verification => "verification",
+            EmailTemplate::Digest => "digest",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<EmailTemplate> {
+        EmailTemplate::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// Made-up values for previews.
+    pub(crate) fn sample_context(&self) -> minijinja::Value {
+        match self {
+            EmailTemplate::Verification => minijinja::Value::from_serialize(VerificationEmail {
+                username: "alex",
+                code: "482913",
+                expires_minutes: db::VERIFICATION_CODE_TTL_MINUTES,
+            }),
+            EmailTemplate::Digest => minijinja::Value::from_serialize(DigestEmail {
+                username: "alex".to_string(),
+                frequency: "weekly",
+                period_start: "2026-10-12".to_string(),
+                period_end: "2026-10-18".to_string(),
+                total: "21h 30m".to_string(),
+                total_change: "+2h".to_string(),
+                topics: vec![
+                    DigestTopicLine {
+                        name: "Work".to_string(),
+                        duration: "18h".to_string(),
+                        previous_duration: "17h 30m".to_string(),
+                        change: "+30m".to_string(),
+                    },
+                    DigestTopicLine {
+                        name: "Reading".to_string(),
+                        duration: "3h 30m".to_string(),
+                        previous_duration: "2h".to_string(),
+                        change: "+1h 30m".to_string(),
+                    },
+                ],
+                unsubscribe_url: "https://tracker.example.com/api/v1/digest/unsubscribe/0123abcd"
+                    .to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct VerificationEmail<'a> {
+    pub username: &'a str,
+    pub code: &'a str,
+    pub expires_minutes: i64,
+}
+
+/// Values of the digest email. Durations are preformatted, e.g. `2h 30m`.
+#[derive(Serialize, Debug)]
+pub(crate) struct DigestEmail {
+    pub username: String,
+    /// `daily` or `weekly`.
+    pub frequency: &'static str,
+    pub period_start: String,
+    pub period_end: String,
+    pub total: String,
+    pub total_change: String,
+    pub topics: Vec<DigestTopicLine>,
+    pub unsubscribe_url: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct DigestTopicLine {
+    pub name: String,
+    pub duration: String,
+    pub previous_duration: String,
+    pub change: String,
+}
+
+/// One email rendered in a single locale.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RenderedEmail {
+    pub locale: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+fn is_safe_template_name(name: &str) -> bool {
+    !name.split('/').any(|part| part.is_empty() || part == "..")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    BUILTIN.iter().find(|(n, _)| *n == name).map(|(_, t)| *t)
+}
+
+/// The template environment and the locales it can render.
+pub(crate) struct Templates {
+    env: Environment<'static>,
+    locales: Vec<String>,
+}
+
+impl Templates {
+    /// Loads the built-in templates, overridden by files in `dir`. A
+    /// subdirectory of `dir` adds a locale, which must then provide every
+    /// template. Every template is compiled here so syntax errors surface at
+    /// startup.
+    pub(crate) fn load(dir: Option<&Path>) -> Result<Templates, String> {
+        let mut locales: Vec<String> = BUILTIN
+            .iter()
+            .filter_map(|(name, _)| name.split_once('/').map(|(locale, _)| locale.to_string()))
+            .collect();
+        if let Some(dir) = dir {
+            let entries = std::fs::read_dir(dir)
+                .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    locales.push(entry.file_name().to_string_lossy().to_lowercase());
+                }
+            }
+        }
+        locales.sort();
+        locales.dedup();
+
+        let mut env = Environment::new();
+        env.set_keep_trailing_newline(true);
+        env.add_global("app_name", APP_NAME);
+        let override_dir: Option<PathBuf> = dir.map(Path::to_path_buf);
+        env.set_loader(move |name| {
+            if !is_safe_template_name(name) {
+                return Ok(None);
+            }
+            if let Some(dir) = &override_dir {
+                match std::fs::read_to_string(dir.join(name)) {
+                    Ok(text) => return Ok(Some(text)),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(minijinja::Error::new(
+                            ErrorKind::InvalidOperation,
+                            format!("failed to read template {}: {}", name, e),
+                        ));
+                    }
+                }
+            }
+            Ok(builtin(name).map(str::to_string))
+        });
+
+        let templates = Templates { env, locales };
+        for locale in &templates.locales {
+            for template in EmailTemplate::ALL {
+                for part in templates.part_names(template, locale) {
+                    templates
+                        .env
+                        .get_template(&part)
+                        .map_err(|e| format!("email template {}: {:#}", part, e))?;
+                }
+            }
+        }
+        Ok(templates)
+    }
+
+    fn part_names(&self, template: EmailTemplate, locale: &str) -> [String; 3] {
+        let name = template.name();
+        [
+            format!("{}/{}.subject.txt", locale, name),
+            format!("{}/{}.txt", locale, name),
+            format!("{}/{}.html", locale, name),
+        ]
+    }
+
+    /// Picks the best available locale for an `Accept-Language` style list
+    /// such as `de-AT, de;q=0.9, en;q=0.5`, or a single tag. Weights are
+    /// ignored; the first supported entry wins.
+    pub(crate) fn negotiate(&self, preferred: &str) -> &str {
+        for tag in preferred.split(',') {
+            let tag = tag
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+                .replace('_', "-");
+            let language = tag.split('-').next().unwrap_or_default();
+            for candidate in [tag.as_str(), language] {
+                if let Some(locale) = self.locales.iter().find(|l| *l == candidate) {
+                    return locale;
+                }
+            }
+        }
+        DEFAULT_LOCALE
+    }
+
+    /// Renders all parts of `template` in the best match for `locale`.
+    pub(crate) fn render<S: Serialize>(
+        &self,
+        template: EmailTemplate,
+        locale: &str,
+        values: S,
+    ) -> Result<RenderedEmail, String> {
+        let locale = self.negotiate(locale).to_string();
+        let ctx = context! { locale => &locale, ..minijinja::Value::from_serialize(values) };
+        let [subject, text, html] = self.part_names(template, &locale).map(|part| {
+            self.env
+                .get_template(&part)
+                .and_then(|t| t.render(&ctx))
+                .map_err(|e| format!("failed to render {}: {:#}", part, e))
+        });
+        Ok(RenderedEmail {
+            // A subject is one line; stray whitespace from the file is dropped.
+            subject: subject?.split_whitespace().collect::<Vec<_>>().join(" "),
+            text: text?,
+            html: html?,
+            locale,
+        })
+    }
+}
+
+/// Installs the templates used for the rest of the process. Called once from
+/// `main` after the configuration is validated.
+pub fn init(dir: Option<&Path>) -> Result<(), String> {
+    let templates = Templates::load(dir)?;
+    if TEMPLATES.set(templates).is_err() {
+        log::warn!("Email templates were already initialised; keeping the first ones");
+    }
+    Ok(())
+}
+
+/// The templates installed by `init`. Tests get the built-in templates.
+pub(crate) fn get() -> &'static Templates {
+    #[cfg(test)]
+    return TEMPLATES.get_or_init(|| Templates::load(None).expect("built-in templates compile"));
+    #[cfg(not(test))]
+    TEMPLATES
+        .get()
+        .expect("email_templates::init must be called before rendering email")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "daily-tracker-templates-{}",
+            utils::snowflake::generate_snowflake_id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn builtin_templates_render_in_every_locale() {
+        let templates = Templates::load(None).unwrap();
+        for locale in ["en", "de"] {
+            let email = templates
+                .render(
+                    EmailTemplate::Verification,
+                    locale,
+                    EmailTemplate::Verification.sample_context(),
+                )
+                .unwrap();
+            assert_eq!(email.locale, locale);
+            assert!(email.text.contains("482913"));
+            assert!(email.html.contains("482913"));
+            assert!(email.html.contains(&format!("<html lang=\"{}\">", locale)));
+            assert!(!email.subject.contains('\n'));
+        }
+    }
+
+    #[test]
+    fn digest_renders_topics_and_unsubscribe_link() {
+        let templates = Templates::load(None).unwrap();
+        for locale in ["en", "de"] {
+            let email = templates
+                .render(
+                    EmailTemplate::Digest,
+                    locale,
+                    EmailTemplate::Digest.sample_context(),
+                )
+                .unwrap();
+            assert!(email.subject.contains("21h 30m"));
+            for part in [&email.text, &email.html] {
+                assert!(part.contains("Reading"));
+                assert!(part.contains("+1h 30m"));
+                assert!(part.contains("0123abcd"));
+            }
+        }
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_language_then_default() {
+        let templates = Templates::load(None).unwrap();
+        assert_eq!(templates.negotiate("de-AT,en;q=0.8"), "de");
+        assert_eq!(templates.negotiate("fr-FR, de;q=0.5"), "de");
+        assert_eq!(templates.negotiate("pt_BR"), DEFAULT_LOCALE);
+        assert_eq!(templates.negotiate(""), DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn html_escapes_values_and_text_does_not() {
+        let email = Templates::load(None)
+            .unwrap()
+            .render(
+                EmailTemplate::Verification,
+                "en",
+                VerificationEmail {
+                    username: "<b>Tom & Jerry</b>",
+                    code: "1",
+                    expires_minutes: 30,
+                },
+            )
+            .unwrap();
+        assert!(email.text.contains("<b>Tom & Jerry</b>"));
+        assert!(
+            email
+                .html
+                .contains("&lt;b&gt;Tom &amp; Jerry&lt;&#x2f;b&gt;")
+        );
+    }
+
+    #[test]
+    fn directory_overrides_files_and_adds_locales() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(dir.join("en")).unwrap();
+        std::fs::write(
+            dir.join("en/verification.subject.txt"),
+            "Welcome, {{ username }}",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("fr")).unwrap();
+        for (name, part, text) in [
+            ("verification", "subject.txt", "Votre code"),
+            ("verification", "txt", "Code : {{ code }}"),
+            ("verification", "html", "<p>{{ code }}</p>"),
+            ("digest", "subject.txt", "Votre résumé"),
+            ("digest", "txt", "{{ total }}"),
+            ("digest", "html", "<p>{{ total }}</p>"),
+        ] {
+            std::fs::write(dir.join(format!("fr/{}.{}", name, part)), text).unwrap();
+        }
+
+        let templates = Templates::load(Some(&dir)).unwrap();
+        let sample = EmailTemplate::Verification.sample_context();
+        let en = templates
+            .render(EmailTemplate::Verification, "en", sample.clone())
+            .unwrap();
+        assert_eq!(en.subject, "Welcome, alex");
+        assert!(en.html.contains("482913"));
+        let fr = templates
+            .render(EmailTemplate::Verification, "fr-CA", sample)
+            .unwrap();
+        assert_eq!(fr.subject, "Votre code");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn incomplete_or_broken_overrides_fail_to_load() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(dir.join("fr")).unwrap();
+        std::fs::write(dir.join("fr/verification.txt"), "{{ code }}").unwrap();
+        assert!(Templates::load(Some(&dir)).is_err());
+
+        std::fs::remove_dir_all(dir.join("fr")).unwrap();
+        std::fs::create_dir_all(dir.join("en")).unwrap();
+        std::fs::write(dir.join("en/verification.txt"), "{% if %}").unwrap();
+        let err = Templates::load(Some(&dir)).err().unwrap();
+        assert!(err.contains("en/verification.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
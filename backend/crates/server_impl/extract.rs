@@ -0,0 +1,332 @@
+//! `Json`, `Query` and `Path` extractors whose rejections use the API's error
+//! envelope, with the offending field in `details`, instead of axum's plain
+//! text bodies.
+
+use axum::async_trait;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts, RawPathParams, Request};
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use models::{ApiError, FieldError, FieldErrorCode};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// `axum::Json`, rejecting with `ApiError`. Also used for JSON responses, so
+/// handlers need only one `Json`.
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::Json::<T>::from_request(req, state).await {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(rejection) => Err(json_rejection(rejection)),
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// `axum::extract::Query`, rejecting with `ApiError`.
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Query::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Query(value)) => Ok(Query(value)),
+            Err(rejection) => Err(query_rejection(rejection)),
+        }
+    }
+}
+
+/// `axum::extract::Path`, rejecting with `ApiError`.
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            Err(rejection) => {
+                // A single value such as `Path<i64>` fails without naming its
+                // parameter, so look the name up.
+                let names = RawPathParams::from_request_parts(parts, state)
+                    .await
+                    .map(|params| params.iter().map(|(key, _)| key.to_string()).collect())
+                    .unwrap_or_default();
+                Err(path_rejection(rejection, names))
+            }
+        }
+    }
+}
+
+/// Tells a serde error message's kind by its standard wording.
+fn classify(message: &str) -> FieldErrorCode {
+    if message.starts_with("missing field") {
+        FieldErrorCode::Required
+    } else if message.starts_with("invalid type") {
+        FieldErrorCode::InvalidType
+    } else if message.starts_with("unknown variant") || message.starts_with("invalid value") {
+        FieldErrorCode::InvalidValue
+    } else {
+        FieldErrorCode::InvalidFormat
+    }
+}
+
+/// The name in serde's "missing field `name`".
+fn missing_field(message: &str) -> Option<&str> {
+    message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split_once('`'))
+        .map(|(name, _)| name)
+}
+
+/// The field a serde error is about: the missing field's name, else `path`
+/// unless it is the document root, else `fallback`.
+fn field_of(message: &str, path: Option<String>, fallback: &str) -> String {
+    let field = missing_field(message).map(str::to_string).or(path);
+    match field {
+        Some(field) if field != "." => field,
+        _ => fallback.to_string(),
+    }
+}
+
+fn json_rejection(rejection: JsonRejection) -> ApiError {
+    let status = rejection.status();
+    let message = rejection.body_text();
+    let details = match &rejection {
+        JsonRejection::JsonDataError(e) => serde_json_field_error(e),
+        JsonRejection::JsonSyntaxError(e) => serde_json_field_error(e),
+        _ => None,
+    };
+    ApiError::Rejected {
+        status,
+        message,
+        details: details.into_iter().collect(),
+    }
+}
+
+/// Field error for the `serde_path_to_error` failure inside an axum JSON
+/// rejection.
+fn serde_json_field_error(error: &(dyn std::error::Error + 'static)) -> Option<FieldError> {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>() {
+            let inner = e.inner();
+            let text = inner.to_string();
+            // serde_json appends the position; the path says it better.
+            let message = text
+                .rsplit_once(" at line ")
+                .map_or(text.as_str(), |(message, _)| message);
+            let code = if inner.is_data() {
+                classify(message)
+            } else {
+                FieldErrorCode::InvalidFormat
+            };
+            let path = inner.is_data().then(|| e.path().to_string());
+            return Some(FieldError::new(
+                field_of(message, path, "body"),
+                code,
+                message,
+            ));
+        }
+        source = e.source();
+    }
+    None
+}
+
+fn query_rejection(rejection: QueryRejection) -> ApiError {
+    let status = rejection.status();
+    let message = rejection.body_text();
+    let details = match &rejection {
+        QueryRejection::FailedToDeserializeQueryString(_) => {
+            let reason = message
+                .strip_prefix("Failed to deserialize query string: ")
+                .unwrap_or(&message);
+            vec![FieldError::new(
+                field_of(reason, None, "query"),
+                classify(reason),
+                reason,
+            )]
+        }
+        _ => Vec::new(),
+    };
+    ApiError::Rejected {
+        status,
+        message,
+        details,
+    }
+}
+
+fn path_rejection(rejection: PathRejection, names: Vec<String>) -> ApiError {
+    use axum::extract::path::ErrorKind;
+
+    let status = rejection.status();
+    let message = rejection.body_text();
+    let details = match &rejection {
+        PathRejection::FailedToDeserializePathParams(e) => {
+            let field = match e.kind() {
+                ErrorKind::ParseErrorAtKey { key, .. } => Some(key.clone()),
+                ErrorKind::ParseErrorAtIndex { index, .. } => names.get(*index).cloned(),
+                ErrorKind::ParseError { .. } if names.len() == 1 => names.first().cloned(),
+                _ => None,
+            };
+            vec![FieldError::new(
+                field.unwrap_or_else(|| "path".to_string()),
+                FieldErrorCode::InvalidFormat,
+                e.body_text(),
+            )]
+        }
+        _ => Vec::new(),
+    };
+    ApiError::Rejected {
+        status,
+        message,
+        details,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use http_body_util::BodyExt;
+    use models::ErrorResponse;
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    struct NewTrack {
+        duration_minutes: i32,
+        start_time: chrono::DateTime<chrono::Utc>,
+    }
+
+    #[derive(Deserialize)]
+    struct Params {
+        limit: Option<u32>,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/tracks",
+                post(|Json(track): Json<NewTrack>| async move {
+                    format!("{} {}", track.start_time, track.duration_minutes)
+                }),
+            )
+            .route(
+                "/tracks/:id",
+                get(|Path(id): Path<i64>| async move { id.to_string() }),
+            )
+            .route(
+                "/search",
+                get(|Query(params): Query<Params>| async move { format!("{:?}", params.limit) }),
+            )
+    }
+
+    async fn send(request: Request<Body>) -> (StatusCode, ErrorResponse) {
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn post_json(body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/tracks")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_type_errors_name_the_field() {
+        let (status, body) = send(post_json(
+            r#"{"duration_minutes":"sixty","start_time":"2026-01-01T09:00:00Z"}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body.error, "VALIDATION_ERROR");
+        assert_eq!(body.details[0].field, "duration_minutes");
+        assert_eq!(body.details[0].code, FieldErrorCode::InvalidType);
+        assert!(!body.details[0].message.contains("line"));
+    }
+
+    #[tokio::test]
+    async fn json_missing_fields_are_required() {
+        let (_, body) = send(post_json(r#"{"duration_minutes":60}"#)).await;
+        assert_eq!(body.details[0].field, "start_time");
+        assert_eq!(body.details[0].code, FieldErrorCode::Required);
+    }
+
+    #[tokio::test]
+    async fn malformed_json_and_content_type() {
+        let (status, body) = send(post_json(r#"{"duration_minutes":"#)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.details[0].field, "body");
+        assert_eq!(body.details[0].code, FieldErrorCode::InvalidFormat);
+
+        let (status, body) = send(
+            Request::builder()
+                .method("POST")
+                .uri("/tracks")
+                .body(Body::from("{}"))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body.error, "UNSUPPORTED_MEDIA_TYPE");
+        assert!(body.details.is_empty());
+    }
+
+    #[tokio::test]
+    async fn path_and_query_errors_name_the_parameter() {
+        let (status, body) = send(
+            Request::builder()
+                .uri("/tracks/abc")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.details[0].field, "id");
+
+        let (status, body) = send(
+            Request::builder()
+                .uri("/search?limit=-1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error, "VALIDATION_ERROR");
+        assert_eq!(body.details.len(), 1);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use db_model::models::{DailyTrack, Topic, TopicGoal};
+use models::{GoalComparison, GoalPeriod, GoalProgress, db_goal_to_goal};
+
+/// How far back a streak is followed. Keeps the track query bounded for goals
+/// that have been met every day for a long time.
+pub(crate) const STREAK_LOOKBACK_DAYS: i64 = 365;
+
+/// Returns the inclusive `(start, end)` days covered by `period` around `date`.
+/// Weeks are ISO weeks (Monday to Sunday).
+pub(crate) fn period_bounds(date: NaiveDate, period: GoalPeriod) -> (NaiveDate, NaiveDate) {
+    match period {
+        GoalPeriod::Day => (date, date),
+        GoalPeriod::Week => {
+            let start = date - Duration::days(i64::from(date.weekday().num_days_from_monday()));
+            (start, start + Duration::days(6))
+        }
+    }
+}
+
+/// The goal's topic plus, when `include_subtopics` is set, every topic below it.
+pub(crate) fn goal_topic_ids(topics: &[Topic], root: i64, include_subtopics: bool) -> HashSet<i64> {
+    let mut ids = HashSet::from([root]);
+    if !include_subtopics {
+        return ids;
+    }
+
+    let mut children: HashMap<i64, Vec<i64>> = HashMap::new();
+    for topic in topics {
+        if let Some(parent) = topic.parent_topic_id {
+            children.entry(parent).or_default().push(topic.id);
+        }
+    }
+
+    let mut pending = vec![root];
+    while let Some(id) = pending.pop() {
+        for &child in children.get(&id).into_iter().flatten() {
+            if ids.insert(child) {
+                pending.push(child);
+            }
+        }
+    }
+    ids
+}
+
+/// Sums tracked minutes per UTC day for tracks whose topic is in `topic_ids`.
+/// A track that crosses midnight is split, so 23:30 + 60 minutes counts 30
+/// minutes toward each day.
+pub(crate) fn minutes_by_day(
+    tracks: &[DailyTrack],
+    topic_ids: &HashSet<i64>,
+) -> HashMap<NaiveDate, i64> {
+    let mut totals: HashMap<NaiveDate, i64> = HashMap::new();
+    for track in tracks {
+        if !track.topic_id.is_some_and(|id| topic_ids.contains(&id)) {
+            continue;
+        }
+
+        let mut cursor = track.start_time;
+        let end = track.start_time + Duration::minutes(i64::from(track.duration_minutes));
+        while cursor < end {
+            let next_midnight = (cursor.date() + Duration::days(1))
+                .and_hms_opt(0, 0, 0)
+                .expect("valid midnight");
+            let slice_end = end.min(next_midnight);
+            *totals.entry(cursor.date()).or_default() += (slice_end - cursor).num_minutes();
+            cursor = slice_end;
+        }
+    }
+    totals
+}
+
+/// Counts consecutive days ending at `end` on which the goal was met, never
+/// going further back than `earliest`. When `end` is today and the goal is not
+/// met yet, the day is still in progress and the run ending yesterday counts.
+pub(crate) fn current_streak(
+    daily_minutes: &HashMap<NaiveDate, i64>,
+    comparison: GoalComparison,
+    target_minutes: i64,
+    end: NaiveDate,
+    earliest: NaiveDate,
+    today: NaiveDate,
+) -> u32 {
+    let met_on = |day: NaiveDate| {
+        comparison.is_met(
+            daily_minutes.get(&day).copied().unwrap_or(0),
+            target_minutes,
+        )
+    };
+
+    let mut day = end;
+    if day >= today && !met_on(day) {
+        day -= Duration::days(1);
+    }
+
+    let mut streak = 0;
+    while day >= earliest && met_on(day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+/// Builds the progress report for one goal. `tracks` must cover the period and
+/// the streak lookback window; `topics` are all of the user's topics.
+pub(crate) fn goal_progress(
+    goal: &TopicGoal,
+    topics: &[Topic],
+    tracks: &[DailyTrack],
+    date: NaiveDate,
+    period: GoalPeriod,
+    today: NaiveDate,
+) -> GoalProgress {
+    let goal_api = db_goal_to_goal(goal);
+    let comparison = goal_api.comparison;
+    let daily_target = i64::from(goal.target_minutes);
+
+    let topic_ids = goal_topic_ids(topics, goal.topic_id, goal.include_subtopics);
+    let daily_minutes = minutes_by_day(tracks, &topic_ids);
+
+    let (period_start, period_end) = period_bounds(date, period);
+    let days_in_period = (period_end - period_start).num_days() as u32 + 1;
+
+    let mut actual_minutes = 0;
+    let mut days_met = 0;
+    for offset in 0..days_in_period {
+        let day = period_start + Duration::days(i64::from(offset));
+        let minutes = daily_minutes.get(&day).copied().unwrap_or(0);
+        actual_minutes += minutes;
+        if comparison.is_met(minutes, daily_target) {
+            days_met += 1;
+        }
+    }
+
+    let target_minutes = daily_target * i64::from(days_in_period);
+    let streak_end = period_end.min(today);
+    let earliest = goal
+        .created_at
+        .date()
+        .max(streak_end - Duration::days(STREAK_LOOKBACK_DAYS));
+
+    GoalProgress {
+        goal: goal_api,
+        period,
+        period_start,
+        period_end,
+        target_minutes,
+        actual_minutes,
+        met: comparison.is_met(actual_minutes, target_minutes),
+        days_met,
+        days_in_period,
+        current_streak_days: current_streak(
+            &daily_minutes,
+            comparison,
+            daily_target,
+            streak_end,
+            earliest,
+            today,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn topic(id: i64, parent: Option<i64>) -> Topic {
+        Topic {
+            id,
+            topic_name: format!("t{}", id),
+            display_color: "#3b82f6".to_string(),
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+            parent_topic_id: parent,
+            user_id: Some(1),
+            version: 1,
+        }
+    }
+
+    fn track(start: &str, topic_id: i64, duration_minutes: i32) -> DailyTrack {
+        DailyTrack {
+            id: 0,
+            start_time: at(start),
+            created_at: at(start),
+            updated_at: None,
+            topic_id: Some(topic_id),
+            comment: None,
+            user_id: Some(1),
+            duration_minutes,
+            version: 1,
+        }
+    }
+
+    fn goal(comparison: &str, target_minutes: i32, include_subtopics: bool) -> TopicGoal {
+        TopicGoal {
+            id: 9,
+            user_id: 1,
+            topic_id: 1,
+            comparison: comparison.to_string(),
+            target_minutes,
+            include_subtopics,
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn period_bounds_week_starts_on_monday() {
+        // 2026-05-14 is a Thursday.
+        let (start, end) = period_bounds(day("2026-05-14"), GoalPeriod::Week);
+        assert_eq!(start, day("2026-05-11"));
+        assert_eq!(end, day("2026-05-17"));
+    }
+
+    #[test]
+    fn period_bounds_day_is_single_day() {
+        let (start, end) = period_bounds(day("2026-05-14"), GoalPeriod::Day);
+        assert_eq!(start, end);
+    }
+
+    #[test]
+    fn goal_topic_ids_follows_nested_subtopics() {
+        let topics = vec![
+            topic(1, None),
+            topic(2, Some(1)),
+            topic(3, Some(2)),
+            topic(4, None),
+        ];
+        let ids = goal_topic_ids(&topics, 1, true);
+        assert_eq!(ids, HashSet::from([1, 2, 3]));
+        assert_eq!(goal_topic_ids(&topics, 1, false), HashSet::from([1]));
+    }
+
+    #[test]
+    fn minutes_by_day_splits_tracks_across_midnight() {
+        let tracks = vec![track("2026-05-11 23:30", 1, 60)];
+        let totals = minutes_by_day(&tracks, &HashSet::from([1]));
+        assert_eq!(totals.get(&day("2026-05-11")), Some(&30));
+        assert_eq!(totals.get(&day("2026-05-12")), Some(&30));
+    }
+
+    #[test]
+    fn minutes_by_day_ignores_other_topics() {
+        let tracks = vec![track("2026-05-11 09:00", 2, 60)];
+        assert!(minutes_by_day(&tracks, &HashSet::from([1])).is_empty());
+    }
+
+    #[test]
+    fn current_streak_skips_unfinished_today() {
+        let totals = HashMap::from([(day("2026-05-10"), 120), (day("2026-05-11"), 120)]);
+        let streak = current_streak(
+            &totals,
+            GoalComparison::AtLeast,
+            120,
+            day("2026-05-12"),
+            day("2026-01-01"),
+            day("2026-05-12"),
+        );
+        assert_eq!(streak, 2);
+    }
+
+    #[test]
+    fn current_streak_stops_at_earliest_for_budgets() {
+        // An empty day meets an at_most budget, so only `earliest` ends the run.
+        let streak = current_streak(
+            &HashMap::new(),
+            GoalComparison::AtMost,
+            60,
+            day("2026-05-12"),
+            day("2026-05-10"),
+            day("2026-05-20"),
+        );
+        assert_eq!(streak, 3);
+    }
+
+    #[test]
+    fn goal_progress_week_counts_subtopics() {
+        let topics = vec![topic(1, None), topic(2, Some(1))];
+        let tracks = vec![
+            track("2026-05-11 09:00", 1, 60),
+            track("2026-05-11 10:00", 2, 60),
+            track("2026-05-12 09:00", 2, 30),
+        ];
+        let progress = goal_progress(
+            &goal("at_least", 120, true),
+            &topics,
+            &tracks,
+            day("2026-05-13"),
+            GoalPeriod::Week,
+            day("2026-05-20"),
+        );
+
+        assert_eq!(progress.period_start, day("2026-05-11"));
+        assert_eq!(progress.days_in_period, 7);
+        assert_eq!(progress.actual_minutes, 150);
+        assert_eq!(progress.target_minutes, 120 * 7);
+        assert!(!progress.met);
+        assert_eq!(progress.days_met, 1);
+        // Sunday 2026-05-17 had nothing tracked, so the streak is broken.
+        assert_eq!(progress.current_streak_days, 0);
+    }
+
+    #[test]
+    fn goal_progress_day_budget() {
+        let topics = vec![topic(1, None)];
+        let tracks = vec![track("2026-05-12 20:00", 1, 90)];
+        let progress = goal_progress(
+            &goal("at_most", 60, false),
+            &topics,
+            &tracks,
+            day("2026-05-12"),
+            GoalPeriod::Day,
+            day("2026-05-12"),
+        );
+
+        assert_eq!(progress.actual_minutes, 90);
+        assert!(!progress.met);
+        assert_eq!(progress.days_met, 0);
+        // Today is over budget already; every earlier day since creation was
+        // empty and therefore within budget.
+        assert_eq!(
+            progress.current_streak_days,
+            (day("2026-05-11") - day("2026-01-01")).num_days() as u32 + 1
+        );
+    }
+}
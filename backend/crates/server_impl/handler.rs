@@ -1,11 +1,14 @@
-use axum::Json;
-use axum::extract::{Extension, Path, Query};
-use axum::http::StatusCode;
+use axum::extract::Extension;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
 use bcrypt::{DEFAULT_COST, hash, verify};
 use chrono::NaiveDate;
 use db_model::models::DEFAULT_TOPIC_DISPLAY_COLOR;
 use models::*;
 
+use crate::conditional::{hashed_json, if_match, tagged_json, version_etag};
+use crate::extract::{Json, Path, Query};
+
 // --- Validation Helpers ---
 pub(crate) fn is_valid_email(email: &str) -> bool {
     let parts: Vec<&str> = email.splitn(2, '@').collect();
@@ -38,49 +41,159 @@ pub(crate) fn is_valid_hex_color(value: &str) -> bool {
 /// Bounds the overlap query window and prevents pathological values.
 const MAX_DURATION_MINUTES: i32 = 24 * 60;
 
-pub(crate) fn validate_duration_minutes(duration_minutes: i32) -> Result<(), ApiError> {
+pub(crate) fn validate_duration_minutes(duration_minutes: i32) -> Result<(), FieldError> {
     if duration_minutes <= 0 || duration_minutes % 30 != 0 {
-        return Err(ApiError::BadRequest(
-            "duration_minutes must be a positive multiple of 30".to_string(),
+        return Err(FieldError::new(
+            "duration_minutes",
+            FieldErrorCode::InvalidValue,
+            "duration_minutes must be a positive multiple of 30",
         ));
     }
     if duration_minutes > MAX_DURATION_MINUTES {
-        return Err(ApiError::BadRequest(format!(
-            "duration_minutes must not exceed {} (24 hours)",
-            MAX_DURATION_MINUTES
-        )));
+        return Err(FieldError::new(
+            "duration_minutes",
+            FieldErrorCode::OutOfRange,
+            format!(
+                "duration_minutes must not exceed {} (24 hours)",
+                MAX_DURATION_MINUTES
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_start_time(
+    start_time: &chrono::DateTime<chrono::Utc>,
+) -> Result<(), FieldError> {
+    let minutes = start_time.format("%M").to_string();
+    if minutes != "00" && minutes != "30" {
+        return Err(FieldError::new(
+            "start_time",
+            FieldErrorCode::InvalidValue,
+            "start_time must be at :00 or :30 minutes",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a topic name, returning it trimmed.
+fn validate_topic_name(topic_name: &str) -> Result<String, FieldError> {
+    let topic_name = topic_name.trim();
+    if topic_name.is_empty() {
+        return Err(FieldError::new(
+            "topic_name",
+            FieldErrorCode::Required,
+            "topic_name is required and cannot be empty",
+        ));
+    }
+    Ok(topic_name.to_string())
+}
+
+fn validate_display_color(display_color: &str) -> Result<(), FieldError> {
+    if !is_valid_hex_color(display_color) {
+        return Err(FieldError::new(
+            "display_color",
+            FieldErrorCode::InvalidFormat,
+            "display_color must be a valid hex color like #3b82f6",
+        ));
     }
     Ok(())
 }
 
+/// Parses an optional `YYYY-MM-DD` query parameter named `field`.
+fn parse_date_param(field: &str, value: Option<String>) -> Result<Option<NaiveDate>, FieldError> {
+    value
+        .map(|s| {
+            NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(|_| {
+                FieldError::new(
+                    field,
+                    FieldErrorCode::InvalidFormat,
+                    format!("Invalid {} format '{}'. Expected YYYY-MM-DD", field, s),
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Parses the `start_date` and `end_date` query parameters, reporting both
+/// when both are malformed.
+fn parse_date_range(
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<(Option<NaiveDate>, Option<NaiveDate>), ApiError> {
+    let mut errors = ValidationErrors::new();
+    let start_date = errors.check(parse_date_param("start_date", start_date));
+    let end_date = errors.check(parse_date_param("end_date", end_date));
+    errors.finish()?;
+    Ok((start_date.flatten(), end_date.flatten()))
+}
+
+/// A PATCH field that may be left out but not set to `null`.
+fn not_null<T>(field: &str, value: Option<Option<T>>) -> Result<Option<T>, FieldError> {
+    match value {
+        Some(None) => Err(FieldError::new(
+            field,
+            FieldErrorCode::Required,
+            format!("{} cannot be null", field),
+        )),
+        value => Ok(value.flatten()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/topics",
+    tag = "Topic",
+    operation_id = "getTopics",
+    summary = "Get all topics",
+    description = "Retrieves a list of all topics. Supports optional filtering by parent_topic_id.",
+    params(GetTopicsParams, ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the list is unchanged")),
+    responses(
+        (status = 200, description = "Successful operation", body = [Topic], headers(("ETag" = String, description = "Tag of this list"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn get_topics(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Query(params): Query<GetTopicsParams>,
-) -> Result<Json<Vec<Topic>>, ApiError> {
-    let topics = db::get_topics(params.parent_topic_id, Some(user_id)).map_err(|e| {
-        log::error!("Failed to retrieve topics: {}", e);
-        ApiError::InternalServerError("Failed to retrieve topics".to_string())
-    })?;
+) -> Result<Response, ApiError> {
+    let topics = db::get_topics(params.parent_topic_id, Some(user_id)).await?;
 
-    let topics: Vec<Topic> = topics.iter().map(|t| db_topic_to_topic(t)).collect();
-    Ok(Json(topics))
+    let topics: Vec<Topic> = topics.iter().map(db_topic_to_topic).collect();
+    hashed_json(&headers, &topics)
 }
 
+#[utoipa::path(
+    post,
+    path = "/topics",
+    tag = "Topic",
+    operation_id = "createTopic",
+    summary = "Create a new topic",
+    description = "Creates a new topic with the given name and optional parent topic.",
+    request_body = CreateTopicRequest,
+    responses(
+        (status = 201, description = "Topic created successfully", body = Topic, headers(("ETag" = String, description = "Current version of the topic"))),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 409, description = "Topic name already exists (`DUPLICATE_NAME`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn create_topic(
     Extension(user_id): Extension<i64>,
     Json(req): Json<CreateTopicRequest>,
-) -> Result<(StatusCode, Json<Topic>), ApiError> {
-    if req.topic_name.trim().is_empty() {
-        return Err(ApiError::BadRequest(
-            "topic_name is required and cannot be empty".to_string(),
-        ));
-    }
+) -> Result<Response, ApiError> {
+    let display_color = req
+        .display_color
+        .unwrap_or_else(|| DEFAULT_TOPIC_DISPLAY_COLOR.to_string());
+    let mut errors = ValidationErrors::new();
+    errors.check(validate_topic_name(&req.topic_name));
+    errors.check(validate_display_color(&display_color));
+    errors.finish()?;
 
     let parent_topic_id = if let Some(parent_id) = req.parent_topic_id {
-        let parent = db::get_topic_by_id_for_user(parent_id, user_id).map_err(|e| {
-            log::error!("Failed to verify parent topic: {}", e);
-            ApiError::InternalServerError("Failed to verify parent topic".to_string())
-        })?;
+        let parent = db::get_topic_by_id_for_user(parent_id, user_id).await?;
         let parent = parent.ok_or_else(|| {
             ApiError::NotFound(format!("Parent topic with id {} not found", parent_id))
         })?;
@@ -89,46 +202,49 @@ pub async fn create_topic(
         None
     };
 
-    let display_color = req
-        .display_color
-        .unwrap_or_else(|| DEFAULT_TOPIC_DISPLAY_COLOR.to_string());
-    if !is_valid_hex_color(&display_color) {
-        return Err(ApiError::BadRequest(
-            "display_color must be a valid hex color like #3b82f6".to_string(),
-        ));
-    }
-
     let db_topic = db::create_topic(
         req.topic_name,
         display_color,
         parent_topic_id,
         Some(user_id),
     )
-    .map_err(|e| match e {
-        diesel::result::Error::DatabaseError(
-            diesel::result::DatabaseErrorKind::UniqueViolation,
-            _,
-        ) => ApiError::Conflict("Topic name already exists".to_string()),
-        _ => {
-            log::error!("Failed to create topic: {}", e);
-            ApiError::InternalServerError("Failed to create topic".to_string())
-        }
-    })?;
+    .await?;
 
-    Ok((StatusCode::CREATED, Json(db_topic_to_topic(&db_topic))))
+    Ok((
+        StatusCode::CREATED,
+        [(header::ETAG, version_etag(db_topic.version))],
+        Json(db_topic_to_topic(&db_topic)),
+    )
+        .into_response())
 }
 
+#[utoipa::path(
+    get,
+    path = "/topics/{id}",
+    tag = "Topic",
+    operation_id = "getTopicById",
+    summary = "Get a topic by ID",
+    description = "Retrieves a single topic by its ID.",
+    params(
+        ("id" = String, Path, description = "ID of the topic to retrieve"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the topic is unchanged"),
+    ),
+    responses(
+        (status = 200, description = "Successful operation", body = Topic, headers(("ETag" = String, description = "Current version of the topic"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+        (status = 404, description = "Topic not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn get_topic_by_id(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Path(id): Path<i64>,
-) -> Result<Json<Topic>, ApiError> {
-    let topic = db::get_topic_by_id_for_user(id, user_id).map_err(|e| {
-        log::error!("Failed to retrieve topic: {}", e);
-        ApiError::InternalServerError("Failed to retrieve topic".to_string())
-    })?;
+) -> Result<Response, ApiError> {
+    let topic = db::get_topic_by_id_for_user(id, user_id).await?;
 
     match topic {
-        Some(t) => Ok(Json(db_topic_to_topic(&t))),
+        Some(t) => tagged_json(&headers, version_etag(t.version), &db_topic_to_topic(&t)),
         None => Err(ApiError::NotFound(format!(
             "Topic with id {} not found",
             id
@@ -136,37 +252,104 @@ pub async fn get_topic_by_id(
     }
 }
 
+#[utoipa::path(
+    put,
+    path = "/topics/{id}",
+    tag = "Topic",
+    operation_id = "updateTopic",
+    summary = "Update a topic",
+    description = "Updates a topic name and display color by its ID. Send the topic's ETag in If-Match to update only if nobody changed it since it was read.",
+    params(
+        ("id" = String, Path, description = "ID of the topic to update"),
+        ("If-Match" = Option<String>, Header, description = "ETag the topic must still have"),
+    ),
+    request_body = UpdateTopicRequest,
+    responses(
+        (status = 200, description = "Topic updated successfully", body = Topic, headers(("ETag" = String, description = "New version of the topic"))),
+        (status = 400, description = "Invalid request payload", body = ErrorResponse),
+        (status = 404, description = "Topic not found", body = ErrorResponse),
+        (status = 409, description = "Topic name already exists (`DUPLICATE_NAME`)", body = ErrorResponse),
+        (status = 412, description = "The topic changed since it was read (`PRECONDITION_FAILED`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn update_topic(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Path(id): Path<i64>,
     Json(req): Json<UpdateTopicRequest>,
-) -> Result<Json<Topic>, ApiError> {
-    let topic_name = req.topic_name.trim().to_string();
-    if topic_name.is_empty() {
-        return Err(ApiError::BadRequest(
-            "topic_name is required and cannot be empty".to_string(),
-        ));
-    }
-    if !is_valid_hex_color(&req.display_color) {
-        return Err(ApiError::BadRequest(
-            "display_color must be a valid hex color like #3b82f6".to_string(),
-        ));
-    }
+) -> Result<Response, ApiError> {
+    let mut errors = ValidationErrors::new();
+    let topic_name = errors.check(validate_topic_name(&req.topic_name));
+    errors.check(validate_display_color(&req.display_color));
+    errors.finish()?;
+
+    let change = db::TopicChange {
+        topic_name,
+        display_color: Some(req.display_color),
+    };
+    let updated = db::update_topic(id, change, user_id, if_match(&headers)).await?;
+    updated_topic_response(id, updated)
+}
 
-    let updated =
-        db::update_topic(id, topic_name, req.display_color, user_id).map_err(|e| match e {
-            diesel::result::Error::DatabaseError(
-                diesel::result::DatabaseErrorKind::UniqueViolation,
-                _,
-            ) => ApiError::Conflict("Topic name already exists".to_string()),
-            _ => {
-                log::error!("Failed to update topic: {}", e);
-                ApiError::InternalServerError("Failed to update topic".to_string())
-            }
-        })?;
+#[utoipa::path(
+    patch,
+    path = "/topics/{id}",
+    tag = "Topic",
+    operation_id = "patchTopic",
+    summary = "Partially update a topic",
+    description = "Changes only the fields present in the body; fields left out keep their value and may not be null. Validation and If-Match work as for PUT.",
+    params(
+        ("id" = String, Path, description = "ID of the topic to update"),
+        ("If-Match" = Option<String>, Header, description = "ETag the topic must still have"),
+    ),
+    request_body = PatchTopicRequest,
+    responses(
+        (status = 200, description = "Topic updated successfully", body = Topic, headers(("ETag" = String, description = "New version of the topic"))),
+        (status = 400, description = "Invalid request payload", body = ErrorResponse),
+        (status = 404, description = "Topic not found", body = ErrorResponse),
+        (status = 409, description = "Topic name already exists (`DUPLICATE_NAME`)", body = ErrorResponse),
+        (status = 412, description = "The topic changed since it was read (`PRECONDITION_FAILED`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
+pub async fn patch_topic(
+    Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(req): Json<PatchTopicRequest>,
+) -> Result<Response, ApiError> {
+    let mut errors = ValidationErrors::new();
+    let topic_name = errors.check(
+        not_null("topic_name", req.topic_name)
+            .and_then(|name| name.map(|name| validate_topic_name(&name)).transpose()),
+    );
+    let display_color = errors.check(not_null("display_color", req.display_color).and_then(
+        |color| {
+            color.as_deref().map(validate_display_color).transpose()?;
+            Ok(color)
+        },
+    ));
+    errors.finish()?;
+
+    let change = db::TopicChange {
+        topic_name: topic_name.flatten(),
+        display_color: display_color.flatten(),
+    };
+    let updated = db::update_topic(id, change, user_id, if_match(&headers)).await?;
+    updated_topic_response(id, updated)
+}
 
+fn updated_topic_response(
+    id: i64,
+    updated: Option<db_model::models::Topic>,
+) -> Result<Response, ApiError> {
     match updated {
-        Some(topic) => Ok(Json(db_topic_to_topic(&topic))),
+        Some(topic) => Ok((
+            [(header::ETAG, version_etag(topic.version))],
+            Json(db_topic_to_topic(&topic)),
+        )
+            .into_response()),
         None => Err(ApiError::NotFound(format!(
             "Topic with id {} not found",
             id
@@ -176,64 +359,63 @@ pub async fn update_topic(
 
 // --- DailyTrack Handlers ---
 
+#[utoipa::path(
+    get,
+    path = "/daily-tracks",
+    tag = "DailyTrack",
+    operation_id = "getDailyTracks",
+    summary = "Get daily track records",
+    description = "Retrieves a list of daily track records. Supports filtering by date range and topic.",
+    params(GetDailyTracksParams, ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the list is unchanged")),
+    responses(
+        (status = 200, description = "Successful operation", body = [DailyTrack], headers(("ETag" = String, description = "Tag of this list"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+        (status = 400, description = "Invalid date format or parameters", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn get_daily_tracks(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Query(params): Query<GetDailyTracksParams>,
-) -> Result<Json<Vec<DailyTrack>>, ApiError> {
-    let start_date = params
-        .start_date
-        .map(|s| {
-            NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(|_| {
-                ApiError::BadRequest(format!(
-                    "Invalid start_date format '{}'. Expected YYYY-MM-DD",
-                    s
-                ))
-            })
-        })
-        .transpose()?;
+) -> Result<Response, ApiError> {
+    let (start_date, end_date) = parse_date_range(params.start_date, params.end_date)?;
 
-    let end_date = params
-        .end_date
-        .map(|s| {
-            NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(|_| {
-                ApiError::BadRequest(format!(
-                    "Invalid end_date format '{}'. Expected YYYY-MM-DD",
-                    s
-                ))
-            })
-        })
-        .transpose()?;
-
-    let tracks = db::get_daily_tracks(start_date, end_date, params.topic_id, Some(user_id))
-        .map_err(|e| {
-            log::error!("Failed to retrieve daily tracks: {}", e);
-            ApiError::InternalServerError("Failed to retrieve daily tracks".to_string())
-        })?;
+    let tracks = db::get_daily_tracks(start_date, end_date, params.topic_id, Some(user_id)).await?;
 
     let tracks: Vec<DailyTrack> = tracks
         .iter()
-        .map(|t| db_daily_track_to_daily_track(t))
+        .map(db_daily_track_to_daily_track)
         .collect();
-    Ok(Json(tracks))
+    hashed_json(&headers, &tracks)
 }
 
+#[utoipa::path(
+    post,
+    path = "/daily-tracks",
+    tag = "DailyTrack",
+    operation_id = "createDailyTrack",
+    summary = "Create a new daily track record",
+    description = "Creates a new daily track record. The start_time must be at :00 or :30 minutes of an hour. duration_minutes must be a positive multiple of 30 (max 1440). Tracks for the same user may not overlap.",
+    request_body = CreateDailyTrackRequest,
+    responses(
+        (status = 201, description = "Daily track record created successfully", body = DailyTrack, headers(("ETag" = String, description = "Current version of the track"))),
+        (status = 400, description = "Invalid input (e.g., start_time not at :00 or :30, or duration_minutes not a positive multiple of 30)", body = ErrorResponse),
+        (status = 404, description = "Referenced topic not found", body = ErrorResponse),
+        (status = 409, description = "The time range overlaps another track (`TRACK_OVERLAP`); `conflicting_track_id` names it", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn create_daily_track(
     Extension(user_id): Extension<i64>,
     Json(req): Json<CreateDailyTrackRequest>,
-) -> Result<(StatusCode, Json<DailyTrack>), ApiError> {
-    let minutes = req.start_time.format("%M").to_string();
-    if minutes != "00" && minutes != "30" {
-        return Err(ApiError::BadRequest(
-            "start_time must be at :00 or :30 minutes".to_string(),
-        ));
-    }
-
-    validate_duration_minutes(req.duration_minutes)?;
+) -> Result<Response, ApiError> {
+    let mut errors = ValidationErrors::new();
+    errors.check(validate_start_time(&req.start_time));
+    errors.check(validate_duration_minutes(req.duration_minutes));
+    errors.finish()?;
 
-    let topic = db::get_topic_by_id_for_user(req.topic_id, user_id).map_err(|e| {
-        log::error!("Failed to verify topic: {}", e);
-        ApiError::InternalServerError("Failed to verify topic".to_string())
-    })?;
+    let topic = db::get_topic_by_id_for_user(req.topic_id, user_id).await?;
 
     if topic.is_none() {
         return Err(ApiError::NotFound(format!(
@@ -252,36 +434,47 @@ pub async fn create_daily_track(
         Some(user_id),
         req.duration_minutes,
     )
-    .map_err(|e| match e {
-        diesel::result::Error::DatabaseError(
-            diesel::result::DatabaseErrorKind::UniqueViolation,
-            _,
-        ) => ApiError::Conflict(
-            "An overlapping record already exists for this time period".to_string(),
-        ),
-        _ => {
-            log::error!("Failed to create daily track: {}", e);
-            ApiError::InternalServerError("Failed to create daily track".to_string())
-        }
-    })?;
+    .await?;
 
     Ok((
         StatusCode::CREATED,
+        [(header::ETAG, version_etag(db_track.version))],
         Json(db_daily_track_to_daily_track(&db_track)),
-    ))
+    )
+        .into_response())
 }
 
+#[utoipa::path(
+    get,
+    path = "/daily-tracks/{id}",
+    tag = "DailyTrack",
+    operation_id = "getDailyTrackById",
+    summary = "Get a daily track record by ID",
+    description = "Retrieves a single daily track record by its ID.",
+    params(
+        ("id" = String, Path, description = "ID of the daily track record to retrieve"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the track is unchanged"),
+    ),
+    responses(
+        (status = 200, description = "Successful operation", body = DailyTrack, headers(("ETag" = String, description = "Current version of the track"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+        (status = 404, description = "Daily track record not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn get_daily_track_by_id(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Path(id): Path<i64>,
-) -> Result<Json<DailyTrack>, ApiError> {
-    let track = db::get_daily_track_by_id(id, user_id).map_err(|e| {
-        log::error!("Failed to retrieve daily track: {}", e);
-        ApiError::InternalServerError("Failed to retrieve daily track".to_string())
-    })?;
+) -> Result<Response, ApiError> {
+    let track = db::get_daily_track_by_id(id, user_id).await?;
 
     match track {
-        Some(t) => Ok(Json(db_daily_track_to_daily_track(&t))),
+        Some(t) => tagged_json(
+            &headers,
+            version_etag(t.version),
+            &db_daily_track_to_daily_track(&t),
+        ),
         None => Err(ApiError::NotFound(format!(
             "Daily track with id {} not found",
             id
@@ -289,41 +482,115 @@ pub async fn get_daily_track_by_id(
     }
 }
 
+#[utoipa::path(
+    put,
+    path = "/daily-tracks/{id}",
+    tag = "DailyTrack",
+    operation_id = "updateDailyTrack",
+    summary = "Update a daily track record",
+    description = "Updates a daily track record's topic, comment, and duration by its ID. duration_minutes must be a positive multiple of 30 (max 1440). The new duration may not cause the track to overlap any other track for the same user. Send the track's ETag in If-Match to update only if nobody changed it since it was read.",
+    params(
+        ("id" = String, Path, description = "ID of the daily track record to update"),
+        ("If-Match" = Option<String>, Header, description = "ETag the track must still have"),
+    ),
+    request_body = UpdateDailyTrackRequest,
+    responses(
+        (status = 200, description = "Daily track updated successfully", body = DailyTrack, headers(("ETag" = String, description = "New version of the track"))),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Daily track or referenced topic not found", body = ErrorResponse),
+        (status = 409, description = "The time range overlaps another track (`TRACK_OVERLAP`); `conflicting_track_id` names it", body = ErrorResponse),
+        (status = 412, description = "The track changed since it was read (`PRECONDITION_FAILED`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn update_daily_track(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Path(id): Path<i64>,
     Json(req): Json<UpdateDailyTrackRequest>,
-) -> Result<Json<DailyTrack>, ApiError> {
+) -> Result<Response, ApiError> {
     validate_duration_minutes(req.duration_minutes)?;
 
-    let topic = db::get_topic_by_id_for_user(req.topic_id, user_id).map_err(|e| {
-        log::error!("Failed to verify topic: {}", e);
-        ApiError::InternalServerError("Failed to verify topic".to_string())
-    })?;
+    let change = db::TrackChange {
+        topic_id: Some(owned_topic_id(req.topic_id, user_id).await?),
+        comment: Some(req.comment),
+        duration_minutes: Some(req.duration_minutes),
+    };
+    let track = db::update_daily_track(id, change, user_id, if_match(&headers)).await?;
+    updated_track_response(id, track)
+}
 
-    let Some(topic) = topic else {
-        return Err(ApiError::NotFound(format!(
-            "Topic with id {} not found",
-            req.topic_id
-        )));
+#[utoipa::path(
+    patch,
+    path = "/daily-tracks/{id}",
+    tag = "DailyTrack",
+    operation_id = "patchDailyTrack",
+    summary = "Partially update a daily track record",
+    description = "Changes only the fields present in the body; fields left out keep their value. Set comment to null to clear it; topic_id and duration_minutes may not be null. Validation, overlap checks and If-Match work as for PUT.",
+    params(
+        ("id" = String, Path, description = "ID of the daily track record to update"),
+        ("If-Match" = Option<String>, Header, description = "ETag the track must still have"),
+    ),
+    request_body = PatchDailyTrackRequest,
+    responses(
+        (status = 200, description = "Daily track updated successfully", body = DailyTrack, headers(("ETag" = String, description = "New version of the track"))),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Daily track or referenced topic not found", body = ErrorResponse),
+        (status = 409, description = "The time range overlaps another track (`TRACK_OVERLAP`); `conflicting_track_id` names it", body = ErrorResponse),
+        (status = 412, description = "The track changed since it was read (`PRECONDITION_FAILED`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
+pub async fn patch_daily_track(
+    Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(req): Json<PatchDailyTrackRequest>,
+) -> Result<Response, ApiError> {
+    let mut errors = ValidationErrors::new();
+    let topic_id = errors.check(not_null("topic_id", req.topic_id));
+    let duration_minutes = errors.check(
+        not_null("duration_minutes", req.duration_minutes).and_then(|duration| {
+            duration.map(validate_duration_minutes).transpose()?;
+            Ok(duration)
+        }),
+    );
+    errors.finish()?;
+
+    let topic_id = match topic_id.flatten() {
+        Some(topic_id) => Some(owned_topic_id(topic_id, user_id).await?),
+        None => None,
     };
+    let change = db::TrackChange {
+        topic_id,
+        comment: req.comment,
+        duration_minutes: duration_minutes.flatten(),
+    };
+    let track = db::update_daily_track(id, change, user_id, if_match(&headers)).await?;
+    updated_track_response(id, track)
+}
 
-    let track = db::update_daily_track(id, topic.id, req.comment, user_id, req.duration_minutes)
-        .map_err(|e| match e {
-            diesel::result::Error::DatabaseError(
-                diesel::result::DatabaseErrorKind::UniqueViolation,
-                _,
-            ) => ApiError::Conflict(
-                "An overlapping record already exists for this time period".to_string(),
-            ),
-            _ => {
-                log::error!("Failed to update daily track: {}", e);
-                ApiError::InternalServerError("Failed to update daily track".to_string())
-            }
-        })?;
+/// The id of the user's topic `topic_id`, or 404 when they have none.
+async fn owned_topic_id(topic_id: i64, user_id: i64) -> Result<i64, ApiError> {
+    match db::get_topic_by_id_for_user(topic_id, user_id).await? {
+        Some(topic) => Ok(topic.id),
+        None => Err(ApiError::NotFound(format!(
+            "Topic with id {} not found",
+            topic_id
+        ))),
+    }
+}
 
+fn updated_track_response(
+    id: i64,
+    track: Option<db_model::models::DailyTrack>,
+) -> Result<Response, ApiError> {
     match track {
-        Some(t) => Ok(Json(db_daily_track_to_daily_track(&t))),
+        Some(t) => Ok((
+            [(header::ETAG, version_etag(t.version))],
+            Json(db_daily_track_to_daily_track(&t)),
+        )
+            .into_response()),
         None => Err(ApiError::NotFound(format!(
             "Daily track with id {} not found",
             id
@@ -331,14 +598,30 @@ pub async fn update_daily_track(
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/daily-tracks/{id}",
+    tag = "DailyTrack",
+    operation_id = "deleteDailyTrack",
+    summary = "Delete a daily track record",
+    description = "Deletes a daily track record by its ID. Send the track's ETag in If-Match to delete only if nobody changed it since it was read.",
+    params(
+        ("id" = String, Path, description = "ID of the daily track record to delete"),
+        ("If-Match" = Option<String>, Header, description = "ETag the track must still have"),
+    ),
+    responses(
+        (status = 204, description = "Daily track deleted successfully"),
+        (status = 404, description = "Daily track not found", body = ErrorResponse),
+        (status = 412, description = "The track changed since it was read (`PRECONDITION_FAILED`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_daily_track(
     Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, ApiError> {
-    let deleted = db::delete_daily_track(id, user_id).map_err(|e| {
-        log::error!("Failed to delete daily track: {}", e);
-        ApiError::InternalServerError("Failed to delete daily track".to_string())
-    })?;
+    let deleted = db::delete_daily_track(id, user_id, if_match(&headers)).await?;
 
     if deleted {
         Ok(StatusCode::NO_CONTENT)
@@ -350,54 +633,71 @@ pub async fn delete_daily_track(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    tag = "Auth",
+    operation_id = "register",
+    summary = "Register a new user",
+    description = "Registers a new user and returns user info.",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Success", body = UserResponse),
+    ),
+    security(()),
+)]
 pub async fn register(
+    headers: axum::http::HeaderMap,
     Json(req): Json<RegisterRequest>,
 ) -> Result<(StatusCode, Json<UserResponse>), ApiError> {
     // Email format validation
+    let mut errors = ValidationErrors::new();
     let email = req.email.trim();
     if email.is_empty() || !is_valid_email(email) {
-        return Err(ApiError::BadRequest(
-            "A valid email address is required".to_string(),
+        errors.add(FieldError::new(
+            "email",
+            FieldErrorCode::InvalidFormat,
+            "A valid email address is required",
         ));
     }
-
     if req.password.len() < 8 {
-        return Err(ApiError::BadRequest(
-            "Password must be at least 8 characters".to_string(),
+        errors.add(FieldError::new(
+            "password",
+            FieldErrorCode::OutOfRange,
+            "Password must be at least 8 characters",
         ));
     }
+    errors.finish()?;
 
     let password_hash = hash(&req.password, DEFAULT_COST).map_err(|e| {
         log::error!("Failed to hash password: {}", e);
         ApiError::InternalServerError("Registration failed".to_string())
     })?;
 
-    let (user, code) =
-        db::create_user(req.username, req.email, password_hash).map_err(|e| match e {
-            diesel::result::Error::DatabaseError(
-                diesel::result::DatabaseErrorKind::UniqueViolation,
-                _,
-            ) => ApiError::Conflict("Username or email already exists".to_string()),
-            _ => {
-                log::error!("Database error during registration: {}", e);
-                ApiError::InternalServerError("Registration failed".to_string())
-            }
-        })?;
-
-    // Send verification email in the background
-    let email_to = user.email.clone();
-    let email_username = user.username.clone();
-    let email_code = code.clone();
-    let log_id = logging::current_log_id();
-    tokio::spawn(logging::LOG_ID.scope(log_id, async move {
-        if let Err(e) =
-            crate::email::send_verification_email(&email_to, &email_username, &email_code).await
-        {
-            log::error!("Failed to send verification email to {}: {}", email_to, e);
-        } else {
-            log::info!("Verification email sent to {}", email_to);
-        }
-    }));
+    let locale = crate::email_templates::get()
+        .negotiate(
+            req.locale
+                .as_deref()
+                .or_else(|| {
+                    headers
+                        .get(header::ACCEPT_LANGUAGE)
+                        .and_then(|h| h.to_str().ok())
+                })
+                .unwrap_or(crate::email_templates::DEFAULT_LOCALE),
+        )
+        .to_string();
+
+    // The verification email is queued with the user and sent by the outbox
+    // worker, which retries until it is delivered.
+    let (user, _) = db::create_user(
+        req.username,
+        req.email,
+        password_hash,
+        locale,
+        crate::email_outbox::verification_email,
+    )
+    .await?;
+    crate::email_outbox::wake();
 
     Ok((
         StatusCode::CREATED,
@@ -410,13 +710,24 @@ pub async fn register(
     ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/verify-email",
+    tag = "Auth",
+    operation_id = "verifyEmail",
+    summary = "Verify email address",
+    description = "Validates the verification code sent to the user's email. Must be called before login.",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Success", body = VerifyEmailResponse),
+        (status = 400, description = "Invalid or expired verification code", body = ErrorResponse),
+    ),
+    security(()),
+)]
 pub async fn verify_email(
     Json(req): Json<VerifyEmailRequest>,
 ) -> Result<Json<VerifyEmailResponse>, ApiError> {
-    let verified = db::verify_email_code(&req.email, &req.code).map_err(|e| {
-        log::error!("Database error during email verification: {}", e);
-        ApiError::InternalServerError("Verification failed".to_string())
-    })?;
+    let verified = db::verify_email_code(&req.email, &req.code).await?;
 
     if !verified {
         return Err(ApiError::BadRequest(
@@ -429,12 +740,22 @@ pub async fn verify_email(
     }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "Auth",
+    operation_id = "login",
+    summary = "Login and get a JWT token",
+    description = "Logs in the user and returns a token.",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Success", body = TokenResponse),
+    ),
+    security(()),
+)]
 pub async fn login(Json(req): Json<LoginRequest>) -> Result<Json<TokenResponse>, ApiError> {
     let user = db::get_user_by_username(&req.username)
-        .map_err(|e| {
-            log::error!("Database error during login: {}", e);
-            ApiError::InternalServerError("Login failed".to_string())
-        })?
+        .await?
         .ok_or_else(|| ApiError::Unauthorized("Invalid username or password".to_string()))?;
 
     let valid = verify(&req.password, &user.password_hash).map_err(|e| {
@@ -465,38 +786,67 @@ pub async fn login(Json(req): Json<LoginRequest>) -> Result<Json<TokenResponse>,
 
 // --- API Key Handlers ---
 
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    tag = "ApiKey",
+    operation_id = "listApiKeys",
+    summary = "List the current user's API keys",
+    description = "Returns all active API keys belonging to the authenticated user. Requires JWT authentication; API-key auth is rejected here.",
+    params(("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the list is unchanged")),
+    responses(
+        (status = 200, description = "Successful operation", body = [ApiKeyResponse], headers(("ETag" = String, description = "Tag of this list"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "API-key auth used; JWT required", body = ErrorResponse),
+    ),
+)]
 pub async fn list_api_keys(
     Extension(user_id): Extension<i64>,
-) -> Result<Json<Vec<ApiKeyResponse>>, ApiError> {
-    let keys = db::list_api_keys_for_user(user_id).map_err(|e| {
-        log::error!("Failed to list API keys: {}", e);
-        ApiError::InternalServerError("Failed to list API keys".to_string())
-    })?;
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let keys = db::list_api_keys_for_user(user_id).await?;
 
     let keys: Vec<ApiKeyResponse> = keys.iter().map(db_api_key_to_response).collect();
-    Ok(Json(keys))
+    hashed_json(&headers, &keys)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "ApiKey",
+    operation_id = "createApiKey",
+    summary = "Create a new API key",
+    description = "Creates a new API key for the authenticated user. The plaintext token is only returned in this response and cannot be retrieved later.",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created", body = CreateApiKeyResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "API-key auth used; JWT required", body = ErrorResponse),
+    ),
+)]
 pub async fn create_api_key(
     Extension(user_id): Extension<i64>,
     Json(req): Json<CreateApiKeyRequest>,
 ) -> Result<(StatusCode, Json<CreateApiKeyResponse>), ApiError> {
     let name = req.name.trim().to_string();
     if name.is_empty() {
-        return Err(ApiError::BadRequest(
-            "name is required and cannot be empty".to_string(),
-        ));
+        return Err(FieldError::new(
+            "name",
+            FieldErrorCode::Required,
+            "name is required and cannot be empty",
+        )
+        .into());
     }
 
-    let (record, token) = db::create_api_key(user_id, name).map_err(|e| {
-        log::error!("Failed to create API key: {}", e);
-        ApiError::InternalServerError("Failed to create API key".to_string())
-    })?;
+    let (record, token) = db::create_api_key(user_id, name, req.scope.as_str().to_string()).await?;
 
     let response = CreateApiKeyResponse {
         id: db_api_key_to_response(&record).id,
         name: record.name,
         key_prefix: record.key_prefix,
+        scope: req.scope,
         token,
         created_at: chrono::Utc::now().to_rfc3339(),
     };
@@ -504,14 +854,26 @@ pub async fn create_api_key(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    tag = "ApiKey",
+    operation_id = "revokeApiKey",
+    summary = "Revoke an API key",
+    description = "Marks the API key as revoked so it can no longer authenticate.",
+    params(("id" = String, Path)),
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "API-key auth used; JWT required", body = ErrorResponse),
+        (status = 404, description = "API key not found", body = ErrorResponse),
+    ),
+)]
 pub async fn revoke_api_key(
     Extension(user_id): Extension<i64>,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, ApiError> {
-    let revoked = db::revoke_api_key(id, user_id).map_err(|e| {
-        log::error!("Failed to revoke API key: {}", e);
-        ApiError::InternalServerError("Failed to revoke API key".to_string())
-    })?;
+    let revoked = db::revoke_api_key(id, user_id).await?;
 
     if revoked {
         Ok(StatusCode::NO_CONTENT)
@@ -523,6 +885,854 @@ pub async fn revoke_api_key(
     }
 }
 
+// --- Goal Handlers ---
+
+pub(crate) fn validate_goal_target_minutes(target_minutes: i32) -> Result<(), FieldError> {
+    if target_minutes <= 0 || target_minutes > MAX_DURATION_MINUTES {
+        return Err(FieldError::new(
+            "target_minutes",
+            FieldErrorCode::OutOfRange,
+            format!(
+                "target_minutes must be between 1 and {} (24 hours)",
+                MAX_DURATION_MINUTES
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/goals",
+    tag = "Goal",
+    operation_id = "listGoals",
+    summary = "List goals",
+    description = "Returns all goals belonging to the authenticated user.",
+    params(("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the list is unchanged")),
+    responses(
+        (status = 200, description = "Successful operation", body = [Goal], headers(("ETag" = String, description = "Tag of this list"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
+pub async fn list_goals(
+    Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let goals = db::list_topic_goals_for_user(user_id).await?;
+
+    let goals: Vec<Goal> = goals.iter().map(db_goal_to_goal).collect();
+    hashed_json(&headers, &goals)
+}
+
+#[utoipa::path(
+    post,
+    path = "/goals",
+    tag = "Goal",
+    operation_id = "createGoal",
+    summary = "Create a goal",
+    description = "Creates a daily target or budget for a topic. A topic can have at most one goal per comparison.",
+    request_body = CreateGoalRequest,
+    responses(
+        (status = 201, description = "Goal created", body = Goal),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Topic not found", body = ErrorResponse),
+        (status = 409, description = "A goal with this comparison already exists for the topic (`DUPLICATE_NAME`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
+pub async fn create_goal(
+    Extension(user_id): Extension<i64>,
+    Json(req): Json<CreateGoalRequest>,
+) -> Result<(StatusCode, Json<Goal>), ApiError> {
+    validate_goal_target_minutes(req.target_minutes)?;
+
+    let topic = db::get_topic_by_id_for_user(req.topic_id, user_id).await?;
+    let Some(topic) = topic else {
+        return Err(ApiError::NotFound(format!(
+            "Topic with id {} not found",
+            req.topic_id
+        )));
+    };
+
+    let goal = db::create_topic_goal(
+        user_id,
+        topic.id,
+        req.comparison.as_str().to_string(),
+        req.target_minutes,
+        req.include_subtopics,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(db_goal_to_goal(&goal))))
+}
+
+#[utoipa::path(
+    put,
+    path = "/goals/{id}",
+    tag = "Goal",
+    operation_id = "updateGoal",
+    summary = "Update a goal",
+    description = "Updates the comparison, target and subtopic scope of a goal.",
+    params(("id" = String, Path)),
+    request_body = UpdateGoalRequest,
+    responses(
+        (status = 200, description = "Goal updated", body = Goal),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Goal not found", body = ErrorResponse),
+        (status = 409, description = "A goal with this comparison already exists for the topic (`DUPLICATE_NAME`)", body = ErrorResponse),
+    ),
+)]
+pub async fn update_goal(
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+    Json(req): Json<UpdateGoalRequest>,
+) -> Result<Json<Goal>, ApiError> {
+    validate_goal_target_minutes(req.target_minutes)?;
+
+    let goal = db::update_topic_goal(
+        id,
+        user_id,
+        req.comparison.as_str().to_string(),
+        req.target_minutes,
+        req.include_subtopics,
+    )
+    .await?;
+
+    match goal {
+        Some(g) => Ok(Json(db_goal_to_goal(&g))),
+        None => Err(ApiError::NotFound(format!("Goal with id {} not found", id))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/goals/{id}",
+    tag = "Goal",
+    operation_id = "deleteGoal",
+    summary = "Delete a goal",
+    params(("id" = String, Path)),
+    responses(
+        (status = 204, description = "Goal deleted"),
+        (status = 404, description = "Goal not found", body = ErrorResponse),
+    ),
+)]
+pub async fn delete_goal(
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = db::delete_topic_goal(id, user_id).await?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("Goal with id {} not found", id)))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/goals/progress",
+    tag = "Goal",
+    operation_id = "getGoalProgress",
+    summary = "Report progress against goals",
+    description = "Computes tracked minutes for each goal over a day or ISO week, whether the goal was met, and the current streak of consecutive days on which it was met.",
+    params(GetGoalProgressParams),
+    responses(
+        (status = 200, description = "Successful operation", body = [GoalProgress]),
+        (status = 400, description = "Invalid date format", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    ),
+)]
+pub async fn get_goal_progress(
+    Extension(user_id): Extension<i64>,
+    Query(params): Query<GetGoalProgressParams>,
+) -> Result<Json<Vec<GoalProgress>>, ApiError> {
+    let today = chrono::Utc::now().date_naive();
+    let date = parse_date_param("date", params.date)?.unwrap_or(today);
+
+    let goals = db::list_topic_goals_for_user(user_id).await?;
+    if goals.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let topics = db::get_topics(None, Some(user_id)).await?;
+
+    // Load the period plus the streak lookback window. Start one day early so
+    // tracks that began the evening before and run past midnight are counted.
+    let (period_start, period_end) = crate::goals::period_bounds(date, params.period);
+    let lookback_start =
+        period_end.min(today) - chrono::Duration::days(crate::goals::STREAK_LOOKBACK_DAYS);
+    let load_start = period_start.min(lookback_start) - chrono::Duration::days(1);
+    let tracks =
+        db::get_daily_tracks(Some(load_start), Some(period_end), None, Some(user_id)).await?;
+
+    let progress = goals
+        .iter()
+        .map(|goal| crate::goals::goal_progress(goal, &topics, &tracks, date, params.period, today))
+        .collect();
+    Ok(Json(progress))
+}
+
+// --- Digest Handlers ---
+
+#[utoipa::path(
+    get,
+    path = "/account/digest",
+    tag = "Account",
+    operation_id = "getDigestSettings",
+    summary = "Get digest email settings",
+    description = "Returns the user's digest settings. Users who never saved any get the defaults with enabled set to false.",
+    responses(
+        (status = 200, description = "Digest settings", body = DigestSettings),
+    ),
+)]
+pub async fn get_digest_settings(
+    Extension(user_id): Extension<i64>,
+) -> Result<Json<DigestSettings>, ApiError> {
+    let subscription = db::get_digest_subscription(user_id).await?;
+
+    Ok(Json(match subscription {
+        Some(subscription) => db_digest_to_settings(&subscription),
+        None => DigestSettings {
+            enabled: false,
+            frequency: DigestFrequency::default(),
+            send_time: crate::digest::DEFAULT_SEND_TIME.to_string(),
+            time_zone: crate::digest::DEFAULT_TIME_ZONE.to_string(),
+            topic_ids: None,
+            last_period_start: None,
+        },
+    }))
+}
+
+/// Saves the digest settings. The period that is already over when the
+/// settings change is skipped, so the first digest arrives at the next send
+/// time rather than right away.
+#[utoipa::path(
+    put,
+    path = "/account/digest",
+    tag = "Account",
+    operation_id = "updateDigestSettings",
+    summary = "Update digest email settings",
+    description = "Turns the daily or weekly summary email on or off and sets its local send time, time zone and topics. The period that has already ended is not sent, so the first digest arrives at the next send time.",
+    request_body = UpdateDigestSettingsRequest,
+    responses(
+        (status = 200, description = "Settings saved", body = DigestSettings),
+        (status = 400, description = "Invalid send time or time zone", body = ErrorResponse),
+        (status = 404, description = "Topic not found", body = ErrorResponse),
+    ),
+)]
+pub async fn update_digest_settings(
+    Extension(user_id): Extension<i64>,
+    Json(req): Json<UpdateDigestSettingsRequest>,
+) -> Result<Json<DigestSettings>, ApiError> {
+    let send_time = crate::digest::parse_send_time(&req.send_time)
+        .map_err(|e| FieldError::new("send_time", FieldErrorCode::InvalidFormat, e));
+    let time_zone = crate::digest::parse_time_zone(&req.time_zone)
+        .map_err(|e| FieldError::new("time_zone", FieldErrorCode::InvalidValue, e));
+    let (send_time, time_zone) = match (send_time, time_zone) {
+        (Ok(send_time), Ok(time_zone)) => (send_time, time_zone),
+        (send_time, time_zone) => {
+            return Err(ApiError::Validation(
+                send_time.err().into_iter().chain(time_zone.err()).collect(),
+            ));
+        }
+    };
+
+    if let Some(topic_ids) = &req.topic_ids {
+        let topics = db::get_topics(None, Some(user_id)).await?;
+        if let Some(missing) = topic_ids
+            .iter()
+            .find(|id| !topics.iter().any(|t| t.id == **id))
+        {
+            return Err(ApiError::NotFound(format!(
+                "Topic with id {} not found",
+                missing
+            )));
+        }
+    }
+
+    let schedule = crate::digest::Schedule {
+        frequency: req.frequency,
+        send_time,
+        time_zone,
+    };
+    let skip_through = schedule.due_period(chrono::Utc::now()).start;
+    let subscription = db::save_digest_subscription(
+        user_id,
+        db::DigestSettingsChange {
+            enabled: req.enabled,
+            frequency: req.frequency.as_str().to_string(),
+            send_time,
+            time_zone: req.time_zone,
+            topic_ids: req
+                .topic_ids
+                .map(|ids| serde_json::to_string(&ids).expect("topic ids serialize")),
+            skip_through: Some(skip_through),
+        },
+    )
+    .await?;
+
+    Ok(Json(db_digest_to_settings(&subscription)))
+}
+
+/// Confirmation page behind the link in every digest. Link scanners and
+/// mail previews follow GET links, so the page only offers a form; the POST
+/// below is what unsubscribes.
+const UNSUBSCRIBE_CONFIRMATION_PAGE: &str = "<!DOCTYPE html>
+<html lang=\"en\">
+<head><meta charset=\"utf-8\"><meta name=\"robots\" content=\"noindex\"><title>Unsubscribe from digest emails</title></head>
+<body>
+<p>Stop receiving digest emails? You can turn them on again in your settings.</p>
+<form method=\"post\"><input type=\"hidden\" name=\"List-Unsubscribe\" value=\"One-Click\"><button type=\"submit\">Unsubscribe</button></form>
+</body>
+</html>
+";
+
+/// Unsubscribe link included in every digest. Renders a confirmation page
+/// and changes nothing; the token in the path is the only credential.
+#[utoipa::path(
+    get,
+    path = "/digest/unsubscribe/{token}",
+    tag = "Account",
+    operation_id = "unsubscribeDigest",
+    summary = "Confirm unsubscribing from digest emails",
+    description = "Unsubscribe link included in every digest. Does not take an Authorization header and does not change the subscription: it renders a page whose form POSTs to the same URL.",
+    params(("token" = String, Path)),
+    responses(
+        (status = 200, description = "Confirmation page", body = String, content_type = "text/html"),
+    ),
+    security(()),
+)]
+pub async fn unsubscribe_digest(Path(_token): Path<String>) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        UNSUBSCRIBE_CONFIRMATION_PAGE,
+    )
+        .into_response()
+}
+
+/// Unsubscribes the owner of the token. Sent by the confirmation page and
+/// by mail clients following `List-Unsubscribe-Post`.
+#[utoipa::path(
+    post,
+    path = "/digest/unsubscribe/{token}",
+    tag = "Account",
+    operation_id = "unsubscribeDigestOneClick",
+    summary = "Unsubscribe from digest emails (RFC 8058)",
+    description = "Turns digest emails off. Posted by the confirmation page behind the GET link and by mail clients that follow List-Unsubscribe-Post. Does not take an Authorization header: the token in the path is the credential.",
+    params(("token" = String, Path)),
+    responses(
+        (status = 200, description = "Unsubscribed", body = String, content_type = "text/plain"),
+        (status = 404, description = "Unknown unsubscribe link", body = ErrorResponse),
+    ),
+    security(()),
+)]
+pub async fn unsubscribe_digest_one_click(Path(token): Path<String>) -> Result<Response, ApiError> {
+    let found = db::unsubscribe_digest(&token).await?;
+    if !found {
+        return Err(ApiError::NotFound("Unknown unsubscribe link".to_string()));
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        "You have been unsubscribed from digest emails. You can turn them on again in your settings.\n",
+    )
+        .into_response())
+}
+
+// --- Calendar Handlers ---
+
+/// How far back the subscribable feed reaches when the client does not ask
+/// for a range. Calendar apps poll the whole feed on every refresh.
+const CALENDAR_FEED_DEFAULT_DAYS: i64 = 90;
+const CALENDAR_NAME: &str = "Daily Tracker";
+
+async fn render_user_calendar(
+    user_id: i64,
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+) -> Result<String, ApiError> {
+    let tracks = db::get_daily_tracks(start_date, end_date, None, Some(user_id)).await?;
+    let topics = db::get_topics(None, Some(user_id)).await?;
+    let topics = topics.into_iter().map(|t| (t.id, t)).collect();
+
+    Ok(crate::ical::render_calendar(
+        CALENDAR_NAME,
+        &tracks,
+        &topics,
+        chrono::Utc::now().naive_utc(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/daily-tracks/export.ics",
+    tag = "Calendar",
+    operation_id = "exportDailyTracksIcal",
+    summary = "Export daily tracks as iCalendar",
+    description = "Renders the user's daily tracks in the date range as a VCALENDAR. Each VEVENT carries the topic name (SUMMARY), the comment (DESCRIPTION) and the topic display color (X-DAILY-TRACKER-COLOR).",
+    params(CalendarExportParams),
+    responses(
+        (status = 200, description = "iCalendar document", body = String, content_type = "text/calendar"),
+        (status = 400, description = "Invalid date format", body = ErrorResponse),
+    ),
+)]
+pub async fn export_daily_tracks_ical(
+    Extension(user_id): Extension<i64>,
+    Query(params): Query<CalendarExportParams>,
+) -> Result<Response, ApiError> {
+    let (start_date, end_date) = parse_date_range(params.start_date, params.end_date)?;
+
+    let body = render_user_calendar(user_id, start_date, end_date).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, crate::ical::ICAL_CONTENT_TYPE),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"daily-tracks.ics\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Read-only calendar feed for subscription URLs. Authenticated by a
+/// `calendar_feed` API key in the path, because calendar clients cannot send
+/// an `Authorization` header. The token may carry a trailing `.ics`.
+#[utoipa::path(
+    get,
+    path = "/calendar/feed/{token}",
+    tag = "Calendar",
+    operation_id = "getCalendarFeed",
+    summary = "Subscribable calendar feed",
+    description = "Read-only iCalendar feed for calendar apps. Does not take an Authorization header: the path carries an API key created with scope calendar_feed, optionally suffixed with .ics. Defaults to the last 90 days when no range is given.",
+    params(
+        ("token" = String, Path, description = "calendar_feed API key, e.g. dt_a1b2....ics"),
+        CalendarExportParams,
+    ),
+    responses(
+        (status = 200, description = "iCalendar document", body = String, content_type = "text/calendar"),
+        (status = 401, description = "Unknown, revoked or non-calendar_feed key", body = ErrorResponse),
+    ),
+    security(()),
+)]
+pub async fn get_calendar_feed(
+    Path(token): Path<String>,
+    Query(params): Query<CalendarExportParams>,
+) -> Result<Response, ApiError> {
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    if !token.starts_with(db::API_KEY_PREFIX) {
+        return Err(ApiError::Unauthorized(
+            "Invalid calendar feed token".to_string(),
+        ));
+    }
+
+    let user_id = crate::server_auth::lookup_api_key(token, db::API_KEY_SCOPE_CALENDAR_FEED)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Invalid calendar feed token".to_string()))?;
+    crate::server_auth::record_caller(user_id, crate::server_auth::AuthMethod::ApiKey, token);
+
+    let default_start =
+        chrono::Utc::now().date_naive() - chrono::Duration::days(CALENDAR_FEED_DEFAULT_DAYS);
+    let (start_date, end_date) = parse_date_range(params.start_date, params.end_date)?;
+    let start_date = start_date.or(Some(default_start));
+
+    let body = render_user_calendar(user_id, start_date, end_date).await?;
+    Ok((
+        [(header::CONTENT_TYPE, crate::ical::ICAL_CONTENT_TYPE)],
+        body,
+    )
+        .into_response())
+}
+
+/// Longest keyword an import rule may hold; matches the column width.
+pub(crate) const MAX_IMPORT_RULE_KEYWORD_LEN: usize = 255;
+
+/// Creates tracks from the VEVENTs of an uploaded `.ics` document. Events are
+/// snapped to 30-minute slots and mapped to topics through the user's import
+/// rules, falling back to `default_topic_id`. Events that overlap an existing
+/// track (or an earlier event in the same file) are reported as conflicts.
+/// With `dry_run` nothing is stored.
+#[utoipa::path(
+    post,
+    path = "/calendar/import",
+    tag = "Calendar",
+    operation_id = "importCalendar",
+    summary = "Import tracks from an iCalendar file",
+    description = "Creates daily tracks from the VEVENTs of an .ics document. Event times are converted to UTC and widened to 30-minute slots (start rounded down, end rounded up), then checked with the same rules as creating a track. Topics come from the longest matching import rule keyword in the summary, then the description, then default_topic_id. All-day, recurring and unmatched events are skipped. Events overlapping an existing track, or an earlier event in the same file, are reported as conflicts. With dry_run nothing is stored.",
+    request_body = ImportCalendarRequest,
+    responses(
+        (status = 200, description = "Per-event import report", body = ImportCalendarResponse),
+        (status = 400, description = "Not an iCalendar document, or too many events", body = ErrorResponse),
+        (status = 404, description = "default_topic_id not found", body = ErrorResponse),
+    ),
+)]
+pub async fn import_calendar(
+    Extension(user_id): Extension<i64>,
+    Json(req): Json<ImportCalendarRequest>,
+) -> Result<Json<ImportCalendarResponse>, ApiError> {
+    let events = crate::ical::parse_events(&req.ics).map_err(ApiError::BadRequest)?;
+    if events.len() > crate::calendar_import::MAX_IMPORT_EVENTS {
+        return Err(ApiError::BadRequest(format!(
+            "ics contains {} events; at most {} can be imported at once",
+            events.len(),
+            crate::calendar_import::MAX_IMPORT_EVENTS
+        )));
+    }
+
+    if let Some(topic_id) = req.default_topic_id {
+        let topic = db::get_topic_by_id_for_user(topic_id, user_id).await?;
+        if topic.is_none() {
+            return Err(ApiError::NotFound(format!(
+                "Topic with id {} not found",
+                topic_id
+            )));
+        }
+    }
+
+    let rules = db::list_calendar_import_rules_for_user(user_id).await?;
+
+    let (mut results, drafts): (Vec<_>, Vec<_>) = events
+        .iter()
+        .map(|event| crate::calendar_import::plan_event(event, &rules, req.default_topic_id))
+        .unzip();
+    let drafts: Vec<db::TrackDraft> = drafts.into_iter().flatten().collect();
+
+    let outcomes = db::import_daily_tracks(user_id, drafts, req.dry_run).await?;
+
+    // Outcomes line up with the results that produced a draft.
+    let planned = results
+        .iter_mut()
+        .filter(|r| r.status == ImportEventStatus::WouldCreate);
+    for (result, outcome) in planned.zip(outcomes) {
+        match outcome {
+            db::DraftOutcome::Created(track) if !req.dry_run => {
+                result.status = ImportEventStatus::Created;
+                result.track_id = Some(track.id);
+            }
+            db::DraftOutcome::Created(_) => {}
+            db::DraftOutcome::Overlap => {
+                result.status = ImportEventStatus::Conflict;
+                result.reason =
+                    Some("An overlapping record already exists for this time period".to_string());
+            }
+        }
+    }
+
+    let count =
+        |status: ImportEventStatus| results.iter().filter(|r| r.status == status).count() as u32;
+    Ok(Json(ImportCalendarResponse {
+        dry_run: req.dry_run,
+        created: count(ImportEventStatus::Created) + count(ImportEventStatus::WouldCreate),
+        conflicts: count(ImportEventStatus::Conflict),
+        skipped: count(ImportEventStatus::Skipped),
+        events: results,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/calendar/import-rules",
+    tag = "Calendar",
+    operation_id = "listImportRules",
+    summary = "List calendar import rules",
+    params(("If-None-Match" = Option<String>, Header, description = "ETag of a previous response; answered with 304 while the list is unchanged")),
+    responses(
+        (status = 200, description = "Import rules, oldest first", body = [ImportRule], headers(("ETag" = String, description = "Tag of this list"))),
+        (status = 304, description = "Not modified: `If-None-Match` names the current ETag"),
+    ),
+)]
+pub async fn list_import_rules(
+    Extension(user_id): Extension<i64>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let rules = db::list_calendar_import_rules_for_user(user_id).await?;
+
+    let rules: Vec<ImportRule> = rules.iter().map(db_import_rule_to_import_rule).collect();
+    hashed_json(&headers, &rules)
+}
+
+#[utoipa::path(
+    post,
+    path = "/calendar/import-rules",
+    tag = "Calendar",
+    operation_id = "createImportRule",
+    summary = "Create a calendar import rule",
+    description = "Maps events whose summary or description contains the keyword (case-insensitive) to a topic.",
+    request_body = CreateImportRuleRequest,
+    responses(
+        (status = 201, description = "Import rule created", body = ImportRule),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Topic not found", body = ErrorResponse),
+    ),
+)]
+pub async fn create_import_rule(
+    Extension(user_id): Extension<i64>,
+    Json(req): Json<CreateImportRuleRequest>,
+) -> Result<(StatusCode, Json<ImportRule>), ApiError> {
+    let keyword = req.keyword.trim();
+    if keyword.is_empty() {
+        return Err(FieldError::new(
+            "keyword",
+            FieldErrorCode::Required,
+            "keyword must not be empty",
+        )
+        .into());
+    }
+    if keyword.chars().count() > MAX_IMPORT_RULE_KEYWORD_LEN {
+        return Err(FieldError::new(
+            "keyword",
+            FieldErrorCode::OutOfRange,
+            format!(
+                "keyword must not exceed {} characters",
+                MAX_IMPORT_RULE_KEYWORD_LEN
+            ),
+        )
+        .into());
+    }
+
+    let topic = db::get_topic_by_id_for_user(req.topic_id, user_id).await?;
+    if topic.is_none() {
+        return Err(ApiError::NotFound(format!(
+            "Topic with id {} not found",
+            req.topic_id
+        )));
+    }
+
+    let rule = db::create_calendar_import_rule(user_id, keyword.to_string(), req.topic_id).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(db_import_rule_to_import_rule(&rule)),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/calendar/import-rules/{id}",
+    tag = "Calendar",
+    operation_id = "deleteImportRule",
+    summary = "Delete a calendar import rule",
+    params(("id" = String, Path)),
+    responses(
+        (status = 204, description = "Import rule deleted"),
+        (status = 404, description = "Import rule not found", body = ErrorResponse),
+    ),
+)]
+pub async fn delete_import_rule(
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = db::delete_calendar_import_rule(id, user_id).await?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Import rule with id {} not found",
+            id
+        )))
+    }
+}
+
+// --- CSV Handlers ---
+
+#[utoipa::path(
+    get,
+    path = "/daily-tracks/export.csv",
+    tag = "DailyTrack",
+    operation_id = "exportDailyTracksCsv",
+    summary = "Export daily tracks as CSV",
+    description = "Columns: start_time, end_time (UTC, RFC 3339), duration_minutes, topic (path such as Work/Backend; a '/' or '\\' inside a name is escaped with '\\'), comment and color (the topic display color). The file can be imported again unchanged.",
+    params(GetDailyTracksParams),
+    responses(
+        (status = 200, description = "CSV document", body = String, content_type = "text/csv"),
+        (status = 400, description = "Invalid date format", body = ErrorResponse),
+    ),
+)]
+pub async fn export_daily_tracks_csv(
+    Extension(user_id): Extension<i64>,
+    Query(params): Query<GetDailyTracksParams>,
+) -> Result<Response, ApiError> {
+    let (start_date, end_date) = parse_date_range(params.start_date, params.end_date)?;
+
+    let tracks = db::get_daily_tracks(start_date, end_date, params.topic_id, Some(user_id)).await?;
+    let topics = db::get_topics(None, Some(user_id)).await?;
+    let topics = topics.into_iter().map(|t| (t.id, t)).collect();
+
+    let body = crate::track_csv::render_tracks_csv(&tracks, &topics);
+    Ok((
+        [
+            (header::CONTENT_TYPE, crate::track_csv::CSV_CONTENT_TYPE),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"daily-tracks.csv\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Imports tracks from a CSV in the export format. Topic paths are resolved
+/// or created. The import is all-or-nothing: any invalid row (400) or any
+/// overlap or topic clash (409) stores nothing, and every failing line is
+/// reported.
+#[utoipa::path(
+    post,
+    path = "/daily-tracks/import.csv",
+    tag = "DailyTrack",
+    operation_id = "importDailyTracksCsv",
+    summary = "Import daily tracks from CSV",
+    description = "Accepts the export format; columns are matched by header name, and either end_time or duration_minutes may be left out. Each row is validated like a new track (start at :00/:30, duration a positive multiple of 30 up to 24h). Topic paths are resolved, and missing topics are created, using the row's color for the leaf. The import is atomic: if any row is invalid (400) or overlaps a track or clashes with an existing topic name (409), nothing is stored and every failing line is reported.",
+    request_body(content = String, content_type = "text/csv"),
+    responses(
+        (status = 201, description = "All rows imported", body = CsvImportResponse),
+        (status = 400, description = "Invalid rows; nothing was stored", body = CsvImportResponse),
+        (status = 409, description = "Overlapping tracks or topic name clashes; nothing was stored", body = CsvImportResponse),
+    ),
+)]
+pub async fn import_daily_tracks_csv(
+    Extension(user_id): Extension<i64>,
+    body: String,
+) -> Result<(StatusCode, Json<CsvImportResponse>), ApiError> {
+    let rejected = |status, errors| {
+        (
+            status,
+            Json(CsvImportResponse {
+                tracks_created: 0,
+                topics_created: 0,
+                errors,
+            }),
+        )
+    };
+
+    let rows = match crate::track_csv::parse_tracks_csv(&body) {
+        Ok(rows) => rows,
+        Err(errors) => return Ok(rejected(StatusCode::BAD_REQUEST, errors)),
+    };
+    if rows.is_empty() {
+        return Err(ApiError::BadRequest("CSV contains no rows".to_string()));
+    }
+
+    let (lines, drafts): (Vec<u64>, Vec<db::PathTrackDraft>) = rows.into_iter().unzip();
+    let result = db::import_tracks_with_topic_paths(user_id, drafts).await?;
+
+    if !result.committed() {
+        let errors = lines
+            .into_iter()
+            .zip(result.outcomes)
+            .filter_map(|(line, outcome)| {
+                let message = match outcome {
+                    db::PathDraftOutcome::Created => return None,
+                    db::PathDraftOutcome::Overlap => {
+                        "An overlapping record already exists for this time period".to_string()
+                    }
+                    db::PathDraftOutcome::TopicConflict(message) => message,
+                };
+                Some(CsvLineError { line, message })
+            })
+            .collect();
+        return Ok(rejected(StatusCode::CONFLICT, errors));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CsvImportResponse {
+            tracks_created: result.tracks_created,
+            topics_created: result.topics_created,
+            errors: Vec::new(),
+        }),
+    ))
+}
+
+// --- Backup Handlers ---
+
+/// Largest backup document accepted by a restore.
+pub(crate) const MAX_BACKUP_BYTES: usize = 32 * 1024 * 1024;
+
+#[utoipa::path(
+    get,
+    path = "/account/backup",
+    tag = "Account",
+    operation_id = "exportAccountBackup",
+    summary = "Export a full account backup",
+    description = "Versioned JSON document with the user's topics (hierarchy and colors), daily tracks, goals, calendar import rules and API key metadata. API key hashes are never included.",
+    responses(
+        (status = 200, description = "Backup document", body = AccountBackup),
+    ),
+)]
+pub async fn export_account_backup(
+    Extension(user_id): Extension<i64>,
+) -> Result<Response, ApiError> {
+    let topics = db::get_topics(None, Some(user_id)).await?;
+    let tracks = db::get_daily_tracks(None, None, None, Some(user_id)).await?;
+    let goals = db::list_topic_goals_for_user(user_id).await?;
+    let rules = db::list_calendar_import_rules_for_user(user_id).await?;
+    let api_keys = db::list_api_keys_for_user(user_id).await?;
+
+    let backup = crate::backup::build_backup(
+        &topics,
+        &tracks,
+        &goals,
+        &rules,
+        &api_keys,
+        chrono::Utc::now(),
+    );
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"daily-tracker-backup.json\"",
+        )],
+        Json(backup),
+    )
+        .into_response())
+}
+
+/// Restores a backup produced by `export_account_backup`, typically into a
+/// fresh account. Ids are remapped; running the same restore again is a
+/// no-op. API keys are never restored.
+#[utoipa::path(
+    post,
+    path = "/account/restore",
+    tag = "Account",
+    operation_id = "restoreAccountBackup",
+    summary = "Restore an account backup",
+    description = "Restores a backup, typically into a fresh account, in one transaction. Every entry gets a new id and parent_topic_id / topic_id links are remapped. Entries already present (topics by name and parent, tracks by start, duration and topic, goals by topic and comparison, rules by keyword and topic) are reused, so restoring the same backup twice creates nothing the second time. API keys are not restored.",
+    request_body = AccountBackup,
+    responses(
+        (status = 200, description = "Restore summary", body = RestoreBackupResponse),
+        (status = 400, description = "Unknown format or version, or invalid or inconsistent entries", body = ErrorResponse),
+        (status = 409, description = "A topic name exists under a different parent (`DUPLICATE_NAME`), or a track overlaps a different existing track (`TRACK_OVERLAP`); nothing was stored", body = ErrorResponse),
+    ),
+)]
+pub async fn restore_account_backup(
+    Extension(user_id): Extension<i64>,
+    Json(backup): Json<AccountBackup>,
+) -> Result<Json<RestoreBackupResponse>, ApiError> {
+    let api_keys_skipped = backup.api_keys.len() as u32;
+    let plan = crate::backup::plan_restore(backup).map_err(ApiError::BadRequest)?;
+
+    let summary = db::restore_account(user_id, plan).await?;
+
+    Ok(Json(RestoreBackupResponse {
+        topics_created: summary.topics_created,
+        topics_existing: summary.topics_existing,
+        daily_tracks_created: summary.tracks_created,
+        daily_tracks_existing: summary.tracks_existing,
+        goals_created: summary.goals_created,
+        goals_existing: summary.goals_existing,
+        import_rules_created: summary.import_rules_created,
+        import_rules_existing: summary.import_rules_existing,
+        api_keys_skipped,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -655,4 +1865,90 @@ mod tests {
     fn hex_color_invalid_hash_only() {
         assert!(!is_valid_hex_color("#"));
     }
+
+    // --- validate_goal_target_minutes tests ---
+
+    #[test]
+    fn goal_target_accepts_non_slot_multiples() {
+        assert!(validate_goal_target_minutes(45).is_ok());
+        assert!(validate_goal_target_minutes(MAX_DURATION_MINUTES).is_ok());
+    }
+
+    #[test]
+    fn goal_target_rejects_zero_and_over_a_day() {
+        assert!(validate_goal_target_minutes(0).is_err());
+        assert!(validate_goal_target_minutes(MAX_DURATION_MINUTES + 1).is_err());
+    }
+
+    // --- field-level validation tests ---
+
+    #[test]
+    fn date_range_reports_both_bad_dates() {
+        let err = parse_date_range(Some("2026-13-01".into()), Some("soon".into())).unwrap_err();
+        match err {
+            ApiError::Validation(details) => {
+                let fields: Vec<_> = details.iter().map(|d| d.field.as_str()).collect();
+                assert_eq!(fields, ["start_date", "end_date"]);
+                assert!(
+                    details
+                        .iter()
+                        .all(|d| d.code == FieldErrorCode::InvalidFormat)
+                );
+            }
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn topic_validators_name_their_field() {
+        assert_eq!(validate_topic_name("  Work ").unwrap(), "Work");
+        let err = validate_topic_name("   ").unwrap_err();
+        assert_eq!(
+            (err.field.as_str(), err.code),
+            ("topic_name", FieldErrorCode::Required)
+        );
+        let err = validate_display_color("blue").unwrap_err();
+        assert_eq!(err.field, "display_color");
+        assert_eq!(err.code, FieldErrorCode::InvalidFormat);
+    }
+
+    #[test]
+    fn patch_fields_may_be_absent_but_not_null() {
+        assert_eq!(not_null::<i32>("duration_minutes", None).unwrap(), None);
+        assert_eq!(
+            not_null("duration_minutes", Some(Some(60))).unwrap(),
+            Some(60)
+        );
+        let err = not_null::<i32>("duration_minutes", Some(None)).unwrap_err();
+        assert_eq!(err.field, "duration_minutes");
+        assert_eq!(err.code, FieldErrorCode::Required);
+    }
+
+    #[tokio::test]
+    async fn unsubscribe_link_leaves_the_subscription_enabled() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use http_body_util::BodyExt;
+        use tower::ServiceExt;
+
+        // The GET handler never reaches the store: in tests any query fails,
+        // so a 200 here means the subscription was not touched.
+        let app = axum::Router::new().route(
+            "/digest/unsubscribe/:token",
+            axum::routing::get(unsubscribe_digest),
+        );
+        let request = Request::get("/digest/unsubscribe/0123abcd")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let page = String::from_utf8(body.to_vec()).unwrap();
+        assert!(page.contains("<form method=\"post\">"));
+    }
 }
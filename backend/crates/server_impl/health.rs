@@ -0,0 +1,143 @@
+use std::time::Instant;
+
+use axum::{Json, http::StatusCode};
+use models::{CheckStatus, HealthCheck, HealthResponse};
+
+/// Runs one check and records how long it took.
+fn timed_check(name: &str, check: impl FnOnce() -> Result<Option<String>, String>) -> HealthCheck {
+    let started = Instant::now();
+    let result = check();
+    let duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+    let (status, detail) = match result {
+        Ok(detail) => (CheckStatus::Ok, detail),
+        Err(detail) => (CheckStatus::Failed, Some(detail)),
+    };
+    HealthCheck {
+        name: name.to_string(),
+        status,
+        detail,
+        duration_ms,
+    }
+}
+
+fn migrations_detail(pending: Vec<String>) -> Result<Option<String>, String> {
+    if pending.is_empty() {
+        Ok(None)
+    } else {
+        Err(format!(
+            "{} pending migration(s): {}",
+            pending.len(),
+            pending.join(", ")
+        ))
+    }
+}
+
+/// Folds check results into a response; any failed check makes the whole
+/// response `failed` and the status 503.
+pub(crate) fn readiness_response(checks: Vec<HealthCheck>) -> (StatusCode, HealthResponse) {
+    let ready = checks.iter().all(|c| c.status == CheckStatus::Ok);
+    let (code, status) = if ready {
+        (StatusCode::OK, CheckStatus::Ok)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, CheckStatus::Failed)
+    };
+    (code, HealthResponse { status, checks })
+}
+
+fn run_readiness_checks() -> Vec<HealthCheck> {
+    let mut connection = None;
+    let database = timed_check("database", || {
+        let mut conn = db::readiness_connection()?;
+        db::ping_database(&mut conn)?;
+        connection = Some(conn);
+        Ok(None)
+    });
+    let migrations = timed_check("migrations", || match connection.as_mut() {
+        Some(conn) => migrations_detail(db::pending_migrations(conn)?),
+        None => Err("skipped: database is unavailable".to_string()),
+    });
+    vec![database, migrations]
+}
+
+/// Liveness: the process is up and serving requests. Never touches MySQL, so
+/// a database outage does not get the service restarted.
+pub async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: CheckStatus::Ok,
+        checks: Vec::new(),
+    })
+}
+
+/// Readiness: MySQL answers `SELECT 1` on a pooled connection and every
+/// embedded migration has been applied.
+pub async fn readyz() -> (StatusCode, Json<HealthResponse>) {
+    let checks = tokio::task::spawn_blocking(run_readiness_checks)
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Readiness checks panicked: {}", e);
+            vec![HealthCheck {
+                name: "database".to_string(),
+                status: CheckStatus::Failed,
+                detail: Some("readiness check panicked".to_string()),
+                duration_ms: 0,
+            }]
+        });
+    let (code, response) = readiness_response(checks);
+    if code != StatusCode::OK {
+        log::warn!("Readiness check failed: {:?}", response.checks);
+    }
+    (code, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readiness_response_is_ok_when_all_checks_pass() {
+        let checks = vec![
+            timed_check("database", || Ok(None)),
+            timed_check("migrations", || migrations_detail(Vec::new())),
+        ];
+        let (code, response) = readiness_response(checks);
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(response.status, CheckStatus::Ok);
+        assert_eq!(response.checks.len(), 2);
+    }
+
+    #[test]
+    fn readiness_response_fails_on_pending_migrations() {
+        let checks = vec![
+            timed_check("database", || Ok(None)),
+            timed_check("migrations", || {
+                migrations_detail(vec!["20260516090000".to_string()])
+            }),
+        ];
+        let (code, response) = readiness_response(checks);
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.status, CheckStatus::Failed);
+        assert_eq!(
+            response.checks[1].detail.as_deref(),
+            Some("1 pending migration(s): 20260516090000")
+        );
+    }
+
+    #[test]
+    fn health_response_json_shape() {
+        let (_, response) = readiness_response(vec![timed_check("database", || {
+            Err("no database connection: timed out".to_string())
+        })]);
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["checks"][0]["name"], "database");
+        assert_eq!(json["checks"][0]["status"], "failed");
+        assert!(json["checks"][0]["duration_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn healthz_reports_ok_without_checks() {
+        let Json(response) = healthz().await;
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#"{"status":"ok"}"#);
+    }
+}
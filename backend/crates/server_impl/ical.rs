@@ -0,0 +1,645 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use db_model::models::{DailyTrack, Topic};
+
+pub(crate) const ICAL_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+const PRODID: &str = "-//Daily Tracker//Daily Tracker Backend//EN";
+const UID_DOMAIN: &str = "daily-tracker";
+const UNTITLED_SUMMARY: &str = "Untitled";
+/// RFC 5545 §3.1: lines SHOULD NOT be longer than 75 octets, excluding CRLF.
+const MAX_LINE_OCTETS: usize = 75;
+
+fn format_utc(dt: NaiveDateTime) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a TEXT value (RFC 5545 §3.3.11).
+fn escape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Appends `line` folded at 75 octets (RFC 5545 §3.1), never splitting a
+/// UTF-8 sequence. Continuation lines start with a single space.
+fn push_folded(out: &mut String, line: &str) {
+    let mut line_octets = 0;
+    for c in line.chars() {
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            line_octets = 1;
+        }
+        out.push(c);
+        line_octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Renders `tracks` as an RFC 5545 VCALENDAR. Each track becomes a VEVENT
+/// whose summary is the topic name and whose description is the comment.
+/// RFC 7986 `COLOR` only accepts CSS3 color names, so the topic's hex
+/// display color is carried in `X-DAILY-TRACKER-COLOR`.
+pub(crate) fn render_calendar(
+    calendar_name: &str,
+    tracks: &[DailyTrack],
+    topics: &HashMap<i64, Topic>,
+    generated_at: NaiveDateTime,
+) -> String {
+    let mut out = String::new();
+    push_folded(&mut out, "BEGIN:VCALENDAR");
+    push_folded(&mut out, "VERSION:2.0");
+    push_folded(&mut out, &format!("PRODID:{}", PRODID));
+    push_folded(&mut out, "CALSCALE:GREGORIAN");
+    push_folded(&mut out, "METHOD:PUBLISH");
+    push_folded(
+        &mut out,
+        &format!("X-WR-CALNAME:{}", escape_text(calendar_name)),
+    );
+
+    for track in tracks {
+        let topic = track.topic_id.and_then(|id| topics.get(&id));
+        let end = track.start_time + Duration::minutes(i64::from(track.duration_minutes));
+
+        push_folded(&mut out, "BEGIN:VEVENT");
+        push_folded(&mut out, &format!("UID:{}@{}", track.id, UID_DOMAIN));
+        push_folded(&mut out, &format!("DTSTAMP:{}", format_utc(generated_at)));
+        push_folded(
+            &mut out,
+            &format!("DTSTART:{}", format_utc(track.start_time)),
+        );
+        push_folded(&mut out, &format!("DTEND:{}", format_utc(end)));
+        push_folded(
+            &mut out,
+            &format!(
+                "LAST-MODIFIED:{}",
+                format_utc(track.updated_at.unwrap_or(track.created_at))
+            ),
+        );
+        let summary = topic.map_or(UNTITLED_SUMMARY, |t| t.topic_name.as_str());
+        push_folded(&mut out, &format!("SUMMARY:{}", escape_text(summary)));
+        if let Some(topic) = topic {
+            push_folded(
+                &mut out,
+                &format!("CATEGORIES:{}", escape_text(&topic.topic_name)),
+            );
+            push_folded(
+                &mut out,
+                &format!("X-DAILY-TRACKER-COLOR:{}", topic.display_color),
+            );
+        }
+        if let Some(comment) = track.comment.as_deref().filter(|c| !c.is_empty()) {
+            push_folded(&mut out, &format!("DESCRIPTION:{}", escape_text(comment)));
+        }
+        push_folded(&mut out, "TRANSP:OPAQUE");
+        push_folded(&mut out, "END:VEVENT");
+    }
+
+    push_folded(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Undoes TEXT escaping (RFC 5545 §3.3.11).
+fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Joins folded lines back together and drops blank lines. Accepts bare LF
+/// line endings as well as CRLF.
+fn unfold_lines(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in ics.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        if let (Some(rest), Some(last)) = (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            last.push_str(rest);
+            continue;
+        }
+        if !raw.is_empty() {
+            lines.push(raw.to_string());
+        }
+    }
+    lines
+}
+
+/// One content line split into its upper-cased name, parameters and raw value.
+struct ContentLine<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+impl ContentLine<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim_matches('"'))
+    }
+}
+
+fn parse_content_line(line: &str) -> Option<ContentLine<'_>> {
+    // The value starts at the first colon outside a quoted parameter value.
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(i),
+        _ => None,
+    })?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.trim().to_ascii_uppercase(), v))
+        .collect();
+    Some(ContentLine {
+        name,
+        params,
+        value,
+    })
+}
+
+/// A DATE or DATE-TIME property value. Times are converted to UTC; the day
+/// of a DATE is not needed because all-day events are never imported.
+enum DateValue {
+    Date,
+    DateTime(NaiveDateTime),
+}
+
+/// Parses DTSTART/DTEND. UTC (`Z`) times are taken as-is, `TZID` times are
+/// converted with the IANA database, and floating times are treated as UTC.
+fn parse_date_value(line: &ContentLine) -> Result<DateValue, String> {
+    let value = line.value.trim();
+    if line.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|_| DateValue::Date)
+            .map_err(|_| format!("invalid {} date '{}'", line.name, value));
+    }
+
+    let invalid = || format!("invalid {} date-time '{}'", line.name, value);
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(DateValue::DateTime)
+            .map_err(|_| invalid());
+    }
+
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    let Some(tzid) = line.param("TZID") else {
+        return Ok(DateValue::DateTime(local));
+    };
+    let tz: Tz = tzid
+        .parse()
+        .map_err(|_| format!("unknown time zone '{}'", tzid))?;
+    // Pick the earlier instant for times repeated by a DST change; times
+    // skipped by one have no instant at all.
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|dt| DateValue::DateTime(dt.naive_utc()))
+        .ok_or_else(|| format!("{} does not exist in time zone '{}'", value, tzid))
+}
+
+/// Parses a DURATION value (RFC 5545 §3.3.6), e.g. `PT1H30M` or `P1W`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid DURATION '{}'", value);
+    let value = value.trim();
+    let body = value.strip_prefix('+').unwrap_or(value);
+    if body.starts_with('-') {
+        return Err(format!("negative DURATION '{}' is not supported", value));
+    }
+    let body = body.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    let mut in_time = false;
+    let mut saw_component = false;
+    for c in body.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            'T' if !in_time && digits.is_empty() => in_time = true,
+            _ => {
+                let n: i64 = digits.parse().map_err(|_| invalid())?;
+                digits.clear();
+                // The file is untrusted, so out-of-range values are errors,
+                // not panics.
+                let component = match (c, in_time) {
+                    ('W', false) => Duration::try_weeks(n),
+                    ('D', false) => Duration::try_days(n),
+                    ('H', true) => Duration::try_hours(n),
+                    ('M', true) => Duration::try_minutes(n),
+                    ('S', true) => Duration::try_seconds(n),
+                    _ => return Err(invalid()),
+                };
+                total = component
+                    .and_then(|component| total.checked_add(&component))
+                    .ok_or_else(|| format!("DURATION '{}' is out of range", value))?;
+                saw_component = true;
+            }
+        }
+    }
+    if !digits.is_empty() || !saw_component {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// When a parsed event takes place.
+#[derive(Debug, PartialEq)]
+pub(crate) enum EventTiming {
+    /// A DATE-valued event with no time of day.
+    AllDay,
+    /// Start and end in UTC.
+    Timed {
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    },
+}
+
+/// A VEVENT reduced to the properties the importer uses.
+#[derive(Debug)]
+pub(crate) struct CalendarEvent {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    /// Whether the event carries an RRULE or RDATE.
+    pub recurring: bool,
+    /// Why the event's time could not be worked out, if it could not.
+    pub timing: Result<EventTiming, String>,
+}
+
+#[derive(Default)]
+struct EventBuilder {
+    uid: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    recurring: bool,
+    start: Option<Result<DateValue, String>>,
+    end: Option<Result<DateValue, String>>,
+    duration: Option<Result<Duration, String>>,
+}
+
+impl EventBuilder {
+    fn timing(self) -> Result<EventTiming, String> {
+        let start = self.start.ok_or("event has no DTSTART")??;
+        let start = match start {
+            DateValue::Date => return Ok(EventTiming::AllDay),
+            DateValue::DateTime(start) => start,
+        };
+        let end = match (self.end, self.duration) {
+            (Some(end), _) => match end? {
+                DateValue::DateTime(end) => end,
+                DateValue::Date => return Err("DTEND is a date but DTSTART is not".to_string()),
+            },
+            (None, Some(duration)) => start
+                .checked_add_signed(duration?)
+                .ok_or("DURATION ends out of range")?,
+            (None, None) => start,
+        };
+        if end <= start {
+            return Err("event has no duration".to_string());
+        }
+        Ok(EventTiming::Timed { start, end })
+    }
+
+    fn build(mut self) -> CalendarEvent {
+        CalendarEvent {
+            uid: self.uid.take(),
+            summary: self.summary.take(),
+            description: self.description.take(),
+            recurring: self.recurring,
+            timing: self.timing(),
+        }
+    }
+}
+
+/// Extracts the VEVENTs from an iCalendar document. Components nested inside
+/// an event (such as VALARM) are ignored. Fails only when the input is not a
+/// VCALENDAR at all; problems with individual events end up in their
+/// `timing`.
+pub(crate) fn parse_events(ics: &str) -> Result<Vec<CalendarEvent>, String> {
+    let lines = unfold_lines(ics.trim_start_matches('\u{feff}'));
+    let is_calendar = lines
+        .first()
+        .is_some_and(|l| l.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR"));
+    if !is_calendar {
+        return Err("ics must start with BEGIN:VCALENDAR".to_string());
+    }
+
+    let mut events = Vec::new();
+    let mut current: Option<EventBuilder> = None;
+    // Depth of components nested inside the current VEVENT.
+    let mut nested = 0usize;
+
+    for line in &lines {
+        let Some(line) = parse_content_line(line) else {
+            continue;
+        };
+        let value = line.value.trim();
+        match line.name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VEVENT") && current.is_none() => {
+                current = Some(EventBuilder::default());
+                nested = 0;
+                continue;
+            }
+            "END" if value.eq_ignore_ascii_case("VEVENT") && nested == 0 => {
+                if let Some(event) = current.take() {
+                    events.push(event.build());
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(event) = current.as_mut() else {
+            continue;
+        };
+        match line.name.as_str() {
+            "BEGIN" => nested += 1,
+            "END" => nested = nested.saturating_sub(1),
+            _ if nested > 0 => {}
+            "UID" => event.uid = Some(value.to_string()),
+            "SUMMARY" => event.summary = Some(unescape_text(line.value)),
+            "DESCRIPTION" => event.description = Some(unescape_text(line.value)),
+            "RRULE" | "RDATE" => event.recurring = true,
+            "DTSTART" => event.start = Some(parse_date_value(&line)),
+            "DTEND" => event.end = Some(parse_date_value(&line)),
+            "DURATION" => event.duration = Some(parse_duration(value)),
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn topic(id: i64, name: &str, color: &str) -> Topic {
+        Topic {
+            id,
+            topic_name: name.to_string(),
+            display_color: color.to_string(),
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+            parent_topic_id: None,
+            user_id: Some(1),
+            version: 1,
+        }
+    }
+
+    fn track(id: i64, start: &str, topic_id: Option<i64>, comment: Option<&str>) -> DailyTrack {
+        DailyTrack {
+            id,
+            start_time: at(start),
+            created_at: at(start),
+            updated_at: None,
+            topic_id,
+            comment: comment.map(str::to_string),
+            user_id: Some(1),
+            duration_minutes: 90,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn escape_text_escapes_special_characters() {
+        assert_eq!(escape_text("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+
+    #[test]
+    fn push_folded_keeps_lines_within_75_octets() {
+        let mut out = String::new();
+        let long = format!("DESCRIPTION:{}", "é".repeat(100));
+        push_folded(&mut out, &long);
+        for line in out.split("\r\n").filter(|l| !l.is_empty()) {
+            assert!(
+                line.len() <= MAX_LINE_OCTETS,
+                "line too long: {}",
+                line.len()
+            );
+        }
+        let unfolded = out.trim_end_matches("\r\n").replace("\r\n ", "");
+        assert_eq!(unfolded, long);
+    }
+
+    #[test]
+    fn render_calendar_emits_event_per_track() {
+        let topics = HashMap::from([(5, topic(5, "Deep work", "#14b8a6"))]);
+        let tracks = vec![
+            track(1, "2026-05-12 09:00", Some(5), Some("design review, notes")),
+            track(2, "2026-05-12 13:00", None, None),
+        ];
+        let ics = render_calendar("Daily Tracker", &tracks, &topics, at("2026-05-13 00:00"));
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("UID:1@daily-tracker\r\n"));
+        assert!(ics.contains("DTSTART:20260512T090000Z\r\n"));
+        assert!(ics.contains("DTEND:20260512T103000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Deep work\r\n"));
+        assert!(ics.contains("X-DAILY-TRACKER-COLOR:#14b8a6\r\n"));
+        assert!(ics.contains("DESCRIPTION:design review\\, notes\r\n"));
+        assert!(ics.contains("SUMMARY:Untitled\r\n"));
+    }
+
+    #[test]
+    fn render_calendar_uses_crlf_only() {
+        let ics = render_calendar(
+            "Daily Tracker",
+            &[],
+            &HashMap::new(),
+            at("2026-05-13 00:00"),
+        );
+        assert!(!ics.replace("\r\n", "").contains('\n'));
+    }
+
+    fn ics(events: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\n{}END:VCALENDAR\r\n",
+            events
+        )
+    }
+
+    fn timed(event: &CalendarEvent) -> (NaiveDateTime, NaiveDateTime) {
+        match event.timing {
+            Ok(EventTiming::Timed { start, end }) => (start, end),
+            ref other => panic!("expected a timed event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unescape_text_reverses_escape_text() {
+        let original = "a,b;c\\d\ne";
+        assert_eq!(unescape_text(&escape_text(original)), original);
+    }
+
+    #[test]
+    fn parse_events_reads_utc_event() {
+        let doc = ics(
+            "BEGIN:VEVENT\r\nUID:abc@example.com\r\nDTSTART:20260512T090000Z\r\n\
+             DTEND:20260512T100000Z\r\nSUMMARY:Team sync\\, weekly\r\n\
+             DESCRIPTION:Agenda\\nNotes\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].uid.as_deref(), Some("abc@example.com"));
+        assert_eq!(events[0].summary.as_deref(), Some("Team sync, weekly"));
+        assert_eq!(events[0].description.as_deref(), Some("Agenda\nNotes"));
+        assert_eq!(
+            timed(&events[0]),
+            (at("2026-05-12 09:00"), at("2026-05-12 10:00"))
+        );
+        assert!(!events[0].recurring);
+    }
+
+    #[test]
+    fn parse_events_unfolds_lines() {
+        let doc = ics(
+            "BEGIN:VEVENT\r\nSUMMARY:Quarterly plan\r\n ning review\r\nDTSTART:20260512T090000Z\r\nDURATION:PT30M\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        assert_eq!(
+            events[0].summary.as_deref(),
+            Some("Quarterly planning review")
+        );
+    }
+
+    #[test]
+    fn parse_events_converts_tzid_to_utc() {
+        let doc = ics(
+            "BEGIN:VEVENT\r\nDTSTART;TZID=Europe/Berlin:20260512T090000\r\n\
+             DTEND;TZID=\"Europe/Berlin\":20260512T103000\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        // Berlin is UTC+2 in May.
+        assert_eq!(
+            timed(&events[0]),
+            (at("2026-05-12 07:00"), at("2026-05-12 08:30"))
+        );
+    }
+
+    #[test]
+    fn parse_events_uses_duration_when_no_dtend() {
+        let doc =
+            ics("BEGIN:VEVENT\r\nDTSTART:20260512T090000Z\r\nDURATION:PT1H30M\r\nEND:VEVENT\r\n");
+        let events = parse_events(&doc).unwrap();
+        assert_eq!(timed(&events[0]).1, at("2026-05-12 10:30"));
+    }
+
+    #[test]
+    fn parse_events_flags_all_day_and_recurring_events() {
+        let doc = ics(
+            "BEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20260512\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nDTSTART:20260512T090000Z\r\nDTEND:20260512T093000Z\r\n\
+             RRULE:FREQ=WEEKLY\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        assert_eq!(events[0].timing, Ok(EventTiming::AllDay));
+        assert!(events[1].recurring);
+    }
+
+    #[test]
+    fn parse_events_ignores_nested_alarms() {
+        let doc = ics(
+            "BEGIN:VEVENT\r\nSUMMARY:Call\r\nDTSTART:20260512T090000Z\r\nDTEND:20260512T093000Z\r\n\
+             BEGIN:VALARM\r\nDESCRIPTION:Reminder\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].description, None);
+    }
+
+    #[test]
+    fn parse_events_reports_bad_times_per_event() {
+        let doc = ics(
+            "BEGIN:VEVENT\r\nDTSTART;TZID=Mars/Olympus:20260512T090000\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nDTSTART:20260512T090000Z\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        assert!(
+            events[0]
+                .timing
+                .as_ref()
+                .unwrap_err()
+                .contains("Mars/Olympus")
+        );
+        assert_eq!(events[1].timing, Err("event has no duration".to_string()));
+    }
+
+    #[test]
+    fn parse_events_rejects_non_calendar_input() {
+        assert!(parse_events("hello").is_err());
+        assert!(parse_events("").is_err());
+    }
+
+    #[test]
+    fn parse_events_round_trips_rendered_calendar() {
+        let topics = HashMap::from([(5, topic(5, "Deep work", "#14b8a6"))]);
+        let tracks = vec![track(1, "2026-05-12 09:00", Some(5), Some("a;b"))];
+        let doc = render_calendar("Daily Tracker", &tracks, &topics, at("2026-05-13 00:00"));
+        let events = parse_events(&doc).unwrap();
+        assert_eq!(events[0].summary.as_deref(), Some("Deep work"));
+        assert_eq!(events[0].description.as_deref(), Some("a;b"));
+        assert_eq!(
+            timed(&events[0]),
+            (at("2026-05-12 09:00"), at("2026-05-12 10:30"))
+        );
+    }
+
+    #[test]
+    fn parse_duration_accepts_weeks_and_days() {
+        assert_eq!(parse_duration("P1W").unwrap(), Duration::weeks(1));
+        assert_eq!(parse_duration("P1DT2H").unwrap(), Duration::hours(26));
+        assert!(parse_duration("-PT15M").is_err());
+        assert!(parse_duration("PT").is_err());
+        assert!(parse_duration("P1H").is_err());
+    }
+
+    #[test]
+    fn oversized_durations_are_per_event_errors() {
+        assert!(parse_duration("P100000000000W").is_err());
+        assert!(parse_duration("PT9223372036854775807S").is_err());
+        // Each part fits, but the sum does not.
+        assert!(parse_duration("P100000000000DT2000000000000H").is_err());
+        let doc = ics(
+            "BEGIN:VEVENT\r\nDTSTART:20260512T090000Z\r\nDURATION:P100000000W\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nDTSTART:20260512T090000Z\r\nDURATION:P100000000000W\r\nEND:VEVENT\r\n",
+        );
+        let events = parse_events(&doc).unwrap();
+        for event in &events {
+            assert!(event.timing.as_ref().unwrap_err().contains("out of range"));
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! `Idempotency-Key` support for authenticated POSTs. The first response to
+//! a key is stored per user, and a retry with the same key and request gets
+//! that response again instead of running the request twice. Responses that
+//! carry a secret are never stored; a retry of such a request gets 409.
+
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{MatchedPath, Request};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header, response};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use db::{IdempotencyClaim, StoredResponse};
+use models::{ApiError, FieldError, FieldErrorCode};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+
+use crate::handler::MAX_BACKUP_BYTES;
+
+pub(crate) const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on replayed responses, so clients can tell a replay from a new result.
+pub(crate) const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// How long a key is remembered.
+const KEY_TTL: chrono::Duration = chrono::Duration::hours(24);
+/// After this long a claim whose request never finished, e.g. because the
+/// process died, may be taken over by a retry.
+const ABANDONED_AFTER: chrono::Duration = chrono::Duration::minutes(5);
+/// Larger responses are not stored; a retry runs the request again.
+const MAX_STORED_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+const MAX_KEY_LEN: usize = 255;
+/// How often keys past `KEY_TTL` are swept for every user.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Axum's own body limit, which every route keeps unless listed in
+/// `ROUTE_BODY_LIMITS`.
+const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+/// Routes that raise the body limit with `DefaultBodyLimit`. A keyed request
+/// is buffered before the handler runs, so it must not exceed the limit the
+/// handler would enforce.
+const ROUTE_BODY_LIMITS: &[(&str, usize)] = &[("/api/v1/account/restore", MAX_BACKUP_BYTES)];
+
+/// Reads the key: 1 to 255 visible ASCII characters, as a UUID would be.
+fn parse_key(value: &HeaderValue) -> Result<String, FieldError> {
+    match value.to_str() {
+        Ok(key)
+            if !key.is_empty()
+                && key.len() <= MAX_KEY_LEN
+                && key.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            Ok(key.to_string())
+        }
+        _ => Err(FieldError::new(
+            "Idempotency-Key",
+            FieldErrorCode::InvalidFormat,
+            format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ),
+        )),
+    }
+}
+
+/// Fingerprint of what the request asks for, so a key reused for another
+/// request can be told apart from a retry.
+fn request_hash(method: &Method, path_and_query: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b"\n");
+    hasher.update(path_and_query);
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn header_string(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+fn replay(stored: StoredResponse) -> Result<Response, ApiError> {
+    let Some(body) = stored.body else {
+        return Err(ApiError::IdempotencyKeyCompleted(
+            "A request with this Idempotency-Key already succeeded; its response held a secret \
+             and cannot be returned again"
+                .to_string(),
+        ));
+    };
+    let status = u16::try_from(stored.status_code)
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = (status, body).into_response();
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_TYPE);
+    for (name, value) in [
+        (header::CONTENT_TYPE, stored.content_type),
+        (header::ETAG, stored.etag),
+    ] {
+        if let Some(value) = value.and_then(|v| HeaderValue::from_str(&v).ok()) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    Ok(response)
+}
+
+/// What is kept of a response for replays, or `None` to release the key so a
+/// retry runs the request again: server errors are not kept, nor responses
+/// too large or not UTF-8. With `keep_body` off only a success is kept, and
+/// only its status.
+fn stored_response(
+    parts: &response::Parts,
+    body: &[u8],
+    keep_body: bool,
+) -> Option<StoredResponse> {
+    if parts.status.is_server_error() || (!keep_body && !parts.status.is_success()) {
+        return None;
+    }
+    let status_code = i32::from(parts.status.as_u16());
+    if !keep_body {
+        return Some(StoredResponse {
+            status_code,
+            content_type: None,
+            etag: None,
+            body: None,
+        });
+    }
+    let body = std::str::from_utf8(body)
+        .ok()
+        .filter(|b| b.len() <= MAX_STORED_RESPONSE_BYTES)?;
+    Some(StoredResponse {
+        status_code,
+        content_type: header_string(&parts.headers, header::CONTENT_TYPE),
+        etag: header_string(&parts.headers, header::ETAG),
+        body: Some(body.to_string()),
+    })
+}
+
+fn body_limit(route: Option<&str>) -> usize {
+    route
+        .and_then(|route| ROUTE_BODY_LIMITS.iter().find(|(path, _)| *path == route))
+        .map_or(DEFAULT_BODY_LIMIT, |(_, limit)| *limit)
+}
+
+async fn read_body(body: Body, limit: usize) -> Result<Bytes, ApiError> {
+    axum::body::to_bytes(body, limit)
+        .await
+        .map_err(|_| ApiError::Rejected {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            message: "Request body is too large or could not be read".to_string(),
+            details: Vec::new(),
+        })
+}
+
+/// Makes POSTs that carry an `Idempotency-Key` safe to retry. Runs after
+/// authentication, since keys are scoped to the user. A replay returns the
+/// stored status and body; the key sent with a different request gets 422,
+/// and while the first request is still running, 409. Server errors are not
+/// stored, so the retry runs the request again.
+pub async fn idempotent_post(req: Request, next: Next) -> Result<Response, ApiError> {
+    handle(req, next, true).await
+}
+
+/// Like `idempotent_post`, for routes whose response holds a secret, such as
+/// a new API key's token. Only the request's fingerprint is stored, so a
+/// retry after a success gets 409 instead of the secret.
+pub async fn idempotent_secret_post(req: Request, next: Next) -> Result<Response, ApiError> {
+    handle(req, next, false).await
+}
+
+async fn handle(req: Request, next: Next, keep_body: bool) -> Result<Response, ApiError> {
+    let (Some(value), Some(user_id)) = (
+        req.headers().get(&IDEMPOTENCY_KEY),
+        req.extensions().get::<i64>().copied(),
+    ) else {
+        return Ok(next.run(req).await);
+    };
+    if req.method() != Method::POST {
+        return Ok(next.run(req).await);
+    }
+    let key = parse_key(value)?;
+
+    let (parts, body) = req.into_parts();
+    // Read before the key is claimed, so a rejected body leaves no claim.
+    let body = read_body(
+        body,
+        body_limit(
+            parts
+                .extensions
+                .get::<MatchedPath>()
+                .map(MatchedPath::as_str),
+        ),
+    )
+    .await?;
+    let path = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), |p| p.as_str());
+    let hash = request_hash(&parts.method, path, &body);
+
+    let now = chrono::Utc::now().naive_utc();
+    let claim = db::claim_idempotency_key(
+        user_id,
+        key.clone(),
+        hash,
+        now - KEY_TTL,
+        now - ABANDONED_AFTER,
+    )
+    .await?;
+    match claim {
+        IdempotencyClaim::Claimed => {}
+        IdempotencyClaim::Replay(stored) => return replay(stored),
+        IdempotencyClaim::Mismatch => {
+            return Err(ApiError::IdempotencyKeyReused(
+                "This Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+        IdempotencyClaim::InProgress => {
+            return Err(ApiError::IdempotencyKeyInUse(
+                "A request with this Idempotency-Key is still being processed; retry shortly"
+                    .to_string(),
+            ));
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Failed to buffer response for idempotency key: {}", e);
+            release(user_id, key).await;
+            return Err(ApiError::InternalServerError(
+                "Internal server error".to_string(),
+            ));
+        }
+    };
+
+    match stored_response(&parts, &body, keep_body) {
+        Some(stored) => {
+            // The client still gets its response; a retry waits out the
+            // abandoned claim and then runs the request again.
+            if let Err(e) = db::complete_idempotency_key(user_id, key, stored).await {
+                log::error!("Failed to store response for idempotency key: {}", e);
+            }
+        }
+        None => release(user_id, key).await,
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+async fn release(user_id: i64, key: String) {
+    if let Err(e) = db::release_idempotency_key(user_id, key).await {
+        log::error!("Failed to release idempotency key: {}", e);
+    }
+}
+
+/// Starts the sweep of expired keys. Each user's expired keys are also
+/// dropped on their next keyed request; this covers users who send none.
+pub(crate) fn spawn_sweeper(shutdown: CancellationToken) {
+    crate::shutdown::spawn_background(async move {
+        loop {
+            let expires_before = chrono::Utc::now().naive_utc() - KEY_TTL;
+            match db::delete_expired_idempotency_keys(expires_before).await {
+                Ok(0) => {}
+                Ok(deleted) => log::info!("Deleted {} expired idempotency key(s)", deleted),
+                Err(e) => log::error!("Idempotency key sweep failed: {}", e),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(SWEEP_INTERVAL) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        log::info!("Idempotency key sweeper stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[test]
+    fn keys_must_be_visible_ascii() {
+        let key = HeaderValue::from_static("0b6f5a2e-6c1d-4c8e-9f3a-2d1e7b9c4a10");
+        assert_eq!(
+            parse_key(&key).unwrap(),
+            "0b6f5a2e-6c1d-4c8e-9f3a-2d1e7b9c4a10"
+        );
+        assert!(parse_key(&HeaderValue::from_static("")).is_err());
+        assert!(parse_key(&HeaderValue::from_static("two words")).is_err());
+        let long = "k".repeat(MAX_KEY_LEN + 1);
+        assert!(parse_key(&HeaderValue::from_str(&long).unwrap()).is_err());
+    }
+
+    #[test]
+    fn request_hash_covers_method_path_and_body() {
+        let hash = request_hash(&Method::POST, "/api/v1/topics", b"{\"a\":1}");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(
+            hash,
+            request_hash(&Method::POST, "/api/v1/topics", b"{\"a\":1}")
+        );
+        assert_ne!(
+            hash,
+            request_hash(&Method::POST, "/api/v1/topics", b"{\"a\":2}")
+        );
+        assert_ne!(
+            hash,
+            request_hash(&Method::POST, "/api/v1/goals", b"{\"a\":1}")
+        );
+    }
+
+    fn parts(status: StatusCode) -> response::Parts {
+        let mut response = (status, "{}").into_response();
+        response
+            .headers_mut()
+            .insert(header::ETAG, HeaderValue::from_static("\"1\""));
+        response.into_parts().0
+    }
+
+    #[test]
+    fn secret_responses_keep_only_the_status_of_a_success() {
+        let created = parts(StatusCode::CREATED);
+        let kept = stored_response(&created, b"{\"token\":\"dt_x\"}", true).unwrap();
+        assert_eq!(kept.body.as_deref(), Some("{\"token\":\"dt_x\"}"));
+        assert_eq!(kept.etag.as_deref(), Some("\"1\""));
+
+        let secret = stored_response(&created, b"{\"token\":\"dt_x\"}", false).unwrap();
+        assert_eq!(
+            secret,
+            StoredResponse {
+                status_code: 201,
+                content_type: None,
+                etag: None,
+                body: None,
+            }
+        );
+        assert!(matches!(
+            replay(secret),
+            Err(ApiError::IdempotencyKeyCompleted(_))
+        ));
+
+        let rejected = parts(StatusCode::BAD_REQUEST);
+        assert!(stored_response(&rejected, b"{}", true).is_some());
+        assert!(stored_response(&rejected, b"{}", false).is_none());
+        let failed = parts(StatusCode::SERVICE_UNAVAILABLE);
+        assert!(stored_response(&failed, b"{}", true).is_none());
+    }
+
+    #[tokio::test]
+    async fn replays_keep_status_headers_and_body() {
+        let response = replay(StoredResponse {
+            status_code: 201,
+            content_type: Some("application/json".to_string()),
+            etag: Some("\"1\"".to_string()),
+            body: Some("{\"id\":\"7\"}".to_string()),
+        })
+        .unwrap_or_else(|_| panic!("a stored body replays"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(response.headers()[header::ETAG], "\"1\"");
+        assert_eq!(response.headers()[IDEMPOTENT_REPLAYED], "true");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"{\"id\":\"7\"}");
+    }
+
+    #[tokio::test]
+    async fn oversized_keyed_posts_are_rejected_before_the_claim() {
+        use axum::Router;
+        use axum::middleware;
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        async fn as_user(mut req: Request, next: Next) -> Response {
+            req.extensions_mut().insert(7_i64);
+            next.run(req).await
+        }
+
+        // Claiming needs the database, which tests do not have: reaching it
+        // would fail the request with a server error instead of 413.
+        let app = Router::new().nest(
+            "/api/v1",
+            Router::new()
+                .route("/topics", post(|| async { "created" }))
+                .route_layer(middleware::from_fn(idempotent_post))
+                .route_layer(middleware::from_fn(as_user)),
+        );
+        let request = Request::post("/api/v1/topics")
+            .header(IDEMPOTENCY_KEY, "k1")
+            .body(Body::from(vec![b' '; DEFAULT_BODY_LIMIT + 1]))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn only_the_restore_route_reads_large_bodies() {
+        assert_eq!(
+            body_limit(Some("/api/v1/account/restore")),
+            MAX_BACKUP_BYTES
+        );
+        assert_eq!(body_limit(Some("/api/v1/topics")), DEFAULT_BODY_LIMIT);
+        assert_eq!(body_limit(None), DEFAULT_BODY_LIMIT);
+    }
+}
@@ -2,41 +2,235 @@
 
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
     http::{HeaderValue, Method},
     middleware,
     routing::get,
 };
+use clap::Parser;
+use std::sync::Arc;
 use logging::init_logging;
 use tower_http::cors::CorsLayer;
 
 mod server_auth;
 mod handler;
+mod extract;
+mod conditional;
+mod idempotency;
+mod openapi;
 mod request_logger;
 mod email;
+mod email_outbox;
+mod email_templates;
+mod digest;
+mod goals;
+mod ical;
+mod calendar_import;
+mod track_csv;
+mod backup;
+mod metrics;
+mod telemetry;
+mod health;
+mod cli;
+mod shutdown;
+mod config;
+mod tls;
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
-    init_logging();
+    let cli = cli::Cli::parse();
+    let config = match config::Config::load(&cli.overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let app = register_routes();
-    let bind_addr = std::env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await.unwrap();
-    println!("Server running on http://{}", bind_addr);
-    axum::serve(listener, app).await.unwrap();
+    let result = match cli.command {
+        // Checking the configuration must not create log files.
+        Some(cli::Command::Config {
+            action: cli::ConfigCommand::Check,
+        }) => cli::run_config_check(&config),
+        Some(cli::Command::Openapi { spec_version }) => cli::run_openapi(spec_version),
+        Some(cli::Command::Email {
+            action: cli::EmailCommand::Preview {
+                template,
+                locale,
+                part,
+            },
+        }) => cli::run_email_preview(&config, template, &locale, part),
+        Some(cli::Command::Email {
+            action: cli::EmailCommand::Outbox { limit },
+        }) => {
+            init_logging(&config.log_options());
+            cli::run_email_outbox(&config, limit).await
+        }
+        Some(cli::Command::Email {
+            action: cli::EmailCommand::RetryFailed,
+        }) => {
+            init_logging(&config.log_options());
+            cli::run_email_retry_failed(&config).await
+        }
+        Some(cli::Command::Migrate { dry_run }) => {
+            init_logging(&config.log_options());
+            cli::run_migrate(&config, dry_run)
+        }
+        Some(cli::Command::Status) => {
+            init_logging(&config.log_options());
+            cli::run_status(&config)
+        }
+        Some(cli::Command::Serve) | None => serve(config).await,
+    };
+    if let Err(e) = result {
+        log::error!("{}", e);
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 }
 
-fn register_routes() -> Router {
+/// Validates the configuration and hands each part to the module that uses
+/// it, so a bad value stops startup instead of failing the first request.
+fn apply_config(config: config::Config) -> Result<(), String> {
+    if let Err(errors) = config.validate() {
+        return Err(format!("invalid configuration: {}", errors.join("; ")));
+    }
+    init_logging(&config.log_options());
+    telemetry::init(&config.tracing)?;
+    db::configure_pool(config.pool_config()?)?;
+    email::init(&config.email)?;
+    email_templates::init(config.email.template_dir.as_deref())?;
+    if let Some(node_id) = config.ids.snowflake_node_id {
+        utils::snowflake::set_node_id(node_id)?;
+    }
+    config::init(config);
+    Ok(())
+}
+
+async fn serve(config: config::Config) -> Result<(), String> {
+    apply_config(config)?;
+    let config = config::get();
+    tokio::task::spawn_blocking(move || cli::prepare_schema(config))
+        .await
+        .map_err(|e| format!("schema check panicked: {}", e))??;
+    let shutdown_token = shutdown::listen_for_shutdown();
+    email_outbox::spawn_worker(config.email.max_attempts, shutdown_token.clone());
+    digest::spawn_scheduler(shutdown_token.clone());
+    idempotency::spawn_sweeper(shutdown_token.clone());
+
+    // Without a private listener, /metrics is only exposed behind a token.
+    let public_metrics = config.metrics.bind_addr.is_none() && config.metrics.token.is_some();
+    let app = register_routes(
+        &config.server.cors_origin,
+        config.tracing.enabled,
+        public_metrics,
+    );
+    if let Some(metrics_addr) = config.metrics.bind_addr.as_deref() {
+        let metrics_app = Router::new().route("/metrics", get(metrics::get_metrics));
+        let metrics_listener = tokio::net::TcpListener::bind(metrics_addr)
+            .await
+            .map_err(|e| format!("failed to bind {}: {}", metrics_addr, e))?;
+        println!("Metrics available on http://{}/metrics", metrics_addr);
+        let metrics_shutdown = shutdown_token.clone().cancelled_owned();
+        shutdown::spawn_listener(
+            "metrics",
+            axum::serve(metrics_listener, metrics_app).with_graceful_shutdown(metrics_shutdown),
+            shutdown_token.clone(),
+        );
+    }
+    let bind_addr = &config.server.bind_addr;
+    let listener = tokio::net::TcpListener::bind(bind_addr)
+        .await
+        .map_err(|e| format!("failed to bind {}: {}", bind_addr, e))?;
+    match (&config.tls.cert_path, &config.tls.key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let acceptor = Arc::new(tls::ReloadableAcceptor::load(cert_path, key_path)?);
+            tls::reload_on_sighup(acceptor.clone(), shutdown_token.clone());
+            if let Some(redirect_addr) = config.tls.redirect_http_addr.as_deref() {
+                let https_port = listener
+                    .local_addr()
+                    .map_err(|e| format!("failed to read bound address: {}", e))?
+                    .port();
+                let redirect_listener = tokio::net::TcpListener::bind(redirect_addr)
+                    .await
+                    .map_err(|e| format!("failed to bind {}: {}", redirect_addr, e))?;
+                println!("Redirecting http://{} to HTTPS", redirect_addr);
+                let redirect_shutdown = shutdown_token.clone().cancelled_owned();
+                shutdown::spawn_listener(
+                    "HTTPS redirect",
+                    axum::serve(redirect_listener, tls::redirect_router(https_port))
+                        .with_graceful_shutdown(redirect_shutdown),
+                    shutdown_token.clone(),
+                );
+            }
+            println!("Server running on https://{}", bind_addr);
+            let server = tls::serve_tls(listener, app, acceptor, shutdown_token.clone());
+            shutdown::serve_until_drained(server, shutdown_token, config.shutdown_timeout()).await
+        }
+        _ => {
+            println!("Server running on http://{}", bind_addr);
+            let server = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_token.clone().cancelled_owned());
+            shutdown::serve_until_drained(server, shutdown_token, config.shutdown_timeout()).await
+        }
+    }
+    .map_err(|e| format!("server error: {}", e))?;
+    telemetry::shutdown();
+    log::info!("Server stopped");
+    Ok(())
+}
+
+fn register_routes(allowed_origin: &str, tracing: bool, public_metrics: bool) -> Router {
     let api_routes = Router::new()
         .route("/topics", get(handler::get_topics).post(handler::create_topic))
-        .route("/topics/:id", get(handler::get_topic_by_id).put(handler::update_topic))
+        .route(
+            "/topics/:id",
+            get(handler::get_topic_by_id)
+                .put(handler::update_topic)
+                .patch(handler::patch_topic),
+        )
         .route("/daily-tracks", get(handler::get_daily_tracks).post(handler::create_daily_track))
+        .route("/daily-tracks/export.ics", get(handler::export_daily_tracks_ical))
+        .route("/daily-tracks/export.csv", get(handler::export_daily_tracks_csv))
+        .route(
+            "/daily-tracks/import.csv",
+            axum::routing::post(handler::import_daily_tracks_csv),
+        )
         .route(
             "/daily-tracks/:id",
             get(handler::get_daily_track_by_id)
                 .put(handler::update_daily_track)
+                .patch(handler::patch_daily_track)
                 .delete(handler::delete_daily_track),
         )
+        .route("/goals", get(handler::list_goals).post(handler::create_goal))
+        .route("/goals/progress", get(handler::get_goal_progress))
+        .route(
+            "/goals/:id",
+            axum::routing::put(handler::update_goal).delete(handler::delete_goal),
+        )
+        .route("/calendar/import", axum::routing::post(handler::import_calendar))
+        .route("/account/backup", get(handler::export_account_backup))
+        .route(
+            "/account/digest",
+            get(handler::get_digest_settings).put(handler::update_digest_settings),
+        )
+        // Keep idempotency::ROUTE_BODY_LIMITS in step with this limit.
+        .route(
+            "/account/restore",
+            axum::routing::post(handler::restore_account_backup)
+                .layer(DefaultBodyLimit::max(handler::MAX_BACKUP_BYTES)),
+        )
+        .route(
+            "/calendar/import-rules",
+            get(handler::list_import_rules).post(handler::create_import_rule),
+        )
+        .route(
+            "/calendar/import-rules/:id",
+            axum::routing::delete(handler::delete_import_rule),
+        )
+        .route_layer(middleware::from_fn(idempotency::idempotent_post))
         .route_layer(middleware::from_fn(server_auth::auth_middleware));
 
     // API-key management is JWT-only: a programmatic caller must not be able to
@@ -47,33 +241,84 @@ fn register_routes() -> Router {
             get(handler::list_api_keys).post(handler::create_api_key),
         )
         .route("/api-keys/:id", axum::routing::delete(handler::revoke_api_key))
+        // The new key's token must not be stored for replays.
+        .route_layer(middleware::from_fn(idempotency::idempotent_secret_post))
         .route_layer(middleware::from_fn(server_auth::jwt_only_middleware));
 
+    // Calendar clients poll this URL without an Authorization header; the
+    // calendar_feed API key in the path is the credential.
+    let calendar_feed_routes =
+        Router::new().route("/calendar/feed/:token", get(handler::get_calendar_feed));
+
+    // Unsubscribe links in digest emails carry their own token.
+    let digest_unsubscribe_routes = Router::new().route(
+        "/digest/unsubscribe/:token",
+        get(handler::unsubscribe_digest).post(handler::unsubscribe_digest_one_click),
+    );
+
     let auth_routes = Router::new()
         .route("/register", axum::routing::post(handler::register))
         .route("/verify-email", axum::routing::post(handler::verify_email))
         .route("/login", axum::routing::post(handler::login));
 
-    let allowed_origin = std::env::var("CORS_ORIGIN")
-        .unwrap_or_else(|_| "http://localhost:5173".to_string());
     let cors = CorsLayer::new()
-        .allow_origin(allowed_origin.parse::<HeaderValue>().expect("Invalid CORS_ORIGIN value"))
+        // Validated by Config::validate before routes are built.
+        .allow_origin(allowed_origin.parse::<HeaderValue>().expect("Invalid cors_origin value"))
         .allow_methods([
             Method::GET,
             Method::POST,
             Method::PUT,
+            Method::PATCH,
             Method::DELETE,
             Method::OPTIONS,
         ])
         .allow_headers([
             axum::http::header::AUTHORIZATION,
             axum::http::header::CONTENT_TYPE,
-        ]);
+            axum::http::HeaderName::from_static("traceparent"),
+            axum::http::HeaderName::from_static("tracestate"),
+            axum::http::header::IF_MATCH,
+            axum::http::header::IF_NONE_MATCH,
+            idempotency::IDEMPOTENCY_KEY,
+        ])
+        .expose_headers([axum::http::header::ETAG, idempotency::IDEMPOTENT_REPLAYED]);
+
+    // Probes for systemd, nginx and load balancers; unauthenticated and
+    // outside /api/v1 so they never change with the API version.
+    let health_routes = Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz));
+
+    // The OpenAPI document and Swagger UI are public, like the spec in the
+    // repository.
+    let docs = utoipa_swagger_ui::SwaggerUi::new(openapi::SWAGGER_UI_PATH)
+        .url(openapi::SPEC_PATH, <openapi::ApiDoc as utoipa::OpenApi>::openapi());
+
+    // Registered before the layers below, so scrapes and failed token
+    // attempts are logged and traced like any other request.
+    let metrics_routes = if public_metrics {
+        Router::new().route("/metrics", get(metrics::get_metrics))
+    } else {
+        Router::new()
+    };
 
-    Router::new()
+    let app = Router::new()
+        .merge(health_routes)
+        .merge(metrics_routes)
+        .merge(docs)
         .nest("/api/v1/auth", auth_routes)
         .nest("/api/v1", api_key_routes)
         .nest("/api/v1", api_routes)
+        .nest("/api/v1", calendar_feed_routes)
+        .nest("/api/v1", digest_unsubscribe_routes)
+        .route_layer(middleware::from_fn(metrics::track_metrics))
+        .route_layer(middleware::from_fn(request_logger::record_route))
         .layer(cors)
-        .layer(middleware::from_fn(request_logger::log_request))
+        .layer(middleware::from_fn(request_logger::log_request));
+    // Outermost, so the log id can be taken from the trace.
+    if tracing {
+        app.layer(middleware::from_fn(telemetry::trace_request))
+    } else {
+        app
+    }
 }
@@ -0,0 +1,298 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use subtle::ConstantTimeEq;
+
+const NAMESPACE: &str = "daily_tracker";
+
+pub(crate) const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+static REGISTRY: Lazy<Registry> = Lazy::new(|| {
+    Registry::new_custom(Some(NAMESPACE.to_string()), None).expect("valid metrics namespace")
+});
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(collector: T) -> T {
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("metric registered once");
+    collector
+}
+
+static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static API_KEY_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("api_key_lookups_total", "API key lookups by result"),
+            &["result"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static JWT_VALIDATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("jwt_validations_total", "JWT validations by result"),
+            &["result"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static EMAIL_SEND_FAILURES: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new(
+            "email_send_failures_total",
+            "Email delivery attempts that failed",
+        )
+        .expect("valid metric"),
+    )
+});
+
+static EMAIL_OUTBOX_PENDING: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "email_outbox_pending",
+            "Queued emails waiting for delivery, as of the last worker pass",
+        )
+        .expect("valid metric"),
+    )
+});
+
+static EMAIL_OUTBOX_FAILED: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "email_outbox_failed",
+            "Queued emails the worker gave up on, as of the last worker pass",
+        )
+        .expect("valid metric"),
+    )
+});
+
+static DB_POOL_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "db_pool_connections",
+            "Connections currently held by the database pool",
+        )
+        .expect("valid metric"),
+    )
+});
+
+static DB_POOL_IDLE_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle connections in the database pool",
+        )
+        .expect("valid metric"),
+    )
+});
+
+/// Label for requests that matched no route, so that scanners probing random
+/// paths cannot blow up the label cardinality.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Outcome label shared by the authentication counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuthResult {
+    Valid,
+    Invalid,
+    Error,
+}
+
+impl AuthResult {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuthResult::Valid => "valid",
+            AuthResult::Invalid => "invalid",
+            AuthResult::Error => "error",
+        }
+    }
+}
+
+pub(crate) fn record_api_key_lookup(result: AuthResult) {
+    API_KEY_LOOKUPS.with_label_values(&[result.as_str()]).inc();
+}
+
+pub(crate) fn record_jwt_validation(result: AuthResult) {
+    JWT_VALIDATIONS.with_label_values(&[result.as_str()]).inc();
+}
+
+pub(crate) fn record_email_send_failure() {
+    EMAIL_SEND_FAILURES.inc();
+}
+
+pub(crate) fn set_email_outbox(pending: i64, failed: i64) {
+    EMAIL_OUTBOX_PENDING.set(pending);
+    EMAIL_OUTBOX_FAILED.set(failed);
+}
+
+/// Counts and times every request by method, route template and status.
+/// Installed as a route layer so the matched route template is known.
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, |p| p.as_str())
+        .to_string();
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+/// Renders all metrics in the Prometheus text format. Pool stats are read at
+/// scrape time, and only once the pool exists, so scraping never opens a
+/// database connection.
+pub(crate) fn render() -> String {
+    if let Some(pool) = Lazy::get(&db::DB_POOL) {
+        let state = pool.state();
+        DB_POOL_CONNECTIONS.set(i64::from(state.connections));
+        DB_POOL_IDLE_CONNECTIONS.set(i64::from(state.idle_connections));
+    }
+    // Touch the counters so they are exported as 0 before the first event.
+    for result in [AuthResult::Valid, AuthResult::Invalid, AuthResult::Error] {
+        API_KEY_LOOKUPS.with_label_values(&[result.as_str()]);
+    }
+    for result in [AuthResult::Valid, AuthResult::Invalid] {
+        JWT_VALIDATIONS.with_label_values(&[result.as_str()]);
+    }
+    Lazy::force(&EMAIL_SEND_FAILURES);
+    Lazy::force(&EMAIL_OUTBOX_PENDING);
+    Lazy::force(&EMAIL_OUTBOX_FAILED);
+    Lazy::force(&HTTP_REQUESTS);
+    Lazy::force(&HTTP_REQUEST_DURATION);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("encode metrics to Vec");
+    String::from_utf8(buffer).expect("metrics text is UTF-8")
+}
+
+/// Whether the scraper's bearer token is `expected`. Compared in constant
+/// time, so response timing does not reveal how much of a guess was right.
+fn token_matches(presented: Option<&str>, expected: &str) -> bool {
+    presented.is_some_and(|p| bool::from(p.as_bytes().ct_eq(expected.as_bytes())))
+}
+
+/// Serves `/metrics`. When `metrics.token` is set the scraper must send it as
+/// a bearer token; without it the endpoint relies on being bound to a
+/// private address (`metrics.bind_addr`).
+pub async fn get_metrics(request: Request) -> Response {
+    if let Some(expected) = crate::config::get().metrics.token.as_deref() {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.strip_prefix("Bearer "));
+        if !token_matches(presented, expected) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], render()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, middleware, routing::get};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt as _;
+
+    async fn ok_handler() -> &'static str {
+        "ok"
+    }
+
+    #[tokio::test]
+    async fn track_metrics_labels_requests_with_route_template() {
+        let app = Router::new()
+            .route("/metrics-test/:id", get(ok_handler))
+            .route_layer(middleware::from_fn(track_metrics));
+        let request = Request::builder()
+            .uri("/metrics-test/42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = render();
+        assert!(text.contains(
+            "daily_tracker_http_requests_total{method=\"GET\",route=\"/metrics-test/:id\",status=\"200\"} 1"
+        ));
+        assert!(text.contains("daily_tracker_http_request_duration_seconds_bucket"));
+    }
+
+    #[test]
+    fn render_exports_auth_and_email_counters() {
+        record_jwt_validation(AuthResult::Invalid);
+        let text = render();
+        assert!(text.contains("daily_tracker_jwt_validations_total{result=\"invalid\"}"));
+        assert!(text.contains("daily_tracker_api_key_lookups_total{result=\"valid\"}"));
+        assert!(text.contains("daily_tracker_email_send_failures_total"));
+        assert!(text.contains("daily_tracker_email_outbox_pending"));
+    }
+
+    #[test]
+    fn token_must_match_exactly() {
+        assert!(token_matches(Some("s3cret"), "s3cret"));
+        assert!(!token_matches(Some("s3cre"), "s3cret"));
+        assert!(!token_matches(Some("s3cret!"), "s3cret"));
+        assert!(!token_matches(None, "s3cret"));
+    }
+
+    #[tokio::test]
+    async fn get_metrics_returns_text_format() {
+        let app = Router::new().route("/metrics", get(get_metrics));
+        let request = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        // The test configuration sets no metrics token.
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(String::from_utf8_lossy(&body).contains("# TYPE"));
+    }
+}
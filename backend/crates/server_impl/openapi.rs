@@ -0,0 +1,310 @@
+use utoipa::openapi::path::{ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{ObjectBuilder, Type};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, RefOr, Required, ResponseBuilder, Responses};
+use utoipa::{Modify, OpenApi};
+
+use crate::handler;
+
+/// Where the document is served, relative to the server root.
+pub(crate) const SPEC_PATH: &str = "/api/v1/openapi.json";
+/// Where Swagger UI is served.
+pub(crate) const SWAGGER_UI_PATH: &str = "/api/v1/docs";
+
+/// The API contract, generated from the `#[utoipa::path]` annotations in
+/// `handler.rs` and the `ToSchema` types in `models`. `openapi.json` in the
+/// repository root is a copy of it; `openapi` on the command line prints it.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Daily Tracker Backend API",
+        version = "1.0.0",
+        description = "Daily tracker backend API"
+    ),
+    servers((url = "/api/v1")),
+    paths(
+        handler::register,
+        handler::verify_email,
+        handler::login,
+        handler::list_api_keys,
+        handler::create_api_key,
+        handler::revoke_api_key,
+        handler::get_topics,
+        handler::create_topic,
+        handler::get_topic_by_id,
+        handler::update_topic,
+        handler::patch_topic,
+        handler::get_daily_tracks,
+        handler::create_daily_track,
+        handler::get_daily_track_by_id,
+        handler::update_daily_track,
+        handler::patch_daily_track,
+        handler::delete_daily_track,
+        handler::list_goals,
+        handler::create_goal,
+        handler::get_goal_progress,
+        handler::update_goal,
+        handler::delete_goal,
+        handler::export_daily_tracks_ical,
+        handler::get_calendar_feed,
+        handler::import_calendar,
+        handler::list_import_rules,
+        handler::create_import_rule,
+        handler::delete_import_rule,
+        handler::export_daily_tracks_csv,
+        handler::import_daily_tracks_csv,
+        handler::export_account_backup,
+        handler::restore_account_backup,
+        handler::get_digest_settings,
+        handler::update_digest_settings,
+        handler::unsubscribe_digest,
+        handler::unsubscribe_digest_one_click,
+    ),
+    modifiers(&BearerAuth, &IdempotencyKeys),
+    security(("bearer_auth" = [])),
+    tags(
+        (name = "Topic", description = "Topic is the category of your daily activities. You can create a topic and assign it to a daily track."),
+        (name = "DailyTrack", description = "Manage your daily track records. Each record represents a 30-minute time period."),
+        (name = "Auth", description = "Authentication endpoints for user registration and login."),
+        (name = "ApiKey", description = "Manage API keys for programmatic access. Authenticated endpoints accept either a JWT or an API key in the Authorization header (Bearer scheme). API keys are prefixed with 'dt_'. The plaintext token is only returned at creation."),
+        (name = "Goal", description = "Daily targets (at_least) and budgets (at_most) per topic, with progress and streak reporting computed from tracked durations."),
+        (name = "Calendar", description = "iCalendar (RFC 5545) export of daily tracks, a subscribable read-only feed authenticated by a calendar_feed API key, and import of .ics files with keyword-to-topic rules."),
+        (name = "Account", description = "Full account backup and restore, and digest email settings."),
+    )
+)]
+pub(crate) struct ApiDoc;
+
+/// Declares the `Authorization: Bearer` scheme, which takes a JWT from
+/// `/auth/login` or a `dt_` API key.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let mut scheme = Http::new(HttpAuthScheme::Bearer);
+        scheme.description = Some("A JWT from /auth/login or a dt_ API key.".to_string());
+        components.add_security_scheme("bearer_auth", SecurityScheme::Http(scheme));
+    }
+}
+
+/// Documents the `Idempotency-Key` header on every authenticated POST, which
+/// `idempotency::idempotent_post` handles in one place rather than per handler.
+struct IdempotencyKeys;
+
+/// Routes behind `idempotency::idempotent_secret_post`, whose responses are
+/// not replayed.
+const SECRET_RESPONSE_PATHS: &[&str] = &["/api-keys"];
+
+fn add_error_response(responses: &mut Responses, status: &str, description: &str) {
+    match responses.responses.get_mut(status) {
+        Some(RefOr::T(response)) => {
+            response.description = format!("{}, or {}", response.description, description);
+        }
+        Some(RefOr::Ref(_)) => {}
+        None => {
+            let content = ContentBuilder::new()
+                .schema(Some(Ref::from_schema_name("ErrorResponse")))
+                .build();
+            let response = ResponseBuilder::new()
+                .description(description)
+                .content("application/json", content)
+                .build();
+            responses
+                .responses
+                .insert(status.to_string(), RefOr::T(response));
+        }
+    }
+}
+
+impl Modify for IdempotencyKeys {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            let Some(post) = item.post.as_mut() else {
+                continue;
+            };
+            // Public routes override the default security; there is no user
+            // to scope their keys to.
+            if post.security.is_some() {
+                continue;
+            }
+            let header = ParameterBuilder::new()
+                .name("Idempotency-Key")
+                .parameter_in(ParameterIn::Header)
+                .required(Required::False)
+                .description(Some(
+                    "Makes the request safe to retry for 24 hours: a repeat with the same key \
+                     and body returns the first response, marked `Idempotent-Replayed: true`",
+                ))
+                .schema(Some(
+                    ObjectBuilder::new()
+                        .schema_type(Type::String)
+                        .min_length(Some(1))
+                        .max_length(Some(255)),
+                ))
+                .build();
+            post.parameters.get_or_insert_with(Vec::new).push(header);
+            add_error_response(
+                &mut post.responses,
+                "409",
+                "a request with the same Idempotency-Key is still running (`IDEMPOTENCY_KEY_IN_USE`)",
+            );
+            if SECRET_RESPONSE_PATHS.contains(&path.as_str()) {
+                add_error_response(
+                    &mut post.responses,
+                    "409",
+                    "a request with the same Idempotency-Key already succeeded; the response \
+                     holds a secret and is not replayed (`IDEMPOTENCY_KEY_COMPLETED`)",
+                );
+            }
+            add_error_response(
+                &mut post.responses,
+                "422",
+                "the Idempotency-Key was used for a different request (`IDEMPOTENCY_KEY_REUSED`)",
+            );
+        }
+    }
+}
+
+/// The document as committed in `openapi.json`.
+pub(crate) fn spec_json() -> String {
+    let mut json = ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("OpenAPI document serializes");
+    json.push('\n');
+    json
+}
+
+/// Which OpenAPI version `openapi` prints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SpecVersion {
+    #[value(name = "3.1")]
+    V3_1,
+    /// For client generators that only read 3.0, such as the frontend's.
+    #[value(name = "3.0")]
+    V3_0,
+}
+
+/// The document as OpenAPI 3.0, committed in `frontend/openapi-3.0.json`
+/// for the frontend's client generator.
+pub(crate) fn spec_json_3_0() -> String {
+    let mut spec = serde_json::to_value(ApiDoc::openapi()).expect("OpenAPI document serializes");
+    spec["openapi"] = serde_json::Value::from("3.0.3");
+    downgrade_nullable_types(&mut spec);
+    let mut json = serde_json::to_string_pretty(&spec).expect("OpenAPI document serializes");
+    json.push('\n');
+    json
+}
+
+/// Rewrites 3.1's `"type": [T, "null"]` as 3.0's `"type": T` with
+/// `"nullable": true`. It is the only 3.1 construct the document uses.
+fn downgrade_nullable_types(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            if let Some(serde_json::Value::Array(types)) = object.get("type") {
+                let mut types = types.clone();
+                let nullable = types.iter().any(|t| t == "null");
+                types.retain(|t| t != "null");
+                if nullable && types.len() == 1 {
+                    object.insert("type".to_string(), types.remove(0));
+                    object.insert("nullable".to_string(), serde_json::Value::Bool(true));
+                }
+            }
+            object.values_mut().for_each(downgrade_nullable_types);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(downgrade_nullable_types),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn committed_spec_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../openapi.json");
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == spec_json(),
+            "openapi.json is out of date; regenerate it from backend/ with \
+             `cargo run -- openapi > ../openapi.json`"
+        );
+    }
+
+    #[test]
+    fn committed_frontend_spec_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../frontend/openapi-3.0.json");
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == spec_json_3_0(),
+            "frontend/openapi-3.0.json is out of date; regenerate it from backend/ with \
+             `cargo run -- openapi --spec-version 3.0 > ../frontend/openapi-3.0.json`"
+        );
+    }
+
+    #[test]
+    fn spec_3_0_has_no_type_lists() {
+        fn has_type_list(value: &serde_json::Value) -> bool {
+            match value {
+                serde_json::Value::Object(object) => {
+                    object.get("type").is_some_and(|t| t.is_array())
+                        || object.values().any(has_type_list)
+                }
+                serde_json::Value::Array(items) => items.iter().any(has_type_list),
+                _ => false,
+            }
+        }
+        let spec: serde_json::Value = serde_json::from_str(&spec_json_3_0()).unwrap();
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert!(!has_type_list(&spec));
+        let conflicting =
+            &spec["components"]["schemas"]["ErrorResponse"]["properties"]["conflicting_track_id"];
+        assert_eq!(conflicting["type"], "string");
+        assert_eq!(conflicting["nullable"], true);
+    }
+
+    #[test]
+    fn spec_is_openapi_3_1_with_every_route() {
+        let spec: serde_json::Value = serde_json::from_str(&spec_json()).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+        let paths = spec["paths"].as_object().unwrap();
+        let operations: usize = paths.values().map(|p| p.as_object().unwrap().len()).sum();
+        assert_eq!(operations, 36);
+        assert!(paths.contains_key("/topics/{id}"));
+        assert!(spec["components"]["schemas"]["ErrorResponse"].is_object());
+    }
+
+    #[test]
+    fn public_routes_need_no_credentials() {
+        let spec: serde_json::Value = serde_json::from_str(&spec_json()).unwrap();
+        assert_eq!(spec["security"], serde_json::json!([{ "bearer_auth": [] }]));
+        let login = &spec["paths"]["/auth/login"]["post"];
+        assert_eq!(login["security"], serde_json::json!([{}]));
+        let topics = &spec["paths"]["/topics"]["get"];
+        assert!(topics.get("security").is_none());
+    }
+
+    #[test]
+    fn authenticated_posts_take_idempotency_keys() {
+        let spec: serde_json::Value = serde_json::from_str(&spec_json()).unwrap();
+        let header_names = |op: &serde_json::Value| -> Vec<String> {
+            op["parameters"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|p| p["in"] == "header")
+                .map(|p| p["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let create = &spec["paths"]["/topics"]["post"];
+        assert_eq!(header_names(create), vec!["Idempotency-Key"]);
+        assert!(create["responses"]["422"].is_object());
+        assert!(
+            create["responses"]["409"]["description"]
+                .as_str()
+                .unwrap()
+                .contains("DUPLICATE_NAME")
+        );
+        assert!(header_names(&spec["paths"]["/auth/login"]["post"]).is_empty());
+    }
+}
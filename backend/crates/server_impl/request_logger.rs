@@ -1,47 +1,189 @@
+use std::time::{Duration, Instant};
+
 use axum::{
-    extract::Request,
-    http::{HeaderName, HeaderValue},
+    body::HttpBody,
+    extract::{MatchedPath, Request},
+    http::{HeaderName, HeaderValue, Uri},
     middleware::Next,
     response::Response,
 };
 
-use log::info;
-use logging::LOG_ID;
+use log::{Level, info};
+use logging::{REQUEST_CONTEXT, RequestContext};
 use uuid::Uuid;
 
 const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
 
+/// Shown instead of values that look like credentials.
+const REDACTED: &str = "[REDACTED]";
+
+/// Query keys whose values are never logged, matched against each `_`, `-`
+/// or `.` separated part of the key, so `api_key` and `access_token` match
+/// but `keyword` does not.
+const SECRET_QUERY_KEYS: &[&str] = &[
+    "token",
+    "key",
+    "apikey",
+    "secret",
+    "password",
+    "passwd",
+    "code",
+    "signature",
+    "sig",
+    "auth",
+    "jwt",
+    "session",
+    "credential",
+];
+
+fn is_secret_key(key: &str) -> bool {
+    key.to_ascii_lowercase()
+        .split(['_', '-', '.'])
+        .any(|part| SECRET_QUERY_KEYS.contains(&part))
+}
+
+/// Routes whose next path segment is a credential, whatever its shape.
+const SECRET_PATH_PREFIXES: &[&str] = &["/api/v1/calendar/feed/", "/api/v1/digest/unsubscribe/"];
+
+/// The path with the credential segment of a `SECRET_PATH_PREFIXES` route
+/// replaced.
+fn redact_secret_route(path: &str) -> String {
+    for prefix in SECRET_PATH_PREFIXES {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            let tail = rest.find('/').map_or("", |i| &rest[i..]);
+            return format!("{}{}{}", prefix, REDACTED, tail);
+        }
+    }
+    path.to_string()
+}
+
+/// API keys, and JWTs by their `eyJ` header and three segments.
+fn looks_like_secret(value: &str) -> bool {
+    value.starts_with(db::API_KEY_PREFIX)
+        || (value.starts_with("eyJ") && value.split('.').count() == 3)
+}
+
+/// The URI as logged: query values with secret-sounding keys, the token
+/// segment of routes that take one in the path, e.g. the API key in a
+/// calendar feed URL, and any path segment or query value that is itself a
+/// credential are replaced by `[REDACTED]`.
+pub(crate) fn redact_uri(uri: &Uri) -> String {
+    let path = redact_secret_route(uri.path())
+        .split('/')
+        .map(|segment| {
+            if looks_like_secret(segment) {
+                REDACTED
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    let Some(query) = uri.query() else {
+        return path;
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if is_secret_key(key) || looks_like_secret(value) => {
+                format!("{}={}", key, REDACTED)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", path, query)
+}
+
+/// Level of the completion line: a warning once the request took at least
+/// `slow_threshold`.
+fn completion_level(latency: Duration, slow_threshold: Option<Duration>) -> Level {
+    if slow_threshold.is_some_and(|threshold| latency >= threshold) {
+        Level::Warn
+    } else {
+        Level::Info
+    }
+}
+
+/// Writes the access-log line for a finished request. The authenticated user
+/// comes from the request context like on every line.
+fn log_completion(method: &str, uri: &str, response: &Response, latency: Duration) {
+    let level = completion_level(latency, crate::config::get().slow_request_threshold());
+    let mut fields: Vec<(&str, log::kv::Value)> = vec![
+        ("status", response.status().as_u16().into()),
+        ("latency_ms", (latency.as_millis() as u64).into()),
+    ];
+    let bytes = response.body().size_hint().exact();
+    if let Some(bytes) = bytes {
+        fields.push(("bytes", bytes.into()));
+    }
+    let (auth, key_prefix) = REQUEST_CONTEXT
+        .try_with(|ctx| (ctx.auth_method(), ctx.api_key_prefix().map(str::to_string)))
+        .unwrap_or((None, None));
+    if let Some(auth) = auth {
+        fields.push(("auth", auth.into()));
+    }
+    if let Some(key_prefix) = &key_prefix {
+        fields.push(("key_prefix", key_prefix.as_str().into()));
+    }
+
+    log::logger().log(
+        &log::Record::builder()
+            .args(format_args!("<-- {} {}", method, uri))
+            .level(level)
+            .target(module_path!())
+            .module_path_static(Some(module_path!()))
+            .file_static(Some(file!()))
+            .line(Some(line!()))
+            .key_values(&fields)
+            .build(),
+    );
+}
+
 pub async fn log_request(request: Request, next: Next) -> Response {
-    // Honor an inbound x-request-id when present; otherwise generate one.
+    // Honor an inbound x-request-id when present; otherwise use the trace id
+    // when tracing is on, so log lines and spans share an id, or generate one.
+    let trace_id = crate::telemetry::current_trace_id();
     let log_id = request
         .headers()
         .get(&REQUEST_ID_HEADER)
         .and_then(|v| v.to_str().ok())
         .filter(|s| !s.is_empty())
         .map(|s| s.to_string())
+        .or_else(|| trace_id.clone())
         .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
 
     let method = request.method().to_string();
-    let uri = request.uri().to_string();
-
-    LOG_ID
-        .scope(log_id.clone(), async move {
-            info!("--> Method: [{}], URI: [{}]", method, uri);
-            let mut response = next.run(request).await;
-            let status = response.status().to_string();
-            info!(
-                "<-- Method: [{}], URI: [{}], Status: [{}]",
-                method, uri, status
-            );
-
-            if let Ok(value) = HeaderValue::from_str(&log_id) {
-                response.headers_mut().insert(REQUEST_ID_HEADER, value);
-            }
-            response
-        })
+    let uri = redact_uri(request.uri());
+
+    REQUEST_CONTEXT
+        .scope(
+            RequestContext::new(log_id.clone()).with_trace_id(trace_id),
+            async move {
+                let started = Instant::now();
+                info!("--> {} {}", method, uri);
+                let mut response = next.run(request).await;
+                log_completion(&method, &uri, &response, started.elapsed());
+
+                if let Ok(value) = HeaderValue::from_str(&log_id) {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+                response
+            },
+        )
         .await
 }
 
+/// Records the matched route template for the request's log lines and span.
+/// Installed as a route layer, since the route is only known once one matched.
+pub async fn record_route(request: Request, next: Next) -> Response {
+    if let Some(route) = request.extensions().get::<MatchedPath>() {
+        logging::set_route(route.as_str());
+        crate::telemetry::set_route(request.method().as_str(), route.as_str());
+    }
+    next.run(request).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -59,6 +201,82 @@ mod tests {
             .layer(middleware::from_fn(log_request))
     }
 
+    #[tokio::test]
+    async fn handlers_see_the_request_context() {
+        async fn context_handler() -> String {
+            logging::set_user_id(7);
+            REQUEST_CONTEXT
+                .with(|ctx| format!("{} {:?} {:?}", ctx.log_id, ctx.route(), ctx.user_id()))
+        }
+        let app = Router::new()
+            .route("/items/:id", get(context_handler))
+            .route_layer(middleware::from_fn(record_route))
+            .layer(middleware::from_fn(log_request));
+        let request = Request::builder()
+            .uri("/items/42")
+            .header("x-request-id", "abc123")
+            .body(Body::empty())
+            .unwrap();
+
+        let response: axum::response::Response = app.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"abc123 Some(\"/items/:id\") Some(7)");
+    }
+
+    #[test]
+    fn redacts_secret_query_values() {
+        let uri: Uri = "/api/v1/x?api_key=abc&keyword=run&access_token=t&code=123456&page=2"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            redact_uri(&uri),
+            "/api/v1/x?api_key=[REDACTED]&keyword=run&access_token=[REDACTED]&code=[REDACTED]&page=2"
+        );
+    }
+
+    #[test]
+    fn redacts_credentials_by_shape() {
+        let uri: Uri = "/api/v1/calendar/feed/dt_a1b2c3d4e5f6.ics?start_date=2026-01-01&t=eyJhbGciOi.eyJzdWIi.sig"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            redact_uri(&uri),
+            "/api/v1/calendar/feed/[REDACTED]?start_date=2026-01-01&t=[REDACTED]"
+        );
+        let plain: Uri = "/api/v1/topics/42".parse().unwrap();
+        assert_eq!(redact_uri(&plain), "/api/v1/topics/42");
+    }
+
+    #[test]
+    fn redacts_calendar_feed_tokens_of_any_shape() {
+        let uri: Uri = "/api/v1/calendar/feed/a1b2c3d4e5f6.ics".parse().unwrap();
+        assert_eq!(redact_uri(&uri), "/api/v1/calendar/feed/[REDACTED]");
+        let plain: Uri = "/api/v1/calendar/import".parse().unwrap();
+        assert_eq!(redact_uri(&plain), "/api/v1/calendar/import");
+    }
+
+    #[test]
+    fn redacts_digest_unsubscribe_tokens() {
+        let uri: Uri = "/api/v1/digest/unsubscribe/9f86d081884c7d659a2feaa0c55ad015"
+            .parse()
+            .unwrap();
+        assert_eq!(redact_uri(&uri), "/api/v1/digest/unsubscribe/[REDACTED]");
+    }
+
+    #[test]
+    fn slow_requests_are_warnings() {
+        let threshold = Some(Duration::from_millis(500));
+        assert_eq!(
+            completion_level(Duration::from_millis(499), threshold),
+            Level::Info
+        );
+        assert_eq!(
+            completion_level(Duration::from_millis(500), threshold),
+            Level::Warn
+        );
+        assert_eq!(completion_level(Duration::from_secs(60), None), Level::Info);
+    }
+
     #[tokio::test]
     async fn log_request_passes_through_get() {
         let app = test_app();
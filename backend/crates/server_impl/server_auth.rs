@@ -6,11 +6,19 @@ use axum::{
     response::Response,
 };
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
-static JWT_SECRET: Lazy<String> =
-    Lazy::new(|| std::env::var("JWT_SECRET").expect("JWT_SECRET environment variable must be set"));
+use crate::metrics::AuthResult;
+
+/// Validated as present at startup.
+fn jwt_secret() -> &'static [u8] {
+    crate::config::get()
+        .auth
+        .jwt_secret
+        .as_deref()
+        .unwrap_or_default()
+        .as_bytes()
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -20,7 +28,9 @@ pub struct Claims {
 
 pub fn create_jwt(user_id: i64) -> Result<String, jsonwebtoken::errors::Error> {
     let exp = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::days(7))
+        .checked_add_signed(chrono::Duration::hours(i64::from(
+            crate::config::get().auth.jwt_lifetime_hours,
+        )))
         .expect("valid timestamp")
         .timestamp() as usize;
 
@@ -32,7 +42,7 @@ pub fn create_jwt(user_id: i64) -> Result<String, jsonwebtoken::errors::Error> {
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &EncodingKey::from_secret(jwt_secret()),
     )
 }
 
@@ -44,6 +54,24 @@ pub enum AuthMethod {
     ApiKey,
 }
 
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Jwt => "jwt",
+            AuthMethod::ApiKey => "api_key",
+        }
+    }
+}
+
+/// Tags the request's log lines with the caller. Only the display prefix of an
+/// API key is logged.
+pub(crate) fn record_caller(user_id: i64, method: AuthMethod, token: &str) {
+    logging::set_user_id(user_id);
+    let key_prefix = (method == AuthMethod::ApiKey).then(|| db::api_key_display_prefix(token));
+    logging::set_auth(method.as_str(), key_prefix);
+    crate::telemetry::set_user(user_id, method.as_str());
+}
+
 fn extract_bearer(req: &Request<Body>) -> Option<String> {
     req.headers()
         .get(header::AUTHORIZATION)
@@ -52,9 +80,41 @@ fn extract_bearer(req: &Request<Body>) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+/// `db::lookup_api_key`, counted in the API-key lookup metric.
+pub(crate) async fn lookup_api_key(
+    token: &str,
+    scope: &str,
+) -> Result<Option<i64>, db::StoreError> {
+    let result = db::lookup_api_key(token, scope).await;
+    crate::metrics::record_api_key_lookup(match &result {
+        Ok(Some(_)) => AuthResult::Valid,
+        Ok(None) => AuthResult::Invalid,
+        Err(_) => AuthResult::Error,
+    });
+    result
+}
+
+/// Decodes a JWT into its user id, counted in the JWT validation metric.
+fn decode_jwt(token: &str) -> Result<i64, StatusCode> {
+    let user_id = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(jwt_secret()),
+        &Validation::default(),
+    )
+    .ok()
+    .and_then(|data| data.claims.sub.parse::<i64>().ok());
+    crate::metrics::record_jwt_validation(if user_id.is_some() {
+        AuthResult::Valid
+    } else {
+        AuthResult::Invalid
+    });
+    user_id.ok_or(StatusCode::UNAUTHORIZED)
+}
+
 /// Accepts either a JWT or a `dt_`-prefixed API key in the `Authorization: Bearer ...`
 /// header. Tokens are dispatched by prefix: API-key lookups hit the database;
-/// everything else is decoded as a JWT.
+/// everything else is decoded as a JWT. Only full-scope API keys are accepted;
+/// calendar-feed keys are limited to the feed route.
 pub async fn auth_middleware(mut req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
     if req.method() == axum::http::Method::OPTIONS {
         return Ok(next.run(req).await);
@@ -63,28 +123,22 @@ pub async fn auth_middleware(mut req: Request<Body>, next: Next) -> Result<Respo
     let token = extract_bearer(&req).ok_or(StatusCode::UNAUTHORIZED)?;
 
     let (user_id, method) = if token.starts_with(db::API_KEY_PREFIX) {
-        let user_id = db::lookup_api_key(&token)
+        let user_id = lookup_api_key(&token, db::API_KEY_SCOPE_FULL)
+            .await
             .map_err(|e| {
                 log::error!("Failed to look up API key: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
+                match e {
+                    db::StoreError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                }
             })?
             .ok_or(StatusCode::UNAUTHORIZED)?;
         (user_id, AuthMethod::ApiKey)
     } else {
-        let token_data = decode::<Claims>(
-            &token,
-            &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-            &Validation::default(),
-        )
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
-        let user_id = token_data
-            .claims
-            .sub
-            .parse::<i64>()
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
-        (user_id, AuthMethod::Jwt)
+        (decode_jwt(&token)?, AuthMethod::Jwt)
     };
 
+    record_caller(user_id, method, &token);
     req.extensions_mut().insert(user_id);
     req.extensions_mut().insert(method);
     Ok(next.run(req).await)
@@ -107,19 +161,9 @@ pub async fn jwt_only_middleware(
         return Err(StatusCode::FORBIDDEN);
     }
 
-    let token_data = decode::<Claims>(
-        &token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-    let user_id = token_data
-        .claims
-        .sub
-        .parse::<i64>()
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = decode_jwt(&token)?;
 
+    record_caller(user_id, AuthMethod::Jwt, &token);
     req.extensions_mut().insert(user_id);
     req.extensions_mut().insert(AuthMethod::Jwt);
     Ok(next.run(req).await)
@@ -130,13 +174,9 @@ mod tests {
     use super::*;
     use jsonwebtoken::{DecodingKey, Validation, decode};
 
-    fn setup_jwt_secret() {
-        // Ensure JWT_SECRET is set for tests
-        // SAFETY: Tests run sequentially via --test-threads=1 or are independent of
-        // each other's env reads; no other thread reads JWT_SECRET concurrently here.
-        unsafe {
-            std::env::set_var("JWT_SECRET", "test_secret_key_for_unit_tests");
-        }
+    /// The secret from the test configuration.
+    fn setup_jwt_secret() -> &'static str {
+        crate::config::get().auth.jwt_secret.as_deref().unwrap()
     }
 
     #[test]
@@ -147,7 +187,7 @@ mod tests {
         assert!(!token.is_empty());
 
         // Decode and verify
-        let secret = std::env::var("JWT_SECRET").unwrap();
+        let secret = setup_jwt_secret();
         let token_data = decode::<Claims>(
             &token,
             &DecodingKey::from_secret(secret.as_bytes()),
@@ -164,7 +204,7 @@ mod tests {
         let user_id = 3_735_928_559;
         let token = create_jwt(user_id).unwrap();
 
-        let secret = std::env::var("JWT_SECRET").unwrap();
+        let secret = setup_jwt_secret();
         let token_data = decode::<Claims>(
             &token,
             &DecodingKey::from_secret(secret.as_bytes()),
@@ -181,7 +221,7 @@ mod tests {
         let user_id = 1;
         let token = create_jwt(user_id).unwrap();
 
-        let secret = std::env::var("JWT_SECRET").unwrap();
+        let secret = setup_jwt_secret();
         let token_data = decode::<Claims>(
             &token,
             &DecodingKey::from_secret(secret.as_bytes()),
@@ -223,7 +263,7 @@ mod tests {
         let token = create_jwt(0).unwrap();
         assert!(!token.is_empty());
 
-        let secret = std::env::var("JWT_SECRET").unwrap();
+        let secret = setup_jwt_secret();
         let token_data = decode::<Claims>(
             &token,
             &DecodingKey::from_secret(secret.as_bytes()),
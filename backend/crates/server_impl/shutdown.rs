@@ -0,0 +1,187 @@
+use std::future::{Future, IntoFuture};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Background work, such as the email outbox worker, that must finish before
+/// the process exits.
+static BACKGROUND_TASKS: Lazy<TaskTracker> = Lazy::new(TaskTracker::new);
+
+/// Spawns a task that shutdown waits for, unlike a bare `tokio::spawn`.
+pub(crate) fn spawn_background<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    BACKGROUND_TASKS.spawn(future);
+}
+
+/// Runs a listener beside the main server, such as the metrics endpoint.
+/// If it fails, the error is logged and `shutdown` is cancelled, so the
+/// process stops instead of running on without it.
+pub(crate) fn spawn_listener<S>(name: &'static str, server: S, shutdown: CancellationToken)
+where
+    S: IntoFuture<Output = std::io::Result<()>>,
+    S::IntoFuture: Send + 'static,
+{
+    let server = server.into_future();
+    spawn_background(async move {
+        if let Err(e) = server.await {
+            log::error!("{} listener failed: {}", name, e);
+            shutdown.cancel();
+        }
+    });
+}
+
+/// Resolves on SIGINT (Ctrl-C) or SIGTERM (systemd stop and restart).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => log::info!("Received SIGINT, shutting down"),
+        _ = terminate => log::info!("Received SIGTERM, shutting down"),
+    }
+}
+
+/// Returns a token that is cancelled when a shutdown signal arrives. Every
+/// listener hands it to `with_graceful_shutdown` so they stop accepting
+/// together.
+pub(crate) fn listen_for_shutdown() -> CancellationToken {
+    let token = CancellationToken::new();
+    let trigger = token.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        trigger.cancel();
+    });
+    token
+}
+
+/// Runs `server` until it has drained after `shutdown` is cancelled, then
+/// waits for background tasks. Both phases share one `drain_timeout`
+/// budget that starts at the signal; whatever is still running when it
+/// runs out is dropped.
+pub(crate) async fn serve_until_drained<S>(
+    server: S,
+    shutdown: CancellationToken,
+    drain_timeout: Duration,
+) -> std::io::Result<()>
+where
+    S: IntoFuture<Output = std::io::Result<()>>,
+{
+    let server = server.into_future();
+    tokio::pin!(server);
+
+    let deadline = tokio::select! {
+        result = &mut server => {
+            // The server stopped on its own, without a signal.
+            result?;
+            Instant::now() + drain_timeout
+        }
+        _ = shutdown.cancelled() => {
+            log::info!("Draining requests in flight (timeout {:?})", drain_timeout);
+            let deadline = Instant::now() + drain_timeout;
+            match tokio::time::timeout_at(deadline, &mut server).await {
+                Ok(result) => result?,
+                Err(_) => log::warn!("Drain timeout reached; dropping open connections"),
+            }
+            deadline
+        }
+    };
+
+    BACKGROUND_TASKS.close();
+    if !BACKGROUND_TASKS.is_empty() {
+        log::info!("Waiting for {} background task(s)", BACKGROUND_TASKS.len());
+    }
+    if tokio::time::timeout_at(deadline, BACKGROUND_TASKS.wait())
+        .await
+        .is_err()
+    {
+        log::warn!(
+            "Drain timeout reached; abandoning {} background task(s)",
+            BACKGROUND_TASKS.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+
+    async fn slow_handler() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        "done"
+    }
+
+    #[tokio::test]
+    async fn request_in_flight_completes_after_shutdown() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/slow", get(slow_handler));
+        let shutdown = CancellationToken::new();
+        let server =
+            axum::serve(listener, app).with_graceful_shutdown(shutdown.clone().cancelled_owned());
+        let served = tokio::spawn(serve_until_drained(
+            server,
+            shutdown.clone(),
+            Duration::from_secs(5),
+        ));
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            stream
+                .write_all(b"GET /slow HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.cancel();
+
+        let response = request.await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("done"));
+        served.await.unwrap().unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failing_listener_cancels_shutdown() {
+        let shutdown = CancellationToken::new();
+        spawn_listener(
+            "test",
+            async { Err(std::io::Error::other("accept failed")) },
+            shutdown.clone(),
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), shutdown.cancelled())
+            .await
+            .expect("shutdown is cancelled");
+    }
+}
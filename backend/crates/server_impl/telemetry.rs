@@ -0,0 +1,287 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use once_cell::sync::OnceCell;
+use opentelemetry::context::FutureExt;
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue, global};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+
+use crate::config::TracingConfig;
+
+/// Instrumentation scope of the request spans.
+const TRACER_NAME: &str = "backend";
+/// Path of the trace endpoint below an OTLP/HTTP collector's base URL.
+const OTLP_TRACES_PATH: &str = "/v1/traces";
+
+static PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
+
+/// Builds a provider that batches spans and posts them to the collector at
+/// `config.otlp_endpoint`.
+fn build_provider(config: &TracingConfig) -> Result<SdkTracerProvider, String> {
+    let endpoint = format!(
+        "{}{}",
+        config.otlp_endpoint.trim_end_matches('/'),
+        OTLP_TRACES_PATH
+    );
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| format!("failed to build the OTLP exporter: {}", e))?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build())
+}
+
+/// Installs the W3C trace context propagator and the OTLP exporter, and
+/// records spans for SQL statements. Does nothing when tracing is off.
+pub(crate) fn init(config: &TracingConfig) -> Result<(), String> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let provider = build_provider(config)?;
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    db::trace_queries()?;
+    let _ = PROVIDER.set(provider);
+    log::info!(
+        "Exporting traces to {} as {}",
+        config.otlp_endpoint,
+        config.service_name
+    );
+    Ok(())
+}
+
+/// Exports the spans still buffered. Called once the server has stopped.
+pub(crate) fn shutdown() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        log::warn!("Failed to flush traces: {}", e);
+    }
+}
+
+/// Opens the server span for a request, as a child of the caller's
+/// `traceparent` when one is sent, and answers with the span's own
+/// `traceparent`. Handlers, and the database calls they make, run inside it.
+pub async fn trace_request(request: Request, next: Next) -> Response {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    let method = request.method().to_string();
+    let tracer = global::tracer(TRACER_NAME);
+    // Renamed to `METHOD /route` by `set_route` once a route matched.
+    let span = tracer
+        .span_builder(method.clone())
+        .with_kind(SpanKind::Server)
+        .with_attributes([KeyValue::new("http.request.method", method)])
+        .start_with_context(&tracer, &parent);
+    let cx = parent.with_span(span);
+
+    let mut response = next.run(request).with_context(cx.clone()).await;
+
+    let span = cx.span();
+    let status = response.status();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        i64::from(status.as_u16()),
+    ));
+    if status.is_server_error() {
+        span.set_status(Status::error(status.to_string()));
+    }
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(response.headers_mut()))
+    });
+    span.end();
+    response
+}
+
+/// Trace id of the current span, if it belongs to a trace.
+pub(crate) fn current_trace_id() -> Option<String> {
+    let cx = Context::current();
+    let span_context = cx.span().span_context().clone();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// Names the request span after the matched route, e.g. `GET /api/v1/topics/:id`.
+pub(crate) fn set_route(method: &str, route: &str) {
+    let cx = Context::current();
+    let span = cx.span();
+    span.update_name(format!("{} {}", method, route));
+    span.set_attribute(KeyValue::new("http.route", route.to_string()));
+}
+
+/// Records the authenticated caller on the request span.
+pub(crate) fn set_user(user_id: i64, auth_method: &'static str) {
+    let cx = Context::current();
+    let span = cx.span();
+    span.set_attribute(KeyValue::new("enduser.id", user_id.to_string()));
+    span.set_attribute(KeyValue::new("auth.method", auth_method));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::StatusCode, middleware, routing::get};
+    use opentelemetry::trace::{Span, TracerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SpanData};
+    use std::io::{Read, Write};
+    use tower::ServiceExt;
+
+    /// Spans recorded by tests. The global provider is process-wide, so each
+    /// test picks its spans out by trace id.
+    fn test_exporter() -> &'static InMemorySpanExporter {
+        static EXPORTER: OnceCell<InMemorySpanExporter> = OnceCell::new();
+        EXPORTER.get_or_init(|| {
+            let exporter = InMemorySpanExporter::default();
+            let provider = SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build();
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            global::set_tracer_provider(provider);
+            exporter
+        })
+    }
+
+    fn spans_of(trace_id: &str) -> Vec<SpanData> {
+        test_exporter()
+            .get_finished_spans()
+            .unwrap()
+            .into_iter()
+            .filter(|s| s.span_context.trace_id().to_string() == trace_id)
+            .collect()
+    }
+
+    /// Answers with the request's log id.
+    fn app() -> Router {
+        async fn handler() -> String {
+            global::tracer("db").start("db::get_topics").end();
+            logging::current_log_id()
+        }
+        Router::new()
+            .route("/api/v1/topics/:id", get(handler))
+            .route_layer(middleware::from_fn(crate::request_logger::record_route))
+            .layer(middleware::from_fn(crate::request_logger::log_request))
+            .layer(middleware::from_fn(trace_request))
+    }
+
+    #[tokio::test]
+    async fn continues_the_callers_trace() {
+        test_exporter();
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/topics/1")
+                    .header(
+                        "traceparent",
+                        format!("00-{}-00f067aa0ba902b7-01", trace_id),
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let traceparent = response.headers()["traceparent"].to_str().unwrap();
+        assert!(traceparent.starts_with(&format!("00-{}-", trace_id)));
+        assert!(!traceparent.contains("00f067aa0ba902b7"));
+        assert_eq!(response.headers()["x-request-id"], trace_id);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, trace_id);
+
+        let spans = spans_of(trace_id);
+        let server = spans
+            .iter()
+            .find(|s| s.span_kind == SpanKind::Server)
+            .unwrap();
+        assert_eq!(server.name, "GET /api/v1/topics/:id");
+        assert_eq!(server.parent_span_id.to_string(), "00f067aa0ba902b7");
+        let call = spans.iter().find(|s| s.name == "db::get_topics").unwrap();
+        assert_eq!(call.parent_span_id, server.span_context.span_id());
+    }
+
+    #[tokio::test]
+    async fn starts_a_trace_without_traceparent() {
+        test_exporter();
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/topics/1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let traceparent = response.headers()["traceparent"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let trace_id = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(trace_id.len(), 32);
+        assert!(traceparent.contains(&trace_id));
+        assert_eq!(spans_of(&trace_id).len(), 2);
+    }
+
+    /// Stands in for a collector: accepts one request, answers 200 and returns
+    /// what was sent.
+    fn stand_in_collector() -> (String, std::thread::JoinHandle<Vec<u8>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(std::time::Duration::from_millis(500)))
+                .unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+            received
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn exports_spans_over_otlp_http() {
+        let (endpoint, collector) = stand_in_collector();
+        let provider = build_provider(&TracingConfig {
+            enabled: true,
+            otlp_endpoint: format!("{}/", endpoint),
+            service_name: "tracing-test".to_string(),
+            sample_ratio: 1.0,
+        })
+        .unwrap();
+        provider.tracer("test").start("exported-span-name").end();
+        let _ = provider.shutdown();
+
+        let received = String::from_utf8_lossy(&collector.join().unwrap()).into_owned();
+        assert!(received.starts_with("POST /v1/traces HTTP/1.1"));
+        assert!(received.contains("application/x-protobuf"));
+        assert!(received.contains("exported-span-name"));
+        assert!(received.contains("tracing-test"));
+    }
+}
@@ -0,0 +1,246 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::{
+    Router,
+    http::{HeaderMap, StatusCode, Uri, header, uri::Authority},
+    response::{IntoResponse, Redirect},
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use native_tls::{Identity, Protocol};
+use tokio::net::TcpListener;
+use tokio_native_tls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+
+/// A client that connects but never finishes the handshake is dropped
+/// after this long.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Builds an acceptor from a PEM certificate chain and a PEM PKCS#8 private
+/// key. TLS 1.2 is the minimum protocol version.
+pub(crate) fn load_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, String> {
+    let cert = std::fs::read(cert_path)
+        .map_err(|e| format!("failed to read {}: {}", cert_path.display(), e))?;
+    let key = std::fs::read(key_path)
+        .map_err(|e| format!("failed to read {}: {}", key_path.display(), e))?;
+    let identity = Identity::from_pkcs8(&cert, &key).map_err(|e| {
+        format!(
+            "invalid certificate or key (the key must be PEM PKCS#8): {}",
+            e
+        )
+    })?;
+    native_tls::TlsAcceptor::builder(identity)
+        .min_protocol_version(Some(Protocol::Tlsv12))
+        .build()
+        .map(TlsAcceptor::from)
+        .map_err(|e| format!("failed to set up TLS: {}", e))
+}
+
+/// The acceptor for new connections. Reloading swaps it out; connections
+/// already open keep the certificate they were accepted with.
+pub(crate) struct ReloadableAcceptor {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<TlsAcceptor>>,
+}
+
+impl ReloadableAcceptor {
+    pub(crate) fn load(cert_path: &Path, key_path: &Path) -> Result<Self, String> {
+        Ok(ReloadableAcceptor {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(Arc::new(load_acceptor(cert_path, key_path)?)),
+        })
+    }
+
+    fn current(&self) -> Arc<TlsAcceptor> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Rereads the certificate and key. On failure the previous pair stays in
+    /// use, so a half-written renewal cannot take the server down.
+    pub(crate) fn reload(&self) -> Result<(), String> {
+        let acceptor = load_acceptor(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(acceptor);
+        Ok(())
+    }
+}
+
+/// Reloads the certificate on every SIGHUP until shutdown.
+pub(crate) fn reload_on_sighup(acceptor: Arc<ReloadableAcceptor>, shutdown: CancellationToken) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                log::error!(
+                    "Failed to listen for SIGHUP; certificate reload is disabled: {}",
+                    e
+                );
+                return;
+            }
+        };
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => match acceptor.reload() {
+                    Ok(()) => log::info!("Reloaded TLS certificate from {}", acceptor.cert_path.display()),
+                    Err(e) => log::error!("TLS certificate reload failed, keeping the current one: {}", e),
+                },
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    });
+    #[cfg(not(unix))]
+    let _ = (acceptor, shutdown);
+}
+
+/// Serves `app` over TLS until `shutdown` is cancelled, then stops accepting
+/// and waits for open connections to finish their requests.
+pub(crate) async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    acceptor: Arc<ReloadableAcceptor>,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Typically EMFILE; back off instead of spinning.
+                    log::error!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = shutdown.cancelled() => break,
+        };
+
+        let tls = acceptor.current();
+        let watcher = graceful.watcher();
+        let builder = builder.clone();
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, tls.accept(stream)).await
+            {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    log::debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    log::debug!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(connection.into_owned()).await {
+                log::debug!("Connection from {} closed with error: {}", peer, e);
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// Where a plain-HTTP request should be sent on the HTTPS listener. The
+/// port is left out when it is the HTTPS default.
+pub(crate) fn https_location(host: &str, path_and_query: &str, https_port: u16) -> Option<String> {
+    let authority: Authority = host.parse().ok()?;
+    let host = authority.host();
+    let port = if https_port == 443 {
+        String::new()
+    } else {
+        format!(":{}", https_port)
+    };
+    Some(format!("https://{}{}{}", host, port, path_and_query))
+}
+
+/// A listener that only redirects to HTTPS, keeping method and body with a
+/// 308.
+pub(crate) fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let path_and_query = uri.path_and_query().map_or("/", |p| p.as_str());
+        headers
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|host| https_location(host, path_and_query, https_port))
+            .map_or_else(
+                || (StatusCode::BAD_REQUEST, "Missing or invalid Host header").into_response(),
+                |location| Redirect::permanent(&location).into_response(),
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::Request;
+    use tower::ServiceExt as _;
+
+    #[test]
+    fn https_location_drops_http_port_and_default_https_port() {
+        assert_eq!(
+            https_location("tracker.example.com:80", "/api/v1/topics?x=1", 443).as_deref(),
+            Some("https://tracker.example.com/api/v1/topics?x=1")
+        );
+    }
+
+    #[test]
+    fn https_location_keeps_non_default_https_port() {
+        assert_eq!(
+            https_location("localhost:8080", "/", 8443).as_deref(),
+            Some("https://localhost:8443/")
+        );
+        assert_eq!(
+            https_location("[::1]:8080", "/healthz", 8443).as_deref(),
+            Some("https://[::1]:8443/healthz")
+        );
+    }
+
+    #[test]
+    fn https_location_rejects_garbage_host() {
+        assert_eq!(https_location("bad host", "/", 443), None);
+    }
+
+    #[tokio::test]
+    async fn redirect_router_sends_308_to_https() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/auth/login")
+            .header(header::HOST, "tracker.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = redirect_router(443).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://tracker.example.com/api/v1/auth/login"
+        );
+    }
+
+    #[test]
+    fn load_acceptor_reports_missing_files() {
+        let err = load_acceptor(
+            Path::new("/nonexistent/cert.pem"),
+            Path::new("/nonexistent/key.pem"),
+        )
+        .err()
+        .unwrap();
+        assert!(err.contains("/nonexistent/cert.pem"));
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDateTime};
+use db::PathTrackDraft;
+use db_model::models::{DailyTrack, Topic};
+use models::CsvLineError;
+
+use crate::handler::{is_valid_hex_color, validate_duration_minutes, validate_start_time};
+
+pub(crate) const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Upper bound on rows per import; the whole file is one transaction.
+pub(crate) const MAX_CSV_IMPORT_ROWS: usize = 2000;
+
+const HEADER: [&str; 6] = [
+    "start_time",
+    "end_time",
+    "duration_minutes",
+    "topic",
+    "comment",
+    "color",
+];
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+/// Leading characters that make a spreadsheet evaluate a cell as a formula.
+const FORMULA_TRIGGERS: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Prefixes a user-written cell with `'`, which spreadsheets read as "text
+/// follows", when it would otherwise be evaluated as a formula. Cells already
+/// starting with `'` get one too, so `unguard_cell` can always strip it.
+fn guard_cell(value: &str) -> String {
+    if value.starts_with(FORMULA_TRIGGERS) || value.starts_with('\'') {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Reverses `guard_cell`.
+fn unguard_cell(value: &str) -> &str {
+    value.strip_prefix('\'').unwrap_or(value)
+}
+
+/// Joins topic names into a path such as `Work/Backend`. A `/` or `\` inside
+/// a name is escaped with a backslash so the path splits back unambiguously.
+pub(crate) fn format_topic_path(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| name.replace('\\', "\\\\").replace('/', "\\/"))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Splits a path written by `format_topic_path` back into topic names.
+pub(crate) fn split_topic_path(path: &str) -> Result<Vec<String>, String> {
+    let mut names = vec![String::new()];
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => names.last_mut().expect("non-empty").push(escaped),
+                None => return Err("topic path ends with a lone '\\'".to_string()),
+            },
+            '/' => names.push(String::new()),
+            _ => names.last_mut().expect("non-empty").push(c),
+        }
+    }
+
+    let names: Vec<String> = names.into_iter().map(|n| n.trim().to_string()).collect();
+    if names.iter().any(String::is_empty) {
+        return Err(format!("topic path '{}' has an empty segment", path));
+    }
+    Ok(names)
+}
+
+/// Topic names from the root down to `topic_id`. Stops at a missing parent,
+/// and at `topics.len()` levels so a parent cycle cannot loop forever.
+fn topic_names(topics: &HashMap<i64, Topic>, topic_id: i64) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut current = topics.get(&topic_id);
+    while let Some(topic) = current {
+        if names.len() >= topics.len() {
+            break;
+        }
+        names.push(topic.topic_name.as_str());
+        current = topic.parent_topic_id.and_then(|id| topics.get(&id));
+    }
+    names.reverse();
+    names
+}
+
+/// Renders tracks as CSV with the columns in `HEADER`. Times are UTC in
+/// RFC 3339 form; `color` is the topic's display color. Topic paths and
+/// comments are user-written, so they go through `guard_cell`.
+pub(crate) fn render_tracks_csv(tracks: &[DailyTrack], topics: &HashMap<i64, Topic>) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(HEADER).expect("write to Vec");
+    for track in tracks {
+        let end = track.start_time + Duration::minutes(i64::from(track.duration_minutes));
+        let topic = track.topic_id.and_then(|id| topics.get(&id));
+        let path = track
+            .topic_id
+            .map(|id| format_topic_path(&topic_names(topics, id)))
+            .unwrap_or_default();
+        writer
+            .write_record([
+                track.start_time.format(TIME_FORMAT).to_string(),
+                end.format(TIME_FORMAT).to_string(),
+                track.duration_minutes.to_string(),
+                guard_cell(&path),
+                guard_cell(track.comment.as_deref().unwrap_or_default()),
+                topic.map(|t| t.display_color.clone()).unwrap_or_default(),
+            ])
+            .expect("write to Vec");
+    }
+    let bytes = writer.into_inner().expect("flush to Vec");
+    String::from_utf8(bytes).expect("CSV of UTF-8 fields is UTF-8")
+}
+
+/// Accepts RFC 3339 (any offset, converted to UTC) or a naive
+/// `YYYY-MM-DD HH:MM[:SS]` taken as UTC, which is what spreadsheets tend to
+/// write back.
+fn parse_time(field: &str, value: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.naive_utc());
+    }
+    [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .ok_or_else(|| {
+        format!(
+            "Invalid {} '{}'. Expected RFC 3339, e.g. 2026-05-12T09:00:00Z",
+            field, value
+        )
+    })
+}
+
+/// Column positions, looked up by header name so columns may be reordered
+/// or left out.
+struct Columns {
+    start_time: usize,
+    end_time: Option<usize>,
+    duration_minutes: Option<usize>,
+    topic: usize,
+    comment: Option<usize>,
+    color: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &csv::StringRecord) -> Result<Self, String> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+        };
+        let required = |name: &str| find(name).ok_or_else(|| format!("missing '{}' column", name));
+
+        let columns = Columns {
+            start_time: required("start_time")?,
+            end_time: find("end_time"),
+            duration_minutes: find("duration_minutes"),
+            topic: required("topic")?,
+            comment: find("comment"),
+            color: find("color"),
+        };
+        if columns.end_time.is_none() && columns.duration_minutes.is_none() {
+            return Err(
+                "either an 'end_time' or a 'duration_minutes' column is required".to_string(),
+            );
+        }
+        Ok(columns)
+    }
+}
+
+fn parse_row(columns: &Columns, record: &csv::StringRecord) -> Result<PathTrackDraft, String> {
+    let field = |index: Option<usize>| {
+        index
+            .and_then(|i| record.get(i))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+
+    let start_time = field(Some(columns.start_time))
+        .ok_or("start_time is required")
+        .map_err(String::from)
+        .and_then(|v| parse_time("start_time", v))?;
+
+    let duration = field(columns.duration_minutes)
+        .map(|v| {
+            v.parse::<i32>()
+                .map_err(|_| format!("Invalid duration_minutes '{}'", v))
+        })
+        .transpose()?;
+    let end_time = field(columns.end_time)
+        .map(|v| parse_time("end_time", v))
+        .transpose()?;
+    let duration_minutes = match (duration, end_time) {
+        (Some(minutes), Some(end)) if start_time + Duration::minutes(i64::from(minutes)) != end => {
+            return Err("end_time does not match start_time + duration_minutes".to_string());
+        }
+        (Some(minutes), _) => minutes,
+        (None, Some(end)) => i32::try_from((end - start_time).num_minutes())
+            .map_err(|_| "end_time is too far from start_time".to_string())?,
+        (None, None) => return Err("end_time or duration_minutes is required".to_string()),
+    };
+
+    validate_start_time(&start_time.and_utc()).map_err(|e| e.message)?;
+    validate_duration_minutes(duration_minutes).map_err(|e| e.message)?;
+
+    let topic_path = field(Some(columns.topic))
+        .ok_or_else(|| "topic is required".to_string())
+        .map(unguard_cell)
+        .and_then(split_topic_path)?;
+
+    let color = field(columns.color).map(str::to_string);
+    if let Some(color) = color.as_deref().filter(|c| !is_valid_hex_color(c)) {
+        return Err(format!(
+            "Invalid color '{}'. Expected hex format like #3b82f6",
+            color
+        ));
+    }
+
+    Ok(PathTrackDraft {
+        start_time,
+        topic_path,
+        color,
+        comment: field(columns.comment).map(unguard_cell).map(str::to_string),
+        duration_minutes,
+    })
+}
+
+/// Parses and validates an uploaded CSV. Returns the drafts paired with their
+/// line numbers, or every problem found, so one upload reports all bad rows.
+pub(crate) fn parse_tracks_csv(
+    body: &str,
+) -> Result<Vec<(u64, PathTrackDraft)>, Vec<CsvLineError>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(body.trim_start_matches('\u{feff}').as_bytes());
+
+    let header_error = |message: String| vec![CsvLineError { line: 1, message }];
+    let header = reader
+        .headers()
+        .map_err(|e| header_error(format!("Invalid CSV header: {}", e)))?
+        .clone();
+    let columns = Columns::from_header(&header).map_err(header_error)?;
+
+    let mut drafts = Vec::new();
+    let mut errors = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        if index == MAX_CSV_IMPORT_ROWS {
+            errors.push(CsvLineError {
+                line: record
+                    .ok()
+                    .and_then(|r| r.position().map(|p| p.line()))
+                    .unwrap_or(0),
+                message: format!(
+                    "at most {} rows can be imported at once",
+                    MAX_CSV_IMPORT_ROWS
+                ),
+            });
+            break;
+        }
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(CsvLineError {
+                    line: e.position().map_or(0, |p| p.line()),
+                    message: format!("Invalid CSV: {}", e),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        match parse_row(&columns, &record) {
+            Ok(draft) => drafts.push((line, draft)),
+            Err(message) => errors.push(CsvLineError { line, message }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(drafts)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn topic(id: i64, name: &str, parent: Option<i64>) -> Topic {
+        Topic {
+            id,
+            topic_name: name.to_string(),
+            display_color: "#14b8a6".to_string(),
+            created_at: at("2026-01-01 00:00"),
+            updated_at: None,
+            parent_topic_id: parent,
+            user_id: Some(1),
+            version: 1,
+        }
+    }
+
+    fn track(start: &str, topic_id: Option<i64>, comment: Option<&str>) -> DailyTrack {
+        DailyTrack {
+            id: 1,
+            start_time: at(start),
+            created_at: at(start),
+            updated_at: None,
+            topic_id,
+            comment: comment.map(str::to_string),
+            user_id: Some(1),
+            duration_minutes: 90,
+            version: 1,
+        }
+    }
+
+    fn line_errors(body: &str) -> Vec<CsvLineError> {
+        match parse_tracks_csv(body) {
+            Ok(_) => panic!("expected line errors"),
+            Err(errors) => errors,
+        }
+    }
+
+    #[test]
+    fn topic_path_round_trips_escaped_names() {
+        let path = format_topic_path(&["Work", "CI/CD", "a\\b"]);
+        assert_eq!(path, "Work/CI\\/CD/a\\\\b");
+        assert_eq!(
+            split_topic_path(&path).unwrap(),
+            vec!["Work", "CI/CD", "a\\b"]
+        );
+    }
+
+    #[test]
+    fn split_topic_path_rejects_empty_segments() {
+        assert!(split_topic_path("Work//Backend").is_err());
+        assert!(split_topic_path("Work/").is_err());
+    }
+
+    #[test]
+    fn render_tracks_csv_writes_paths_and_quotes_comments() {
+        let topics = HashMap::from([
+            (1, topic(1, "Work", None)),
+            (2, topic(2, "Backend", Some(1))),
+        ]);
+        let tracks = vec![track("2026-05-12 09:00", Some(2), Some("review, part 1"))];
+        let csv = render_tracks_csv(&tracks, &topics);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("start_time,end_time,duration_minutes,topic,comment,color")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "2026-05-12T09:00:00Z,2026-05-12T10:30:00Z,90,Work/Backend,\"review, part 1\",#14b8a6"
+            )
+        );
+    }
+
+    #[test]
+    fn parse_tracks_csv_round_trips_export() {
+        let topics = HashMap::from([
+            (1, topic(1, "Work", None)),
+            (2, topic(2, "Backend", Some(1))),
+        ]);
+        let tracks = vec![track("2026-05-12 09:00", Some(2), Some("review, part 1"))];
+        let drafts = parse_tracks_csv(&render_tracks_csv(&tracks, &topics)).unwrap();
+        let (line, draft) = &drafts[0];
+        assert_eq!(*line, 2);
+        assert_eq!(draft.start_time, at("2026-05-12 09:00"));
+        assert_eq!(draft.duration_minutes, 90);
+        assert_eq!(draft.topic_path, vec!["Work", "Backend"]);
+        assert_eq!(draft.comment.as_deref(), Some("review, part 1"));
+        assert_eq!(draft.color.as_deref(), Some("#14b8a6"));
+    }
+
+    #[test]
+    fn formula_cells_are_guarded_and_round_trip() {
+        let topics = HashMap::from([(1, topic(1, "=Work", None))]);
+        let comments = ["=HYPERLINK(\"x\")", "-1", "@SUM(A1)", "'quoted", "plain"];
+        let tracks: Vec<DailyTrack> = comments
+            .iter()
+            .map(|c| track("2026-05-12 09:00", Some(1), Some(c)))
+            .collect();
+        let csv = render_tracks_csv(&tracks, &topics);
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        assert!(rows[0].ends_with(",'=Work,\"'=HYPERLINK(\"\"x\"\")\",#14b8a6"));
+        assert!(rows[1].contains(",'-1,"));
+        assert!(rows[3].contains(",''quoted,"));
+        assert!(rows[4].contains(",plain,"));
+
+        let drafts = parse_tracks_csv(&csv).unwrap();
+        for ((_, draft), comment) in drafts.iter().zip(comments) {
+            assert_eq!(draft.topic_path, vec!["=Work"]);
+            assert_eq!(draft.comment.as_deref(), Some(comment));
+        }
+    }
+
+    #[test]
+    fn parse_tracks_csv_accepts_end_time_only_and_reordered_columns() {
+        let body = "topic,end_time,start_time\nWork,2026-05-12 10:00,2026-05-12 09:00\n";
+        let drafts = parse_tracks_csv(body).unwrap();
+        assert_eq!(drafts[0].1.duration_minutes, 60);
+        assert_eq!(drafts[0].1.comment, None);
+    }
+
+    #[test]
+    fn parse_tracks_csv_reports_every_bad_line() {
+        let body = "start_time,duration_minutes,topic\n\
+                    2026-05-12T09:15:00Z,30,Work\n\
+                    2026-05-12T10:00:00Z,30,Work\n\
+                    2026-05-12T11:00:00Z,45,Work\n\
+                    2026-05-12T12:00:00Z,30,\n";
+        let errors = line_errors(body);
+        let lines: Vec<u64> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 4, 5]);
+        assert!(errors[0].message.contains(":00 or :30"));
+        assert!(errors[1].message.contains("multiple of 30"));
+        assert_eq!(errors[2].message, "topic is required");
+    }
+
+    #[test]
+    fn parse_tracks_csv_rejects_mismatched_end_time() {
+        let body = "start_time,end_time,duration_minutes,topic\n\
+                    2026-05-12T09:00:00Z,2026-05-12T10:00:00Z,30,Work\n";
+        let errors = line_errors(body);
+        assert!(errors[0].message.contains("does not match"));
+    }
+
+    #[test]
+    fn parse_tracks_csv_requires_topic_and_time_columns() {
+        let errors = line_errors("start_time,topic\n");
+        assert_eq!(errors[0].line, 1);
+        assert!(parse_tracks_csv("start_time,duration_minutes\n").is_err());
+    }
+
+    #[test]
+    fn parse_tracks_csv_rejects_invalid_color() {
+        let body = "start_time,duration_minutes,topic,color\n2026-05-12T09:00:00Z,30,Work,blue\n";
+        let errors = line_errors(body);
+        assert!(errors[0].message.contains("Invalid color"));
+    }
+}
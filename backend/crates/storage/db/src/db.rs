@@ -1,211 +1,341 @@
 use chrono::{NaiveDate, NaiveDateTime};
 use db_model::models::{
-    ApiKey, DailyTrack, NewApiKey, NewDailyTrack, NewTopic, NewUser, Topic, User,
+    ApiKey, CalendarImportRule, DEFAULT_TOPIC_DISPLAY_COLOR, DailyTrack, NewApiKey,
+    NewCalendarImportRule, NewDailyTrack, NewTopic, NewTopicGoal, NewUser, Topic, TopicGoal, User,
 };
 use db_model::schema;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
-use diesel::result::Error as DieselError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use diesel::MysqlConnection;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use rand::RngCore;
 use sha2::{Digest, Sha256};
 use std::time::Duration;
 use utils::snowflake::generate_snowflake_id;
 use uuid::Uuid;
 
+use crate::error::StoreError;
+use crate::outbox::{OutboxDraft, insert_outbox_email};
+use crate::telemetry;
+
 pub const API_KEY_PREFIX: &str = "dt_";
+/// Scope of a general-purpose API key, accepted wherever a JWT is.
+pub const API_KEY_SCOPE_FULL: &str = "full";
+/// Scope of a key that can only read the user's calendar feed. Such keys end
+/// up in calendar URLs, so they must not unlock anything else.
+pub const API_KEY_SCOPE_CALENDAR_FEED: &str = "calendar_feed";
 
 pub type DbPool = Pool<ConnectionManager<MysqlConnection>>;
 pub type DbConn = PooledConnection<ConnectionManager<MysqlConnection>>;
 
+/// Connection pool settings, supplied by the server's configuration.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub database_url: String,
+    pub max_connections: u32,
+    pub min_idle: u32,
+    pub connection_timeout: Duration,
+    pub idle_timeout: Duration,
+    pub max_lifetime: Duration,
+}
+
+static POOL_CONFIG: OnceCell<PoolConfig> = OnceCell::new();
+
+/// Sets the pool settings. Must be called once, before `DB_POOL` is first
+/// used.
+pub fn configure_pool(config: PoolConfig) -> Result<(), String> {
+    POOL_CONFIG
+        .set(config)
+        .map_err(|_| "database pool is already configured".to_string())
+}
+
+/// Built unchecked so an unreachable MySQL does not poison the pool: the
+/// pool keeps retrying in the background and `/readyz` reports the outage.
 pub static DB_POOL: Lazy<DbPool> = Lazy::new(|| {
-    dotenvy::dotenv().ok();
-    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let config = POOL_CONFIG
+        .get()
+        .expect("db::configure_pool must be called before the pool is used");
     Pool::builder()
-        .max_size(8)
-        .min_idle(Some(1))
-        .connection_timeout(Duration::from_secs(10))
-        .idle_timeout(Some(Duration::from_secs(300)))
-        .max_lifetime(Some(Duration::from_secs(1800)))
-        .build(ConnectionManager::<MysqlConnection>::new(url))
-        .expect("failed to build MySQL connection pool")
+        .max_size(config.max_connections)
+        .min_idle(Some(config.min_idle))
+        .connection_timeout(config.connection_timeout)
+        .idle_timeout(Some(config.idle_timeout))
+        .max_lifetime(Some(config.max_lifetime))
+        .build_unchecked(ConnectionManager::<MysqlConnection>::new(
+            &config.database_url,
+        ))
 });
 
-pub fn get_topics(
+/// Checks out a pooled connection, waiting at most the pool's connection
+/// timeout.
+fn connection() -> Result<DbConn, StoreError> {
+    DB_POOL
+        .get()
+        .map_err(|e| StoreError::Unavailable(format!("no pooled connection: {}", e)))
+}
+
+/// Runs `f` with a pooled connection on tokio's blocking threads, so slow
+/// queries and waits for a free connection never hold up the async workers.
+/// The caller's trace context follows it there.
+pub(crate) async fn run<T, F>(operation: &'static str, f: F) -> Result<T, StoreError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut MysqlConnection) -> Result<T, StoreError> + Send + 'static,
+{
+    let cx = opentelemetry::Context::current();
+    tokio::task::spawn_blocking(move || {
+        let _cx = cx.attach();
+        let _span = telemetry::call_span(operation);
+        let mut connection = connection()?;
+        f(&mut connection)
+    })
+    .await
+    .map_err(|e| join_error(operation, e))?
+}
+
+/// A panic in `f` is a bug, not an outage: it is logged and reported as a
+/// database failure. Only a cancelled task, e.g. during shutdown, counts as
+/// unavailable.
+fn join_error(operation: &'static str, e: tokio::task::JoinError) -> StoreError {
+    if e.is_panic() {
+        log::error!("{} panicked: {}", operation, e);
+        StoreError::Database(DieselError::DatabaseError(
+            DatabaseErrorKind::Unknown,
+            Box::new(format!("{} panicked", operation)),
+        ))
+    } else {
+        StoreError::Unavailable(format!("{} did not finish: {}", operation, e))
+    }
+}
+
+/// How long a readiness check waits for a pooled connection.
+const READINESS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Checks out a pooled connection for a readiness probe, waiting at most
+/// `READINESS_CONNECTION_TIMEOUT` instead of the pool's usual timeout.
+pub fn readiness_connection() -> Result<DbConn, String> {
+    DB_POOL
+        .get_timeout(READINESS_CONNECTION_TIMEOUT)
+        .map_err(|e| format!("no database connection: {}", e))
+}
+
+/// Runs `SELECT 1` to prove the connection can answer queries.
+pub fn ping_database(connection: &mut MysqlConnection) -> Result<(), String> {
+    diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>("1"))
+        .get_result::<i32>(connection)
+        .map(|_| ())
+        .map_err(|e| format!("SELECT 1 failed: {}", e))
+}
+
+pub async fn get_topics(
     parent_topic_id: Option<i64>,
     user_id: Option<i64>,
-) -> Result<Vec<Topic>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let mut query = schema::topic::dsl::topic
-        .select(Topic::as_select())
-        .into_boxed();
-    if let Some(uid) = user_id {
-        query = query.filter(schema::topic::dsl::user_id.eq(uid));
-    }
-    if let Some(parent_id) = parent_topic_id {
-        query = query.filter(schema::topic::dsl::parent_topic_id.eq(parent_id));
-    }
+) -> Result<Vec<Topic>, StoreError> {
+    run("get_topics", move |connection| {
+        let mut query = schema::topic::dsl::topic
+            .select(Topic::as_select())
+            .into_boxed();
+        if let Some(uid) = user_id {
+            query = query.filter(schema::topic::dsl::user_id.eq(uid));
+        }
+        if let Some(parent_id) = parent_topic_id {
+            query = query.filter(schema::topic::dsl::parent_topic_id.eq(parent_id));
+        }
 
-    query.load(&mut *connection)
+        Ok(query.load(connection)?)
+    })
+    .await
 }
 
-pub fn create_topic(
+pub async fn create_topic(
     topic_name: String,
     display_color: String,
     parent_topic_id: Option<i64>,
     user_id: Option<i64>,
-) -> Result<Topic, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
+) -> Result<Topic, StoreError> {
+    run("create_topic", move |connection| {
+        let mut query = schema::topic::dsl::topic
+            .filter(schema::topic::topic_name.eq(&topic_name))
+            .into_boxed();
+        if let Some(ref uid) = user_id {
+            query = query.filter(schema::topic::user_id.eq(*uid));
+        }
+        let existing: Option<Topic> = query
+            .select(Topic::as_select())
+            .first(connection)
+            .optional()?;
+
+        if existing.is_some() {
+            return Err(StoreError::DuplicateName(format!(
+                "Topic with name '{}' already exists",
+                topic_name
+            )));
+        }
 
-    let mut query = schema::topic::dsl::topic
-        .filter(schema::topic::topic_name.eq(&topic_name))
-        .into_boxed();
-    if let Some(ref uid) = user_id {
-        query = query.filter(schema::topic::user_id.eq(uid.clone()));
-    }
-    let existing: Option<Topic> = query
-        .select(Topic::as_select())
-        .first(&mut *connection)
-        .optional()?;
+        let now = chrono::Utc::now().naive_utc();
+        let id = generate_snowflake_id();
+
+        let new_topic = NewTopic {
+            id,
+            topic_name,
+            display_color,
+            created_at: now,
+            updated_at: None,
+            parent_topic_id,
+            user_id,
+        };
+
+        diesel::insert_into(schema::topic::table)
+            .values(&new_topic)
+            .execute(connection)?;
+
+        Ok(schema::topic::dsl::topic
+            .find(id)
+            .select(Topic::as_select())
+            .first(connection)?)
+    })
+    .await
+}
 
-    if existing.is_some() {
-        return Err(DieselError::DatabaseError(
-            diesel::result::DatabaseErrorKind::UniqueViolation,
-            Box::new(format!("Topic with name '{}' already exists", topic_name)),
-        ));
+/// Fails with `VersionMismatch` unless `if_match` is unset or lists `version`.
+fn check_version(if_match: &Option<Vec<i32>>, version: i32) -> Result<(), StoreError> {
+    match if_match {
+        Some(versions) if !versions.contains(&version) => Err(StoreError::VersionMismatch),
+        _ => Ok(()),
     }
+}
 
-    let now = chrono::Utc::now().naive_utc();
-    let id = generate_snowflake_id();
-
-    let new_topic = NewTopic {
-        id,
-        topic_name,
-        display_color,
-        created_at: now,
-        updated_at: None,
-        parent_topic_id,
-        user_id,
-    };
-
-    diesel::insert_into(schema::topic::table)
-        .values(&new_topic)
-        .execute(&mut *connection)?;
-
-    schema::topic::dsl::topic
-        .find(id)
-        .select(Topic::as_select())
-        .first(&mut *connection)
+/// Fields to change on a topic; `None` keeps the current value.
+#[derive(Default)]
+pub struct TopicChange {
+    pub topic_name: Option<String>,
+    pub display_color: Option<String>,
 }
 
-pub fn update_topic(
+/// Updates a topic and bumps its version. With `if_match`, the update only
+/// happens while the topic is at one of those versions.
+pub async fn update_topic(
     id: i64,
-    topic_name: String,
-    display_color: String,
+    change: TopicChange,
     user_id: i64,
-) -> Result<Option<Topic>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let Some(existing_topic) = schema::topic::dsl::topic
-        .filter(schema::topic::dsl::id.eq(id))
-        .filter(schema::topic::dsl::user_id.eq(user_id))
-        .select(Topic::as_select())
-        .first(&mut *connection)
-        .optional()?
-    else {
-        return Ok(None);
-    };
-
-    let duplicate_name_exists = schema::topic::dsl::topic
-        .filter(schema::topic::dsl::user_id.eq(user_id))
-        .filter(schema::topic::dsl::topic_name.eq(&topic_name))
-        .filter(schema::topic::dsl::id.ne(id))
-        .select(Topic::as_select())
-        .first(&mut *connection)
-        .optional()?
-        .is_some();
-    if duplicate_name_exists {
-        return Err(DieselError::DatabaseError(
-            diesel::result::DatabaseErrorKind::UniqueViolation,
-            Box::new(format!("Topic with name '{}' already exists", topic_name)),
-        ));
-    }
-
-    diesel::update(schema::topic::dsl::topic.find(existing_topic.id))
-        .set((
-            schema::topic::dsl::topic_name.eq(topic_name),
-            schema::topic::dsl::display_color.eq(display_color),
-            schema::topic::dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
-        ))
-        .execute(&mut *connection)?;
-
-    schema::topic::dsl::topic
-        .find(existing_topic.id)
-        .select(Topic::as_select())
-        .first(&mut *connection)
-        .optional()
+    if_match: Option<Vec<i32>>,
+) -> Result<Option<Topic>, StoreError> {
+    run("update_topic", move |connection| {
+        connection.transaction(|conn| {
+            let Some(existing_topic) = schema::topic::dsl::topic
+                .filter(schema::topic::dsl::id.eq(id))
+                .filter(schema::topic::dsl::user_id.eq(user_id))
+                .select(Topic::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            check_version(&if_match, existing_topic.version)?;
+            let topic_name = change.topic_name.unwrap_or(existing_topic.topic_name);
+            let display_color = change.display_color.unwrap_or(existing_topic.display_color);
+
+            let duplicate_name_exists = schema::topic::dsl::topic
+                .filter(schema::topic::dsl::user_id.eq(user_id))
+                .filter(schema::topic::dsl::topic_name.eq(&topic_name))
+                .filter(schema::topic::dsl::id.ne(id))
+                .select(Topic::as_select())
+                .first(conn)
+                .optional()?
+                .is_some();
+            if duplicate_name_exists {
+                return Err(StoreError::DuplicateName(format!(
+                    "Topic with name '{}' already exists",
+                    topic_name
+                )));
+            }
+
+            diesel::update(schema::topic::dsl::topic.find(existing_topic.id))
+                .set((
+                    schema::topic::dsl::topic_name.eq(topic_name),
+                    schema::topic::dsl::display_color.eq(display_color),
+                    schema::topic::dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+                    schema::topic::dsl::version.eq(schema::topic::dsl::version + 1),
+                ))
+                .execute(conn)?;
+
+            Ok(schema::topic::dsl::topic
+                .find(existing_topic.id)
+                .select(Topic::as_select())
+                .first(conn)
+                .optional()?)
+        })
+    })
+    .await
 }
 
-pub fn get_topic_by_id(id: i64) -> Result<Option<Topic>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    schema::topic::dsl::topic
-        .find(id)
-        .select(Topic::as_select())
-        .first(&mut *connection)
-        .optional()
+pub async fn get_topic_by_id(id: i64) -> Result<Option<Topic>, StoreError> {
+    run("get_topic_by_id", move |connection| {
+        Ok(schema::topic::dsl::topic
+            .find(id)
+            .select(Topic::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
 }
 
-pub fn get_topic_by_id_for_user(id: i64, user_id: i64) -> Result<Option<Topic>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    schema::topic::dsl::topic
-        .filter(schema::topic::dsl::id.eq(id))
-        .filter(schema::topic::dsl::user_id.eq(user_id))
-        .select(Topic::as_select())
-        .first(&mut *connection)
-        .optional()
+pub async fn get_topic_by_id_for_user(id: i64, user_id: i64) -> Result<Option<Topic>, StoreError> {
+    run("get_topic_by_id_for_user", move |connection| {
+        Ok(schema::topic::dsl::topic
+            .filter(schema::topic::dsl::id.eq(id))
+            .filter(schema::topic::dsl::user_id.eq(user_id))
+            .select(Topic::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
 }
 
-pub fn get_daily_tracks(
+pub async fn get_daily_tracks(
     start_date: Option<NaiveDate>,
     end_date: Option<NaiveDate>,
     topic_id: Option<i64>,
     user_id: Option<i64>,
-) -> Result<Vec<DailyTrack>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-
-    let mut query = schema::daily_track::dsl::daily_track
-        .select(DailyTrack::as_select())
-        .into_boxed();
-    if let Some(uid) = user_id {
-        query = query.filter(schema::daily_track::dsl::user_id.eq(uid));
-    }
+) -> Result<Vec<DailyTrack>, StoreError> {
+    run("get_daily_tracks", move |connection| {
+        let mut query = schema::daily_track::dsl::daily_track
+            .select(DailyTrack::as_select())
+            .into_boxed();
+        if let Some(uid) = user_id {
+            query = query.filter(schema::daily_track::dsl::user_id.eq(uid));
+        }
 
-    if let Some(start) = start_date {
-        let start_dt = start.and_hms_opt(0, 0, 0).expect("valid start datetime");
-        query = query.filter(schema::daily_track::start_time.ge(start_dt));
-    }
+        if let Some(start) = start_date {
+            let start_dt = start.and_hms_opt(0, 0, 0).expect("valid start datetime");
+            query = query.filter(schema::daily_track::start_time.ge(start_dt));
+        }
 
-    if let Some(end) = end_date {
-        let end_dt = end.and_hms_opt(23, 59, 59).expect("valid end datetime");
-        query = query.filter(schema::daily_track::start_time.le(end_dt));
-    }
+        if let Some(end) = end_date {
+            let end_dt = end.and_hms_opt(23, 59, 59).expect("valid end datetime");
+            query = query.filter(schema::daily_track::start_time.le(end_dt));
+        }
 
-    if let Some(tid) = topic_id {
-        query = query.filter(schema::daily_track::topic_id.eq(tid));
-    }
-    query.load(&mut *connection)
+        if let Some(tid) = topic_id {
+            query = query.filter(schema::daily_track::topic_id.eq(tid));
+        }
+        Ok(query.load(connection)?)
+    })
+    .await
 }
 
-/// Returns true when the user already has a track that overlaps the half-open
-/// interval `[start_time, start_time + duration_minutes minutes)`. If
-/// `exclude_id` is supplied, that track id is ignored (used for updates).
-fn has_overlapping_track(
-    connection: &mut DbConn,
+/// Returns the id of a track of the user that overlaps the half-open
+/// interval `[start_time, start_time + duration_minutes minutes)`, if any.
+/// If `exclude_id` is supplied, that track id is ignored (used for updates).
+fn overlapping_track(
+    connection: &mut MysqlConnection,
     user_id: Option<i64>,
     start_time: NaiveDateTime,
     duration_minutes: i32,
     exclude_id: Option<i64>,
-) -> Result<bool, DieselError> {
+) -> Result<Option<i64>, DieselError> {
     use diesel::sql_types::{Bool, Datetime as SqlDatetime};
 
     let new_end = start_time + chrono::Duration::minutes(duration_minutes as i64);
@@ -227,129 +357,676 @@ fn has_overlapping_track(
         query = query.filter(schema::daily_track::id.ne(id));
     }
 
-    Ok(query
-        .select(DailyTrack::as_select())
+    query
+        .select(schema::daily_track::id)
         .first(connection)
-        .optional()?
-        .is_some())
+        .optional()
 }
 
-pub fn create_daily_track(
+pub async fn create_daily_track(
     start_time: NaiveDateTime,
     topic_id: Option<i64>,
     comment: Option<String>,
     user_id: Option<i64>,
     duration_minutes: i32,
-) -> Result<DailyTrack, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-
-    if has_overlapping_track(&mut connection, user_id, start_time, duration_minutes, None)? {
-        return Err(DieselError::DatabaseError(
-            diesel::result::DatabaseErrorKind::UniqueViolation,
-            Box::new(format!(
-                "An overlapping record already exists for time period starting at {}",
-                start_time
-            )),
-        ));
-    }
+) -> Result<DailyTrack, StoreError> {
+    run("create_daily_track", move |connection| {
+        if let Some(conflicting_track_id) =
+            overlapping_track(connection, user_id, start_time, duration_minutes, None)?
+        {
+            return Err(StoreError::Overlap {
+                conflicting_track_id,
+            });
+        }
 
-    let id = generate_snowflake_id();
-    let new_track = NewDailyTrack::new(
-        id,
-        start_time,
-        topic_id,
-        comment,
-        user_id,
-        duration_minutes,
-    );
-
-    diesel::insert_into(schema::daily_track::table)
-        .values(&new_track)
-        .execute(&mut *connection)?;
-
-    schema::daily_track::dsl::daily_track
-        .find(id)
-        .select(DailyTrack::as_select())
-        .first(&mut *connection)
-}
-
-pub fn get_daily_track_by_id(id: i64, user_id: i64) -> Result<Option<DailyTrack>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    schema::daily_track::dsl::daily_track
-        .filter(schema::daily_track::dsl::id.eq(id))
-        .filter(schema::daily_track::dsl::user_id.eq(user_id))
-        .select(DailyTrack::as_select())
-        .first(&mut *connection)
-        .optional()
+        let id = generate_snowflake_id();
+        let new_track =
+            NewDailyTrack::new(id, start_time, topic_id, comment, user_id, duration_minutes);
+
+        diesel::insert_into(schema::daily_track::table)
+            .values(&new_track)
+            .execute(connection)?;
+
+        Ok(schema::daily_track::dsl::daily_track
+            .find(id)
+            .select(DailyTrack::as_select())
+            .first(connection)?)
+    })
+    .await
 }
 
-pub fn update_daily_track(
+pub async fn get_daily_track_by_id(
     id: i64,
-    topic_id: i64,
-    comment: Option<String>,
     user_id: i64,
-    duration_minutes: i32,
-) -> Result<Option<DailyTrack>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let Some(existing_track) = schema::daily_track::dsl::daily_track
-        .filter(schema::daily_track::dsl::id.eq(id))
-        .filter(schema::daily_track::dsl::user_id.eq(user_id))
-        .select(DailyTrack::as_select())
-        .first(&mut *connection)
-        .optional()?
-    else {
-        return Ok(None);
-    };
-
-    if has_overlapping_track(
-        &mut connection,
-        Some(user_id),
-        existing_track.start_time,
-        duration_minutes,
-        Some(existing_track.id),
-    )? {
-        return Err(DieselError::DatabaseError(
-            diesel::result::DatabaseErrorKind::UniqueViolation,
-            Box::new(format!(
-                "An overlapping record already exists for time period starting at {}",
-                existing_track.start_time
-            )),
-        ));
+) -> Result<Option<DailyTrack>, StoreError> {
+    run("get_daily_track_by_id", move |connection| {
+        Ok(schema::daily_track::dsl::daily_track
+            .filter(schema::daily_track::dsl::id.eq(id))
+            .filter(schema::daily_track::dsl::user_id.eq(user_id))
+            .select(DailyTrack::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
+}
+
+/// Fields to change on a track; `None` keeps the current value, and
+/// `comment: Some(None)` clears the comment.
+#[derive(Default)]
+pub struct TrackChange {
+    pub topic_id: Option<i64>,
+    pub comment: Option<Option<String>>,
+    pub duration_minutes: Option<i32>,
+}
+
+/// Updates a track and bumps its version. With `if_match`, the update only
+/// happens while the track is at one of those versions.
+pub async fn update_daily_track(
+    id: i64,
+    change: TrackChange,
+    user_id: i64,
+    if_match: Option<Vec<i32>>,
+) -> Result<Option<DailyTrack>, StoreError> {
+    run("update_daily_track", move |connection| {
+        connection.transaction(|conn| {
+            let Some(existing_track) = schema::daily_track::dsl::daily_track
+                .filter(schema::daily_track::dsl::id.eq(id))
+                .filter(schema::daily_track::dsl::user_id.eq(user_id))
+                .select(DailyTrack::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            check_version(&if_match, existing_track.version)?;
+            let topic_id = change.topic_id.or(existing_track.topic_id);
+            let comment = change.comment.unwrap_or(existing_track.comment);
+            let duration_minutes = change
+                .duration_minutes
+                .unwrap_or(existing_track.duration_minutes);
+
+            if let Some(conflicting_track_id) = overlapping_track(
+                conn,
+                Some(user_id),
+                existing_track.start_time,
+                duration_minutes,
+                Some(existing_track.id),
+            )? {
+                return Err(StoreError::Overlap {
+                    conflicting_track_id,
+                });
+            }
+
+            diesel::update(schema::daily_track::dsl::daily_track.find(existing_track.id))
+                .set((
+                    schema::daily_track::dsl::topic_id.eq(topic_id),
+                    schema::daily_track::dsl::comment.eq(comment),
+                    schema::daily_track::dsl::duration_minutes.eq(duration_minutes),
+                    schema::daily_track::dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+                    schema::daily_track::dsl::version.eq(schema::daily_track::dsl::version + 1),
+                ))
+                .execute(conn)?;
+
+            Ok(schema::daily_track::dsl::daily_track
+                .find(existing_track.id)
+                .select(DailyTrack::as_select())
+                .first(conn)
+                .optional()?)
+        })
+    })
+    .await
+}
+
+/// Deletes a track. With `if_match`, only while the track is at one of those
+/// versions.
+pub async fn delete_daily_track(
+    id: i64,
+    user_id: i64,
+    if_match: Option<Vec<i32>>,
+) -> Result<bool, StoreError> {
+    run("delete_daily_track", move |connection| {
+        connection.transaction(|conn| {
+            let Some(track) = schema::daily_track::dsl::daily_track
+                .filter(schema::daily_track::dsl::id.eq(id))
+                .filter(schema::daily_track::dsl::user_id.eq(user_id))
+                .select(DailyTrack::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?
+            else {
+                return Ok(false);
+            };
+            check_version(&if_match, track.version)?;
+
+            let deleted = diesel::delete(schema::daily_track::dsl::daily_track.find(track.id))
+                .execute(conn)?;
+            Ok(deleted > 0)
+        })
+    })
+    .await
+}
+
+/// A track to be created by a bulk import.
+pub struct TrackDraft {
+    pub start_time: NaiveDateTime,
+    pub topic_id: i64,
+    pub comment: Option<String>,
+    pub duration_minutes: i32,
+}
+
+pub enum DraftOutcome {
+    /// The track was inserted. On a dry run the row was rolled back afterwards.
+    Created(DailyTrack),
+    /// The draft overlaps an existing track or an earlier draft in the batch.
+    Overlap,
+}
+
+/// Inserts `drafts` for `user_id` in one transaction, skipping any that fail
+/// the same overlap check as `create_daily_track`. Outcomes are returned in
+/// draft order. With `dry_run` the transaction is rolled back, so overlaps
+/// between drafts in the same batch are still detected.
+pub async fn import_daily_tracks(
+    user_id: i64,
+    drafts: Vec<TrackDraft>,
+    dry_run: bool,
+) -> Result<Vec<DraftOutcome>, StoreError> {
+    run("import_daily_tracks", move |connection| {
+        let mut outcomes = Vec::with_capacity(drafts.len());
+
+        let result = connection.transaction::<_, DieselError, _>(|conn| {
+            for draft in drafts {
+                if overlapping_track(
+                    conn,
+                    Some(user_id),
+                    draft.start_time,
+                    draft.duration_minutes,
+                    None,
+                )?
+                .is_some()
+                {
+                    outcomes.push(DraftOutcome::Overlap);
+                    continue;
+                }
+
+                let id = generate_snowflake_id();
+                let new_track = NewDailyTrack::new(
+                    id,
+                    draft.start_time,
+                    Some(draft.topic_id),
+                    draft.comment,
+                    Some(user_id),
+                    draft.duration_minutes,
+                );
+                diesel::insert_into(schema::daily_track::table)
+                    .values(&new_track)
+                    .execute(conn)?;
+                let track = schema::daily_track::dsl::daily_track
+                    .find(id)
+                    .select(DailyTrack::as_select())
+                    .first(conn)?;
+                outcomes.push(DraftOutcome::Created(track));
+            }
+
+            if dry_run {
+                Err(DieselError::RollbackTransaction)
+            } else {
+                Ok(())
+            }
+        });
+
+        match result {
+            Ok(()) | Err(DieselError::RollbackTransaction) => Ok(outcomes),
+            Err(e) => Err(e.into()),
+        }
+    })
+    .await
+}
+
+/// A track to be created by a CSV import, naming its topic by path.
+pub struct PathTrackDraft {
+    pub start_time: NaiveDateTime,
+    /// Topic names from the root down, e.g. `["Work", "Backend"]`.
+    pub topic_path: Vec<String>,
+    /// Display color for the leaf topic if it has to be created.
+    pub color: Option<String>,
+    pub comment: Option<String>,
+    pub duration_minutes: i32,
+}
+
+pub enum PathDraftOutcome {
+    Created,
+    /// The draft overlaps an existing track or an earlier draft in the batch.
+    Overlap,
+    /// The path cannot be resolved, e.g. a name is already used by a topic
+    /// under a different parent.
+    TopicConflict(String),
+}
+
+pub struct PathImportResult {
+    /// One outcome per draft, in draft order.
+    pub outcomes: Vec<PathDraftOutcome>,
+    pub tracks_created: u32,
+    pub topics_created: u32,
+}
+
+impl PathImportResult {
+    /// Whether every draft was created and the transaction committed.
+    pub fn committed(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|o| matches!(o, PathDraftOutcome::Created))
     }
+}
 
-    diesel::update(schema::daily_track::dsl::daily_track.find(existing_track.id))
-        .set((
-            schema::daily_track::dsl::topic_id.eq(Some(topic_id)),
-            schema::daily_track::dsl::comment.eq(comment),
-            schema::daily_track::dsl::duration_minutes.eq(duration_minutes),
-            schema::daily_track::dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
-        ))
-        .execute(&mut *connection)?;
+/// Resolves a topic path against `by_name`, creating missing topics. Topic
+/// names are unique per user, so an existing name must already sit under
+/// the expected parent.
+fn resolve_topic_path(
+    conn: &mut MysqlConnection,
+    user_id: i64,
+    path: &[String],
+    leaf_color: Option<&str>,
+    by_name: &mut std::collections::HashMap<String, (i64, Option<i64>)>,
+    topics_created: &mut u32,
+) -> Result<Result<i64, String>, DieselError> {
+    let mut parent: Option<i64> = None;
+    for (depth, name) in path.iter().enumerate() {
+        if let Some(&(id, existing_parent)) = by_name.get(name) {
+            if existing_parent != parent {
+                return Ok(Err(format!(
+                    "Topic '{}' already exists under a different parent",
+                    name
+                )));
+            }
+            parent = Some(id);
+            continue;
+        }
 
-    schema::daily_track::dsl::daily_track
-        .find(existing_track.id)
-        .select(DailyTrack::as_select())
-        .first(&mut *connection)
-        .optional()
+        let color = match leaf_color {
+            Some(color) if depth + 1 == path.len() => color,
+            _ => DEFAULT_TOPIC_DISPLAY_COLOR,
+        };
+        let id = generate_snowflake_id();
+        let new_topic = NewTopic {
+            id,
+            topic_name: name.clone(),
+            display_color: color.to_string(),
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: None,
+            parent_topic_id: parent,
+            user_id: Some(user_id),
+        };
+        diesel::insert_into(schema::topic::table)
+            .values(&new_topic)
+            .execute(conn)?;
+        by_name.insert(name.clone(), (id, parent));
+        *topics_created += 1;
+        parent = Some(id);
+    }
+    Ok(parent.ok_or_else(|| "Topic path is empty".to_string()))
 }
 
-pub fn delete_daily_track(id: i64, user_id: i64) -> Result<bool, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let Some(track) = schema::daily_track::dsl::daily_track
-        .filter(schema::daily_track::dsl::id.eq(id))
-        .filter(schema::daily_track::dsl::user_id.eq(user_id))
-        .select(DailyTrack::as_select())
-        .first(&mut *connection)
-        .optional()?
-    else {
-        return Ok(false);
-    };
+/// Creates `drafts` for `user_id` all-or-nothing: topic paths are resolved or
+/// created and every track passes the overlap check, otherwise the whole
+/// transaction is rolled back. Every draft is still checked so that all
+/// failing rows can be reported at once.
+pub async fn import_tracks_with_topic_paths(
+    user_id: i64,
+    drafts: Vec<PathTrackDraft>,
+) -> Result<PathImportResult, StoreError> {
+    run("import_tracks_with_topic_paths", move |connection| {
+        let mut result = PathImportResult {
+            outcomes: Vec::with_capacity(drafts.len()),
+            tracks_created: 0,
+            topics_created: 0,
+        };
+
+        let tx = connection.transaction::<_, DieselError, _>(|conn| {
+            let mut by_name = schema::topic::dsl::topic
+                .filter(schema::topic::dsl::user_id.eq(user_id))
+                .select(Topic::as_select())
+                .load(conn)?
+                .into_iter()
+                .map(|t| (t.topic_name, (t.id, t.parent_topic_id)))
+                .collect();
+
+            for draft in drafts {
+                let topic_id = match resolve_topic_path(
+                    conn,
+                    user_id,
+                    &draft.topic_path,
+                    draft.color.as_deref(),
+                    &mut by_name,
+                    &mut result.topics_created,
+                )? {
+                    Ok(id) => id,
+                    Err(message) => {
+                        result
+                            .outcomes
+                            .push(PathDraftOutcome::TopicConflict(message));
+                        continue;
+                    }
+                };
+
+                if overlapping_track(
+                    conn,
+                    Some(user_id),
+                    draft.start_time,
+                    draft.duration_minutes,
+                    None,
+                )?
+                .is_some()
+                {
+                    result.outcomes.push(PathDraftOutcome::Overlap);
+                    continue;
+                }
+
+                let new_track = NewDailyTrack::new(
+                    generate_snowflake_id(),
+                    draft.start_time,
+                    Some(topic_id),
+                    draft.comment,
+                    Some(user_id),
+                    draft.duration_minutes,
+                );
+                diesel::insert_into(schema::daily_track::table)
+                    .values(&new_track)
+                    .execute(conn)?;
+                result.tracks_created += 1;
+                result.outcomes.push(PathDraftOutcome::Created);
+            }
+
+            if result.committed() {
+                Ok(())
+            } else {
+                Err(DieselError::RollbackTransaction)
+            }
+        });
+
+        match tx {
+            Ok(()) => Ok(result),
+            Err(DieselError::RollbackTransaction) => {
+                result.tracks_created = 0;
+                result.topics_created = 0;
+                Ok(result)
+            }
+            Err(e) => Err(e.into()),
+        }
+    })
+    .await
+}
 
-    let deleted = diesel::delete(schema::daily_track::dsl::daily_track.find(track.id))
-        .execute(&mut *connection)?;
-    Ok(deleted > 0)
+pub async fn create_calendar_import_rule(
+    user_id: i64,
+    keyword: String,
+    topic_id: i64,
+) -> Result<CalendarImportRule, StoreError> {
+    run("create_calendar_import_rule", move |connection| {
+        let id = generate_snowflake_id();
+        let rule = NewCalendarImportRule {
+            id,
+            user_id,
+            keyword,
+            topic_id,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(schema::calendar_import_rules::table)
+            .values(&rule)
+            .execute(connection)?;
+
+        Ok(schema::calendar_import_rules::dsl::calendar_import_rules
+            .find(id)
+            .select(CalendarImportRule::as_select())
+            .first(connection)?)
+    })
+    .await
 }
 
+pub async fn list_calendar_import_rules_for_user(
+    user_id: i64,
+) -> Result<Vec<CalendarImportRule>, StoreError> {
+    run("list_calendar_import_rules_for_user", move |connection| {
+        Ok(schema::calendar_import_rules::dsl::calendar_import_rules
+            .filter(schema::calendar_import_rules::dsl::user_id.eq(user_id))
+            .order(schema::calendar_import_rules::dsl::created_at.asc())
+            .select(CalendarImportRule::as_select())
+            .load(connection)?)
+    })
+    .await
+}
+
+pub async fn delete_calendar_import_rule(id: i64, user_id: i64) -> Result<bool, StoreError> {
+    run("delete_calendar_import_rule", move |connection| {
+        let deleted = diesel::delete(
+            schema::calendar_import_rules::dsl::calendar_import_rules
+                .filter(schema::calendar_import_rules::dsl::id.eq(id))
+                .filter(schema::calendar_import_rules::dsl::user_id.eq(user_id)),
+        )
+        .execute(connection)?;
+        Ok(deleted > 0)
+    })
+    .await
+}
+
+/// A topic from a backup. `source_id` and `parent_source_id` are ids from the
+/// exported account; parents must come before their children.
+pub struct RestoreTopic {
+    pub source_id: i64,
+    pub topic_name: String,
+    pub display_color: String,
+    pub parent_source_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+pub struct RestoreTrack {
+    pub start_time: NaiveDateTime,
+    pub duration_minutes: i32,
+    pub topic_source_id: Option<i64>,
+    pub comment: Option<String>,
+}
+
+pub struct RestoreGoal {
+    pub topic_source_id: i64,
+    pub comparison: String,
+    pub target_minutes: i32,
+    pub include_subtopics: bool,
+}
+
+pub struct RestoreImportRule {
+    pub keyword: String,
+    pub topic_source_id: i64,
+}
+
+/// A validated backup. Every source id referenced by a track, goal or rule
+/// must belong to a topic in `topics`.
+pub struct AccountRestore {
+    pub topics: Vec<RestoreTopic>,
+    pub tracks: Vec<RestoreTrack>,
+    pub goals: Vec<RestoreGoal>,
+    pub import_rules: Vec<RestoreImportRule>,
+}
+
+#[derive(Default)]
+pub struct RestoreSummary {
+    pub topics_created: u32,
+    pub topics_existing: u32,
+    pub tracks_created: u32,
+    pub tracks_existing: u32,
+    pub goals_created: u32,
+    pub goals_existing: u32,
+    pub import_rules_created: u32,
+    pub import_rules_existing: u32,
+}
+
+/// Restores a backup into `user_id`'s account in one transaction, giving every
+/// entry a fresh id. Entries that already exist are reused rather than
+/// duplicated, so restoring the same backup twice changes nothing:
+/// topics match by name and parent, tracks by start, duration and topic,
+/// goals by topic and comparison, and rules by keyword and topic. A topic
+/// name used under another parent aborts the restore with `DuplicateName`, a
+/// track overlapping a different one with `Overlap`.
+pub async fn restore_account(
+    user_id: i64,
+    data: AccountRestore,
+) -> Result<RestoreSummary, StoreError> {
+    run("restore_account", move |connection| {
+        connection.transaction(|conn| restore_account_in(conn, user_id, data))
+    })
+    .await
+}
+
+fn restore_account_in(
+    conn: &mut MysqlConnection,
+    user_id: i64,
+    data: AccountRestore,
+) -> Result<RestoreSummary, StoreError> {
+    use std::collections::HashMap;
+
+    let mut summary = RestoreSummary::default();
+    let existing_topics: HashMap<String, Topic> = schema::topic::dsl::topic
+        .filter(schema::topic::dsl::user_id.eq(user_id))
+        .select(Topic::as_select())
+        .load(conn)?
+        .into_iter()
+        .map(|t| (t.topic_name.clone(), t))
+        .collect();
+
+    let mut topic_ids: HashMap<i64, i64> = HashMap::new();
+    for topic in data.topics {
+        let parent = topic.parent_source_id.map(|id| topic_ids[&id]);
+        if let Some(existing) = existing_topics.get(&topic.topic_name) {
+            if existing.parent_topic_id != parent {
+                return Err(StoreError::DuplicateName(format!(
+                    "Topic '{}' already exists under a different parent",
+                    topic.topic_name
+                )));
+            }
+            topic_ids.insert(topic.source_id, existing.id);
+            summary.topics_existing += 1;
+            continue;
+        }
+
+        let id = generate_snowflake_id();
+        let new_topic = NewTopic {
+            id,
+            topic_name: topic.topic_name,
+            display_color: topic.display_color,
+            created_at: topic.created_at,
+            updated_at: None,
+            parent_topic_id: parent,
+            user_id: Some(user_id),
+        };
+        diesel::insert_into(schema::topic::table)
+            .values(&new_topic)
+            .execute(conn)?;
+        topic_ids.insert(topic.source_id, id);
+        summary.topics_created += 1;
+    }
+
+    for track in data.tracks {
+        let topic_id = track.topic_source_id.map(|id| topic_ids[&id]);
+        let mut identical = schema::daily_track::dsl::daily_track
+            .filter(schema::daily_track::dsl::user_id.eq(user_id))
+            .filter(schema::daily_track::dsl::start_time.eq(track.start_time))
+            .filter(schema::daily_track::dsl::duration_minutes.eq(track.duration_minutes))
+            .into_boxed();
+        // `topic_id = NULL` never matches in SQL, so topicless tracks need IS NULL.
+        identical = match topic_id {
+            Some(id) => identical.filter(schema::daily_track::dsl::topic_id.eq(id)),
+            None => identical.filter(schema::daily_track::dsl::topic_id.is_null()),
+        };
+        let identical = identical
+            .select(DailyTrack::as_select())
+            .first(conn)
+            .optional()?;
+        if identical.is_some() {
+            summary.tracks_existing += 1;
+            continue;
+        }
+        if let Some(conflicting_track_id) = overlapping_track(
+            conn,
+            Some(user_id),
+            track.start_time,
+            track.duration_minutes,
+            None,
+        )? {
+            return Err(StoreError::Overlap {
+                conflicting_track_id,
+            });
+        }
+
+        let new_track = NewDailyTrack::new(
+            generate_snowflake_id(),
+            track.start_time,
+            topic_id,
+            track.comment,
+            Some(user_id),
+            track.duration_minutes,
+        );
+        diesel::insert_into(schema::daily_track::table)
+            .values(&new_track)
+            .execute(conn)?;
+        summary.tracks_created += 1;
+    }
+
+    for goal in data.goals {
+        let topic_id = topic_ids[&goal.topic_source_id];
+        if goal_exists_for_topic(conn, topic_id, &goal.comparison, None)? {
+            summary.goals_existing += 1;
+            continue;
+        }
+        let now = chrono::Utc::now().naive_utc();
+        let new_goal = NewTopicGoal {
+            id: generate_snowflake_id(),
+            user_id,
+            topic_id,
+            comparison: goal.comparison,
+            target_minutes: goal.target_minutes,
+            include_subtopics: goal.include_subtopics,
+            created_at: now,
+            updated_at: None,
+        };
+        diesel::insert_into(schema::topic_goals::table)
+            .values(&new_goal)
+            .execute(conn)?;
+        summary.goals_created += 1;
+    }
+
+    for rule in data.import_rules {
+        let topic_id = topic_ids[&rule.topic_source_id];
+        let exists = schema::calendar_import_rules::dsl::calendar_import_rules
+            .filter(schema::calendar_import_rules::dsl::user_id.eq(user_id))
+            .filter(schema::calendar_import_rules::dsl::keyword.eq(&rule.keyword))
+            .filter(schema::calendar_import_rules::dsl::topic_id.eq(topic_id))
+            .select(CalendarImportRule::as_select())
+            .first(conn)
+            .optional()?
+            .is_some();
+        if exists {
+            summary.import_rules_existing += 1;
+            continue;
+        }
+        let new_rule = NewCalendarImportRule {
+            id: generate_snowflake_id(),
+            user_id,
+            keyword: rule.keyword,
+            topic_id,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+        diesel::insert_into(schema::calendar_import_rules::table)
+            .values(&new_rule)
+            .execute(conn)?;
+        summary.import_rules_created += 1;
+    }
+
+    Ok(summary)
+}
+
+/// How long an email verification code stays valid.
+pub const VERIFICATION_CODE_TTL_MINUTES: i64 = 30;
+
 fn generate_verification_code() -> String {
     // Derive a 6-digit numeric code from a random UUID
     let bytes = Uuid::new_v4().into_bytes();
@@ -357,107 +1034,133 @@ fn generate_verification_code() -> String {
     format!("{:06}", n % 1_000_000)
 }
 
-pub fn create_user(
+/// Creates an unverified user and queues the verification email in the same
+/// transaction, so a user never exists without a pending code email.
+/// `verification_email` builds the email from the new user and code.
+pub async fn create_user(
     username: String,
     email: String,
     password_hash: String,
-) -> Result<(User, String), DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let now = chrono::Utc::now().naive_utc();
-    let id = generate_snowflake_id();
-    let code = generate_verification_code();
-    let expires_at = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::minutes(30))
-        .expect("valid expiry timestamp")
-        .naive_utc();
-
-    let new_user = NewUser {
-        id,
-        username,
-        email,
-        password_hash,
-        email_verified: false,
-        verification_code: Some(code.clone()),
-        verification_code_expires_at: Some(expires_at),
-        created_at: now,
-        updated_at: None,
-    };
-
-    diesel::insert_into(schema::users::table)
-        .values(&new_user)
-        .execute(&mut *connection)?;
-    let user = schema::users::dsl::users
-        .find(id)
-        .select(User::as_select())
-        .first(&mut *connection)?;
-
-    Ok((user, code))
-}
-
-pub fn get_user_by_username(username: &str) -> Result<Option<User>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    schema::users::dsl::users
-        .filter(schema::users::username.eq(username))
-        .select(User::as_select())
-        .first(&mut *connection)
-        .optional()
+    locale: String,
+    verification_email: impl FnOnce(&User, &str) -> OutboxDraft + Send + 'static,
+) -> Result<(User, String), StoreError> {
+    run("create_user", move |connection| {
+        let now = chrono::Utc::now().naive_utc();
+        let id = generate_snowflake_id();
+        let code = generate_verification_code();
+        let expires_at = chrono::Utc::now()
+            .checked_add_signed(chrono::Duration::minutes(VERIFICATION_CODE_TTL_MINUTES))
+            .expect("valid expiry timestamp")
+            .naive_utc();
+
+        let new_user = NewUser {
+            id,
+            username,
+            email,
+            password_hash,
+            email_verified: false,
+            locale,
+            verification_code: Some(code.clone()),
+            verification_code_expires_at: Some(expires_at),
+            created_at: now,
+            updated_at: None,
+        };
+
+        connection.transaction(|conn| {
+            diesel::insert_into(schema::users::table)
+                .values(&new_user)
+                .execute(conn)
+                .map_err(|e| match e {
+                    DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                        StoreError::DuplicateName("Username or email already exists".to_string())
+                    }
+                    e => e.into(),
+                })?;
+            let user = schema::users::dsl::users
+                .find(id)
+                .select(User::as_select())
+                .first(conn)?;
+            insert_outbox_email(conn, Some(user.id), verification_email(&user, &code))?;
+            Ok((user, code))
+        })
+    })
+    .await
 }
 
-pub fn get_user_by_email(email: &str) -> Result<Option<User>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    schema::users::dsl::users
-        .filter(schema::users::email.eq(email))
-        .select(User::as_select())
-        .first(&mut *connection)
-        .optional()
+pub async fn get_user_by_username(username: &str) -> Result<Option<User>, StoreError> {
+    let username = username.to_string();
+    run("get_user_by_username", move |connection| {
+        Ok(schema::users::dsl::users
+            .filter(schema::users::username.eq(username))
+            .select(User::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
+}
+
+pub async fn get_user_by_email(email: &str) -> Result<Option<User>, StoreError> {
+    let email = email.to_string();
+    run("get_user_by_email", move |connection| {
+        Ok(schema::users::dsl::users
+            .filter(schema::users::email.eq(email))
+            .select(User::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
 }
 
 /// Validates `code` against the stored verification code for `email`.
 /// On success, marks the user as verified and clears the code. Returns
 /// `false` when the code is wrong or expired without touching the database.
-pub fn verify_email_code(email: &str, code: &str) -> Result<bool, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let now = chrono::Utc::now().naive_utc();
-
-    let user: Option<User> = schema::users::dsl::users
-        .filter(schema::users::email.eq(email))
-        .select(User::as_select())
-        .first(&mut *connection)
-        .optional()?;
-
-    let Some(user) = user else {
-        return Ok(false);
-    };
-
-    if user.email_verified {
-        return Ok(true); // already verified — idempotent
-    }
-
-    let stored_code = match user.verification_code.as_ref() {
-        Some(c) => c,
-        None => return Ok(false),
-    };
+pub async fn verify_email_code(email: &str, code: &str) -> Result<bool, StoreError> {
+    let email = email.to_string();
+    let code = code.to_string();
+    run("verify_email_code", move |connection| {
+        let now = chrono::Utc::now().naive_utc();
+
+        let user: Option<User> = schema::users::dsl::users
+            .filter(schema::users::email.eq(email))
+            .select(User::as_select())
+            .first(connection)
+            .optional()?;
+
+        let Some(user) = user else {
+            return Ok(false);
+        };
+
+        if user.email_verified {
+            return Ok(true); // already verified — idempotent
+        }
 
-    let expires_at = match user.verification_code_expires_at {
-        Some(t) => t,
-        None => return Ok(false),
-    };
+        let stored_code = match user.verification_code.as_ref() {
+            Some(c) => c,
+            None => return Ok(false),
+        };
 
-    if stored_code != code || now > expires_at {
-        return Ok(false);
-    }
+        let expires_at = match user.verification_code_expires_at {
+            Some(t) => t,
+            None => return Ok(false),
+        };
 
-    // Mark as verified and remove the one-time code
-    diesel::update(schema::users::dsl::users.find(user.id))
-        .set((
-            schema::users::dsl::email_verified.eq(true),
-            schema::users::dsl::verification_code.eq::<Option<String>>(None),
-            schema::users::dsl::verification_code_expires_at.eq::<Option<NaiveDateTime>>(None),
-            schema::users::dsl::updated_at.eq(Some(now)),
-        ))
-        .execute(&mut *connection)?;
+        if *stored_code != code || now > expires_at {
+            return Ok(false);
+        }
 
-    Ok(true)
+        // Mark as verified and remove the one-time code
+        diesel::update(schema::users::dsl::users.find(user.id))
+            .set((
+                schema::users::dsl::email_verified.eq(true),
+                schema::users::dsl::verification_code.eq::<Option<String>>(None),
+                schema::users::dsl::verification_code_expires_at.eq::<Option<NaiveDateTime>>(None),
+                schema::users::dsl::updated_at.eq(Some(now)),
+            ))
+            .execute(connection)?;
+
+        Ok(true)
+    })
+    .await
 }
 
 pub fn hash_api_key(token: &str) -> String {
@@ -472,92 +1175,262 @@ pub fn generate_api_key_token() -> String {
     format!("{}{}", API_KEY_PREFIX, hex::encode(bytes))
 }
 
+/// First 10 characters of a token (e.g. "dt_abcdef1"). Stored with the key
+/// so users can identify it later; not enough to authenticate.
+pub fn api_key_display_prefix(token: &str) -> String {
+    token.chars().take(10).collect()
+}
+
 /// Inserts a new API key for `user_id` and returns (record, plaintext token).
 /// The plaintext token is only available at creation time.
-pub fn create_api_key(user_id: i64, name: String) -> Result<(ApiKey, String), DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let token = generate_api_key_token();
-    let key_hash = hash_api_key(&token);
-    // Show first 10 chars (e.g. "dt_abcdef1") so users can identify keys later.
-    let key_prefix: String = token.chars().take(10).collect();
-
-    let now = chrono::Utc::now().naive_utc();
-    let id = generate_snowflake_id();
-
-    let record = NewApiKey {
-        id,
-        user_id,
-        key_hash,
-        key_prefix,
-        name,
-        created_at: now,
-        last_used_at: None,
-        revoked_at: None,
-    };
-
-    diesel::insert_into(schema::api_keys::table)
-        .values(&record)
-        .execute(&mut *connection)?;
-    let record = schema::api_keys::dsl::api_keys
-        .find(id)
-        .select(ApiKey::as_select())
-        .first(&mut *connection)?;
-
-    Ok((record, token))
-}
-
-pub fn list_api_keys_for_user(user_id: i64) -> Result<Vec<ApiKey>, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    schema::api_keys::dsl::api_keys
-        .filter(schema::api_keys::dsl::user_id.eq(user_id))
-        .filter(schema::api_keys::dsl::revoked_at.is_null())
-        .select(ApiKey::as_select())
-        .load(&mut *connection)
-}
-
-/// Looks up an active API key by its plaintext token. Bumps `last_used_at` on
-/// success. Returns the owning `user_id` when valid.
-pub fn lookup_api_key(token: &str) -> Result<Option<i64>, DieselError> {
-    let key_hash = hash_api_key(token);
-    let mut connection = DB_POOL.get().unwrap();
-    let key: Option<ApiKey> = schema::api_keys::dsl::api_keys
-        .filter(schema::api_keys::dsl::key_hash.eq(&key_hash))
-        .filter(schema::api_keys::dsl::revoked_at.is_null())
-        .select(ApiKey::as_select())
-        .first(&mut *connection)
-        .optional()?;
-
-    let Some(key) = key else { return Ok(None) };
-
-    let now = chrono::Utc::now().naive_utc();
-    let _ = diesel::update(schema::api_keys::dsl::api_keys.find(key.id))
-        .set(schema::api_keys::dsl::last_used_at.eq(Some(now)))
-        .execute(&mut *connection);
-
-    Ok(Some(key.user_id))
+pub async fn create_api_key(
+    user_id: i64,
+    name: String,
+    scope: String,
+) -> Result<(ApiKey, String), StoreError> {
+    run("create_api_key", move |connection| {
+        let token = generate_api_key_token();
+        let key_hash = hash_api_key(&token);
+        let key_prefix = api_key_display_prefix(&token);
+
+        let now = chrono::Utc::now().naive_utc();
+        let id = generate_snowflake_id();
+
+        let record = NewApiKey {
+            id,
+            user_id,
+            key_hash,
+            key_prefix,
+            name,
+            scope,
+            created_at: now,
+            last_used_at: None,
+            revoked_at: None,
+        };
+
+        diesel::insert_into(schema::api_keys::table)
+            .values(&record)
+            .execute(connection)?;
+        let record = schema::api_keys::dsl::api_keys
+            .find(id)
+            .select(ApiKey::as_select())
+            .first(connection)?;
+
+        Ok((record, token))
+    })
+    .await
+}
+
+pub async fn list_api_keys_for_user(user_id: i64) -> Result<Vec<ApiKey>, StoreError> {
+    run("list_api_keys_for_user", move |connection| {
+        Ok(schema::api_keys::dsl::api_keys
+            .filter(schema::api_keys::dsl::user_id.eq(user_id))
+            .filter(schema::api_keys::dsl::revoked_at.is_null())
+            .select(ApiKey::as_select())
+            .load(connection)?)
+    })
+    .await
+}
+
+/// Looks up an active API key with the given `scope` by its plaintext token.
+/// Bumps `last_used_at` on success. Returns the owning `user_id` when valid.
+pub async fn lookup_api_key(token: &str, scope: &str) -> Result<Option<i64>, StoreError> {
+    let token = token.to_string();
+    let scope = scope.to_string();
+    run("lookup_api_key", move |connection| {
+        let key_hash = hash_api_key(&token);
+        let key: Option<ApiKey> = schema::api_keys::dsl::api_keys
+            .filter(schema::api_keys::dsl::key_hash.eq(&key_hash))
+            .filter(schema::api_keys::dsl::scope.eq(scope))
+            .filter(schema::api_keys::dsl::revoked_at.is_null())
+            .select(ApiKey::as_select())
+            .first(connection)
+            .optional()?;
+
+        let Some(key) = key else { return Ok(None) };
+
+        let now = chrono::Utc::now().naive_utc();
+        let _ = diesel::update(schema::api_keys::dsl::api_keys.find(key.id))
+            .set(schema::api_keys::dsl::last_used_at.eq(Some(now)))
+            .execute(connection);
+
+        Ok(Some(key.user_id))
+    })
+    .await
 }
 
 /// Marks the user's API key as revoked. Returns
 /// `true` when a row was updated.
-pub fn revoke_api_key(id: i64, user_id: i64) -> Result<bool, DieselError> {
-    let mut connection = DB_POOL.get().unwrap();
-    let Some(key) = schema::api_keys::dsl::api_keys
-        .filter(schema::api_keys::dsl::id.eq(id))
-        .filter(schema::api_keys::dsl::user_id.eq(user_id))
-        .filter(schema::api_keys::dsl::revoked_at.is_null())
-        .select(ApiKey::as_select())
-        .first(&mut *connection)
+pub async fn revoke_api_key(id: i64, user_id: i64) -> Result<bool, StoreError> {
+    run("revoke_api_key", move |connection| {
+        let Some(key) = schema::api_keys::dsl::api_keys
+            .filter(schema::api_keys::dsl::id.eq(id))
+            .filter(schema::api_keys::dsl::user_id.eq(user_id))
+            .filter(schema::api_keys::dsl::revoked_at.is_null())
+            .select(ApiKey::as_select())
+            .first(connection)
+            .optional()?
+        else {
+            return Ok(false);
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+        let updated = diesel::update(schema::api_keys::dsl::api_keys.find(key.id))
+            .set(schema::api_keys::dsl::revoked_at.eq(Some(now)))
+            .execute(connection)?;
+
+        Ok(updated > 0)
+    })
+    .await
+}
+
+fn goal_exists_for_topic(
+    connection: &mut MysqlConnection,
+    topic_id: i64,
+    comparison: &str,
+    exclude_id: Option<i64>,
+) -> Result<bool, DieselError> {
+    let mut query = schema::topic_goals::dsl::topic_goals
+        .filter(schema::topic_goals::dsl::topic_id.eq(topic_id))
+        .filter(schema::topic_goals::dsl::comparison.eq(comparison))
+        .into_boxed();
+    if let Some(id) = exclude_id {
+        query = query.filter(schema::topic_goals::dsl::id.ne(id));
+    }
+
+    Ok(query
+        .select(TopicGoal::as_select())
+        .first(connection)
         .optional()?
-    else {
-        return Ok(false);
-    };
+        .is_some())
+}
 
-    let now = chrono::Utc::now().naive_utc();
-    let updated = diesel::update(schema::api_keys::dsl::api_keys.find(key.id))
-        .set(schema::api_keys::dsl::revoked_at.eq(Some(now)))
-        .execute(&mut *connection)?;
+/// Inserts a goal for `topic_id`. A topic can carry at most one goal per
+/// comparison (one target and one budget); a second one is reported as
+/// `DuplicateName`.
+pub async fn create_topic_goal(
+    user_id: i64,
+    topic_id: i64,
+    comparison: String,
+    target_minutes: i32,
+    include_subtopics: bool,
+) -> Result<TopicGoal, StoreError> {
+    run("create_topic_goal", move |connection| {
+        if goal_exists_for_topic(connection, topic_id, &comparison, None)? {
+            return Err(StoreError::DuplicateName(format!(
+                "An '{}' goal already exists for this topic",
+                comparison
+            )));
+        }
+
+        let id = generate_snowflake_id();
+        let new_goal = NewTopicGoal {
+            id,
+            user_id,
+            topic_id,
+            comparison,
+            target_minutes,
+            include_subtopics,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: None,
+        };
+
+        diesel::insert_into(schema::topic_goals::table)
+            .values(&new_goal)
+            .execute(connection)?;
+
+        Ok(schema::topic_goals::dsl::topic_goals
+            .find(id)
+            .select(TopicGoal::as_select())
+            .first(connection)?)
+    })
+    .await
+}
 
-    Ok(updated > 0)
+pub async fn list_topic_goals_for_user(user_id: i64) -> Result<Vec<TopicGoal>, StoreError> {
+    run("list_topic_goals_for_user", move |connection| {
+        Ok(schema::topic_goals::dsl::topic_goals
+            .filter(schema::topic_goals::dsl::user_id.eq(user_id))
+            .select(TopicGoal::as_select())
+            .load(connection)?)
+    })
+    .await
+}
+
+pub async fn get_topic_goal_by_id_for_user(
+    id: i64,
+    user_id: i64,
+) -> Result<Option<TopicGoal>, StoreError> {
+    run("get_topic_goal_by_id_for_user", move |connection| {
+        Ok(schema::topic_goals::dsl::topic_goals
+            .filter(schema::topic_goals::dsl::id.eq(id))
+            .filter(schema::topic_goals::dsl::user_id.eq(user_id))
+            .select(TopicGoal::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
+}
+
+pub async fn update_topic_goal(
+    id: i64,
+    user_id: i64,
+    comparison: String,
+    target_minutes: i32,
+    include_subtopics: bool,
+) -> Result<Option<TopicGoal>, StoreError> {
+    run("update_topic_goal", move |connection| {
+        let Some(existing_goal) = schema::topic_goals::dsl::topic_goals
+            .filter(schema::topic_goals::dsl::id.eq(id))
+            .filter(schema::topic_goals::dsl::user_id.eq(user_id))
+            .select(TopicGoal::as_select())
+            .first(connection)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        if goal_exists_for_topic(
+            connection,
+            existing_goal.topic_id,
+            &comparison,
+            Some(existing_goal.id),
+        )? {
+            return Err(StoreError::DuplicateName(format!(
+                "An '{}' goal already exists for this topic",
+                comparison
+            )));
+        }
+
+        diesel::update(schema::topic_goals::dsl::topic_goals.find(existing_goal.id))
+            .set((
+                schema::topic_goals::dsl::comparison.eq(comparison),
+                schema::topic_goals::dsl::target_minutes.eq(target_minutes),
+                schema::topic_goals::dsl::include_subtopics.eq(include_subtopics),
+                schema::topic_goals::dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+            ))
+            .execute(connection)?;
+
+        Ok(schema::topic_goals::dsl::topic_goals
+            .find(existing_goal.id)
+            .select(TopicGoal::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
+}
+
+pub async fn delete_topic_goal(id: i64, user_id: i64) -> Result<bool, StoreError> {
+    run("delete_topic_goal", move |connection| {
+        let deleted = diesel::delete(
+            schema::topic_goals::dsl::topic_goals
+                .filter(schema::topic_goals::dsl::id.eq(id))
+                .filter(schema::topic_goals::dsl::user_id.eq(user_id)),
+        )
+        .execute(connection)?;
+        Ok(deleted > 0)
+    })
+    .await
 }
 
 #[cfg(test)]
@@ -658,4 +1531,148 @@ mod tests {
     fn api_key_prefix_constant() {
         assert_eq!(API_KEY_PREFIX, "dt_");
     }
+
+    // --- check_version tests ---
+
+    #[test]
+    fn check_version_passes_unconditional_and_matching_writes() {
+        assert!(check_version(&None, 3).is_ok());
+        assert!(check_version(&Some(vec![2, 3]), 3).is_ok());
+    }
+
+    #[test]
+    fn check_version_rejects_stale_writes() {
+        assert!(matches!(
+            check_version(&Some(vec![2]), 3),
+            Err(StoreError::VersionMismatch)
+        ));
+        assert!(matches!(
+            check_version(&Some(Vec::new()), 1),
+            Err(StoreError::VersionMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn panics_are_database_errors_not_outages() {
+        let panicked = tokio::task::spawn_blocking(|| panic!("bad row"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            join_error("get_topics", panicked),
+            StoreError::Database(_)
+        ));
+
+        let cancelled = tokio::spawn(std::future::pending::<()>());
+        cancelled.abort();
+        let cancelled = cancelled.await.unwrap_err();
+        assert!(matches!(
+            join_error("get_topics", cancelled),
+            StoreError::Unavailable(_)
+        ));
+    }
+
+    // --- tests against MySQL, skipped unless TEST_DATABASE_URL is set ---
+
+    /// A connection to the database named by `TEST_DATABASE_URL`, migrated
+    /// to the latest schema. Each test runs in a transaction that is rolled
+    /// back, so the database may hold other data.
+    fn test_connection() -> Option<MysqlConnection> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let mut connection = crate::migration_connection(&url).expect("test database reachable");
+        crate::run_pending_migrations(&mut connection).expect("migrations apply");
+        Some(connection)
+    }
+
+    fn insert_test_user(conn: &mut MysqlConnection) -> Result<i64, DieselError> {
+        let id = generate_snowflake_id();
+        diesel::insert_into(schema::users::table)
+            .values(&NewUser {
+                id,
+                username: format!("restore-test-{}", id),
+                email: format!("restore-test-{}@example.com", id),
+                password_hash: "x".to_string(),
+                email_verified: true,
+                locale: "en".to_string(),
+                verification_code: None,
+                verification_code_expires_at: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: None,
+            })
+            .execute(conn)?;
+        Ok(id)
+    }
+
+    fn backup() -> AccountRestore {
+        let created_at = NaiveDate::from_ymd_opt(2026, 1, 5)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        AccountRestore {
+            topics: vec![
+                RestoreTopic {
+                    source_id: 1,
+                    topic_name: "Work".to_string(),
+                    display_color: "#3b82f6".to_string(),
+                    parent_source_id: None,
+                    created_at,
+                },
+                RestoreTopic {
+                    source_id: 2,
+                    topic_name: "Meetings".to_string(),
+                    display_color: "#3b82f6".to_string(),
+                    parent_source_id: Some(1),
+                    created_at,
+                },
+            ],
+            tracks: vec![RestoreTrack {
+                start_time: created_at,
+                duration_minutes: 30,
+                topic_source_id: Some(2),
+                comment: None,
+            }],
+            goals: Vec::new(),
+            import_rules: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn one_backup_restores_into_two_accounts() {
+        let Some(mut connection) = test_connection() else {
+            return;
+        };
+        connection.test_transaction::<_, StoreError, _>(|conn| {
+            for _ in 0..2 {
+                let user_id = insert_test_user(conn)?;
+                let summary = restore_account_in(conn, user_id, backup())?;
+                assert_eq!((summary.topics_created, summary.tracks_created), (2, 1));
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn csv_topic_paths_are_created_per_account() {
+        let Some(mut connection) = test_connection() else {
+            return;
+        };
+        connection.test_transaction::<_, DieselError, _>(|conn| {
+            let path = ["Work".to_string(), "Meetings".to_string()];
+            for _ in 0..2 {
+                let user_id = insert_test_user(conn)?;
+                let mut by_name = std::collections::HashMap::new();
+                let mut topics_created = 0;
+                let resolved = resolve_topic_path(
+                    conn,
+                    user_id,
+                    &path,
+                    None,
+                    &mut by_name,
+                    &mut topics_created,
+                )?;
+                assert!(resolved.is_ok());
+                assert_eq!(topics_created, 2);
+            }
+            Ok(())
+        });
+    }
 }
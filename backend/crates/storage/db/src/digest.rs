@@ -0,0 +1,184 @@
+use chrono::{NaiveDate, NaiveTime};
+use db_model::models::{DigestSubscription, NewDigestSubscription, User};
+use db_model::schema::{digest_subscriptions::dsl, users};
+use diesel::prelude::*;
+use rand::RngCore;
+
+use crate::db::run;
+use crate::error::StoreError;
+use crate::outbox::{OutboxDraft, insert_outbox_email};
+
+/// The settings a user chooses for their digest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DigestSettingsChange {
+    pub enabled: bool,
+    pub frequency: String,
+    pub send_time: NaiveTime,
+    pub time_zone: String,
+    /// JSON array of topic ids; `None` includes every topic.
+    pub topic_ids: Option<String>,
+    /// Most recent period that is already over under the new settings. Its
+    /// digest is not sent, so changing settings never floods the inbox.
+    pub skip_through: Option<NaiveDate>,
+}
+
+/// The token only unlocks unsubscribing, so it is stored as is and can be
+/// put into every digest.
+fn generate_unsubscribe_token() -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub async fn get_digest_subscription(
+    user_id: i64,
+) -> Result<Option<DigestSubscription>, StoreError> {
+    run("get_digest_subscription", move |connection| {
+        Ok(dsl::digest_subscriptions
+            .find(user_id)
+            .select(DigestSubscription::as_select())
+            .first(connection)
+            .optional()?)
+    })
+    .await
+}
+
+/// Creates or replaces the user's digest settings. The unsubscribe token is
+/// kept across updates so links in earlier digests keep working.
+pub async fn save_digest_subscription(
+    user_id: i64,
+    change: DigestSettingsChange,
+) -> Result<DigestSubscription, StoreError> {
+    run("save_digest_subscription", move |connection| {
+        connection.transaction(|conn| {
+            let existing = dsl::digest_subscriptions
+                .find(user_id)
+                .select(DigestSubscription::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?;
+            match existing {
+                Some(existing) => {
+                    let last_period_start = existing.last_period_start.max(change.skip_through);
+                    diesel::update(dsl::digest_subscriptions.find(user_id))
+                        .set((
+                            dsl::enabled.eq(change.enabled),
+                            dsl::frequency.eq(change.frequency),
+                            dsl::send_time.eq(change.send_time),
+                            dsl::time_zone.eq(change.time_zone),
+                            dsl::topic_ids.eq(change.topic_ids),
+                            dsl::last_period_start.eq(last_period_start),
+                            dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+                        ))
+                        .execute(conn)?;
+                }
+                None => {
+                    diesel::insert_into(dsl::digest_subscriptions)
+                        .values(&NewDigestSubscription {
+                            user_id,
+                            enabled: change.enabled,
+                            frequency: change.frequency,
+                            send_time: change.send_time,
+                            time_zone: change.time_zone,
+                            topic_ids: change.topic_ids,
+                            unsubscribe_token: generate_unsubscribe_token(),
+                            last_period_start: change.skip_through,
+                            created_at: chrono::Utc::now().naive_utc(),
+                        })
+                        .execute(conn)?;
+                }
+            }
+            Ok(dsl::digest_subscriptions
+                .find(user_id)
+                .select(DigestSubscription::as_select())
+                .first(conn)?)
+        })
+    })
+    .await
+}
+
+/// Turns off the digest the token belongs to. Returns `false` for an unknown
+/// token; unsubscribing twice succeeds.
+pub async fn unsubscribe_digest(token: &str) -> Result<bool, StoreError> {
+    let token = token.to_string();
+    run("unsubscribe_digest", move |connection| {
+        let found = dsl::digest_subscriptions
+            .filter(dsl::unsubscribe_token.eq(token))
+            .select(dsl::user_id)
+            .first::<i64>(connection)
+            .optional()?;
+        let Some(user_id) = found else {
+            return Ok(false);
+        };
+        diesel::update(dsl::digest_subscriptions.find(user_id))
+            .set((
+                dsl::enabled.eq(false),
+                dsl::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+            ))
+            .execute(connection)?;
+        Ok(true)
+    })
+    .await
+}
+
+/// Enabled digests of users with a verified email address.
+pub async fn list_active_digest_subscriptions()
+-> Result<Vec<(DigestSubscription, User)>, StoreError> {
+    run("list_active_digest_subscriptions", move |connection| {
+        Ok(dsl::digest_subscriptions
+            .inner_join(users::table)
+            .filter(dsl::enabled.eq(true))
+            .filter(users::email_verified.eq(true))
+            .select((DigestSubscription::as_select(), User::as_select()))
+            .load(connection)?)
+    })
+    .await
+}
+
+/// Records that the digest for the period starting `period_start` is done
+/// and queues `email`, if any, in the same transaction. Returns `false`
+/// without queueing when the period was already handled, e.g. by another
+/// instance or before a restart.
+pub async fn claim_digest_period(
+    user_id: i64,
+    period_start: NaiveDate,
+    email: Option<OutboxDraft>,
+) -> Result<bool, StoreError> {
+    run("claim_digest_period", move |connection| {
+        connection.transaction(|conn| {
+            let claimed = diesel::update(
+                dsl::digest_subscriptions
+                    .filter(dsl::user_id.eq(user_id))
+                    .filter(dsl::enabled.eq(true))
+                    .filter(
+                        dsl::last_period_start
+                            .is_null()
+                            .or(dsl::last_period_start.lt(period_start)),
+                    ),
+            )
+            .set(dsl::last_period_start.eq(Some(period_start)))
+            .execute(conn)?;
+            if claimed == 0 {
+                return Ok(false);
+            }
+            if let Some(email) = email {
+                insert_outbox_email(conn, Some(user_id), email)?;
+            }
+            Ok(true)
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsubscribe_tokens_are_long_and_unique() {
+        let a = generate_unsubscribe_token();
+        assert_eq!(a.len(), 48);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, generate_unsubscribe_token());
+    }
+}
@@ -0,0 +1,132 @@
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use std::fmt;
+
+/// Why a storage call failed.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The track's time range overlaps another of the user's tracks.
+    Overlap { conflicting_track_id: i64 },
+    /// A row the call expected to read is missing.
+    NotFound,
+    /// A conditional write found the row at a version the caller did not
+    /// name: someone else changed it since the caller read it.
+    VersionMismatch,
+    /// A name that must be unique is already taken. The message is fit to
+    /// show to the client.
+    DuplicateName(String),
+    /// A unique index other than a name's rejected the write, e.g. a race
+    /// between two identical inserts. Carries the database's message.
+    Conflict(String),
+    /// A foreign key rejected the write: the referenced row is missing, or
+    /// the row is still referenced. Carries the database's message.
+    ForeignKey(String),
+    /// No pooled connection became free in time, or the database could not
+    /// be reached. Worth retrying later.
+    Unavailable(String),
+    /// Any other failure of the database or the query.
+    Database(DieselError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Overlap {
+                conflicting_track_id,
+            } => write!(f, "overlaps track {}", conflicting_track_id),
+            StoreError::NotFound => write!(f, "record not found"),
+            StoreError::VersionMismatch => write!(f, "record was changed by another request"),
+            StoreError::DuplicateName(message) => write!(f, "{}", message),
+            StoreError::Conflict(message) => write!(f, "unique violation: {}", message),
+            StoreError::ForeignKey(message) => write!(f, "foreign key violation: {}", message),
+            StoreError::Unavailable(reason) => write!(f, "database unavailable: {}", reason),
+            StoreError::Database(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// Unique indexes that hold a name the user chose. Violating one of them
+/// means the name is taken; any other unique violation is a plain conflict.
+const NAME_INDEXES: &[&str] = &["uq_topic_user_name", "username"];
+
+/// Whether MySQL's "Duplicate entry '..' for key '[table.]index'" message
+/// names one of `NAME_INDEXES`.
+fn violates_name_index(message: &str) -> bool {
+    message
+        .rsplit_once("for key '")
+        .map(|(_, key)| key.trim_end_matches('\''))
+        .map(|key| key.rsplit('.').next().unwrap_or(key))
+        .is_some_and(|index| NAME_INDEXES.contains(&index))
+}
+
+/// Classifies diesel's errors. Unique violations that reach this point come
+/// from an index rather than a check in this crate, so they get a generic
+/// message; callers that know which name clashed say so themselves.
+impl From<DieselError> for StoreError {
+    fn from(e: DieselError) -> Self {
+        match e {
+            DieselError::NotFound => StoreError::NotFound,
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info)
+                if violates_name_index(info.message()) =>
+            {
+                StoreError::DuplicateName("A record with this name already exists".to_string())
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                StoreError::Conflict(info.message().to_string())
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) => {
+                StoreError::ForeignKey(info.message().to_string())
+            }
+            e => StoreError::Database(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_diesel_errors() {
+        assert!(matches!(
+            StoreError::from(DieselError::NotFound),
+            StoreError::NotFound
+        ));
+        assert!(matches!(
+            StoreError::from(DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                Box::new("Duplicate entry 'x' for key 'users.username'".to_string()),
+            )),
+            StoreError::DuplicateName(message) if !message.contains("users.username")
+        ));
+        assert!(matches!(
+            StoreError::from(DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                Box::new("Duplicate entry '1-Work' for key 'topic.uq_topic_user_name'".to_string()),
+            )),
+            StoreError::DuplicateName(_)
+        ));
+        assert!(matches!(
+            StoreError::from(DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                Box::new(
+                    "Duplicate entry '1-above' for key 'topic_goals.uq_topic_goals_topic_comparison'"
+                        .to_string()
+                ),
+            )),
+            StoreError::Conflict(message) if message.contains("uq_topic_goals_topic_comparison")
+        ));
+        assert!(matches!(
+            StoreError::from(DieselError::DatabaseError(
+                DatabaseErrorKind::ForeignKeyViolation,
+                Box::new("Cannot add or update a child row".to_string()),
+            )),
+            StoreError::ForeignKey(message) if message == "Cannot add or update a child row"
+        ));
+        assert!(matches!(
+            StoreError::from(DieselError::RollbackTransaction),
+            StoreError::Database(DieselError::RollbackTransaction)
+        ));
+    }
+}
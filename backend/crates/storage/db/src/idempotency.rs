@@ -0,0 +1,149 @@
+use chrono::NaiveDateTime;
+use db_model::models::{IdempotencyKey, NewIdempotencyKey};
+use db_model::schema::idempotency_keys::dsl;
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+use crate::db::run;
+use crate::error::StoreError;
+
+/// The response to the first request made with an idempotency key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredResponse {
+    pub status_code: i32,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    /// `None` when the response carried a secret and was not kept; a replay
+    /// then only learns that the request succeeded.
+    pub body: Option<String>,
+}
+
+/// Outcome of presenting an idempotency key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdempotencyClaim {
+    /// The key is new to this user. The caller runs the request, then either
+    /// completes the key with the response or releases it.
+    Claimed,
+    /// The same request was made with this key before; this was its response.
+    Replay(StoredResponse),
+    /// The key was used for a different request.
+    Mismatch,
+    /// The first request with this key has not finished yet.
+    InProgress,
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+/// Claims `key` for a request whose method, path and body hash to
+/// `request_hash`. Keys older than `expires_before` are forgotten, and a
+/// claim whose request never finished is taken over once it is older than
+/// `abandoned_before`, so a crash does not block the key until it expires.
+pub async fn claim_idempotency_key(
+    user_id: i64,
+    key: String,
+    request_hash: String,
+    expires_before: NaiveDateTime,
+    abandoned_before: NaiveDateTime,
+) -> Result<IdempotencyClaim, StoreError> {
+    run("claim_idempotency_key", move |connection| {
+        connection.transaction(|conn| {
+            diesel::delete(
+                dsl::idempotency_keys
+                    .filter(dsl::user_id.eq(user_id))
+                    .filter(dsl::created_at.lt(expires_before)),
+            )
+            .execute(conn)?;
+
+            let existing = dsl::idempotency_keys
+                .find((user_id, &key))
+                .select(IdempotencyKey::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?;
+            let Some(existing) = existing else {
+                let inserted = diesel::insert_into(dsl::idempotency_keys)
+                    .values(&NewIdempotencyKey {
+                        user_id,
+                        idempotency_key: key,
+                        request_hash,
+                        created_at: now(),
+                    })
+                    .execute(conn);
+                return match inserted {
+                    Ok(_) => Ok(IdempotencyClaim::Claimed),
+                    // A concurrent request claimed the key first.
+                    Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                        Ok(IdempotencyClaim::InProgress)
+                    }
+                    Err(e) => Err(e.into()),
+                };
+            };
+
+            if existing.request_hash != request_hash {
+                return Ok(IdempotencyClaim::Mismatch);
+            }
+            match existing.status_code {
+                Some(status_code) => Ok(IdempotencyClaim::Replay(StoredResponse {
+                    status_code,
+                    content_type: existing.content_type,
+                    etag: existing.etag,
+                    body: existing.response_body,
+                })),
+                _ if existing.created_at < abandoned_before => {
+                    diesel::update(dsl::idempotency_keys.find((user_id, &key)))
+                        .set(dsl::created_at.eq(now()))
+                        .execute(conn)?;
+                    Ok(IdempotencyClaim::Claimed)
+                }
+                _ => Ok(IdempotencyClaim::InProgress),
+            }
+        })
+    })
+    .await
+}
+
+/// Stores the response of a claimed key for replays.
+pub async fn complete_idempotency_key(
+    user_id: i64,
+    key: String,
+    response: StoredResponse,
+) -> Result<(), StoreError> {
+    run("complete_idempotency_key", move |connection| {
+        diesel::update(dsl::idempotency_keys.find((user_id, &key)))
+            .set((
+                dsl::status_code.eq(Some(response.status_code)),
+                dsl::content_type.eq(response.content_type),
+                dsl::etag.eq(response.etag),
+                dsl::response_body.eq(response.body),
+            ))
+            .execute(connection)?;
+        Ok(())
+    })
+    .await
+}
+
+/// Forgets a claimed key whose response should not be replayed, so the
+/// client can retry with it.
+pub async fn release_idempotency_key(user_id: i64, key: String) -> Result<(), StoreError> {
+    run("release_idempotency_key", move |connection| {
+        diesel::delete(dsl::idempotency_keys.find((user_id, &key))).execute(connection)?;
+        Ok(())
+    })
+    .await
+}
+
+/// Forgets every user's keys older than `expires_before`. Returns how many
+/// were deleted.
+pub async fn delete_expired_idempotency_keys(
+    expires_before: NaiveDateTime,
+) -> Result<usize, StoreError> {
+    run("delete_expired_idempotency_keys", move |connection| {
+        Ok(
+            diesel::delete(dsl::idempotency_keys.filter(dsl::created_at.lt(expires_before)))
+                .execute(connection)?,
+        )
+    })
+    .await
+}
@@ -1,2 +1,14 @@
 mod db;
+mod digest;
+mod error;
+mod idempotency;
+mod migrations;
+mod outbox;
+mod telemetry;
 pub use db::*;
+pub use digest::*;
+pub use error::StoreError;
+pub use idempotency::*;
+pub use migrations::*;
+pub use outbox::*;
+pub use telemetry::trace_queries;
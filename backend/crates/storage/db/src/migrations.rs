@@ -0,0 +1,134 @@
+use diesel::migration::MigrationSource;
+use diesel::mysql::Mysql;
+use diesel::{Connection, MysqlConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+
+/// The migrations under `db_model/migrations`, compiled into the binary so a
+/// deployed server knows which schema it expects.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../../db_model/migrations");
+
+/// How the connected database compares to the embedded migrations.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MigrationStatus {
+    /// Embedded migrations already applied, oldest first.
+    pub applied: Vec<String>,
+    /// Embedded migrations not applied yet, oldest first.
+    pub pending: Vec<String>,
+    /// Applied migrations this binary does not know about. Non-empty means
+    /// the schema is newer than the binary.
+    pub unknown: Vec<String>,
+}
+
+impl MigrationStatus {
+    /// Compares embedded migration versions with the versions recorded in
+    /// the database.
+    pub fn compare(embedded: &[String], applied: &[String]) -> Self {
+        let mut status = MigrationStatus::default();
+        for version in embedded {
+            if applied.contains(version) {
+                status.applied.push(version.clone());
+            } else {
+                status.pending.push(version.clone());
+            }
+        }
+        let mut unknown: Vec<String> = applied
+            .iter()
+            .filter(|v| !embedded.contains(v))
+            .cloned()
+            .collect();
+        unknown.sort();
+        status.unknown = unknown;
+        status
+    }
+
+    pub fn schema_is_newer(&self) -> bool {
+        !self.unknown.is_empty()
+    }
+}
+
+/// Opens a standalone connection for migration work, outside `DB_POOL`.
+pub fn migration_connection(database_url: &str) -> Result<MysqlConnection, String> {
+    MysqlConnection::establish(database_url)
+        .map_err(|e| format!("failed to connect to MySQL: {}", e))
+}
+
+fn embedded_versions() -> Result<Vec<String>, String> {
+    let mut versions: Vec<String> = MigrationSource::<Mysql>::migrations(&MIGRATIONS)
+        .map_err(|e| format!("failed to read embedded migrations: {}", e))?
+        .iter()
+        .map(|m| m.name().version().to_string())
+        .collect();
+    versions.sort();
+    Ok(versions)
+}
+
+pub fn migration_status(connection: &mut MysqlConnection) -> Result<MigrationStatus, String> {
+    let applied: Vec<String> = connection
+        .applied_migrations()
+        .map_err(|e| format!("failed to read applied migrations: {}", e))?
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    Ok(MigrationStatus::compare(&embedded_versions()?, &applied))
+}
+
+/// Versions of embedded migrations that have not been applied to the
+/// connected database, oldest first.
+pub fn pending_migrations(connection: &mut MysqlConnection) -> Result<Vec<String>, String> {
+    migration_status(connection).map(|status| status.pending)
+}
+
+/// Applies every pending embedded migration and returns the versions that
+/// ran. Refuses to touch a schema that is newer than the binary.
+pub fn run_pending_migrations(connection: &mut MysqlConnection) -> Result<Vec<String>, String> {
+    let status = migration_status(connection)?;
+    if status.schema_is_newer() {
+        return Err(format!(
+            "database schema is newer than this binary (unknown migrations: {})",
+            status.unknown.join(", ")
+        ));
+    }
+    connection
+        .run_pending_migrations(MIGRATIONS)
+        .map(|ran| ran.iter().map(|v| v.to_string()).collect())
+        .map_err(|e| format!("failed to run migrations: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(list: &[&str]) -> Vec<String> {
+        list.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn embedded_versions_cover_the_migrations_directory() {
+        let embedded = embedded_versions().unwrap();
+        let on_disk = std::fs::read_dir(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../db_model/migrations"
+        ))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().is_dir())
+        .count();
+        assert_eq!(embedded.len(), on_disk);
+        assert!(embedded.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn compare_splits_applied_and_pending() {
+        let status = MigrationStatus::compare(&versions(&["1", "2", "3"]), &versions(&["1"]));
+        assert_eq!(status.applied, versions(&["1"]));
+        assert_eq!(status.pending, versions(&["2", "3"]));
+        assert!(!status.schema_is_newer());
+    }
+
+    #[test]
+    fn compare_flags_migrations_unknown_to_the_binary() {
+        let status = MigrationStatus::compare(&versions(&["1", "2"]), &versions(&["3", "1", "2"]));
+        assert!(status.pending.is_empty());
+        assert_eq!(status.unknown, versions(&["3"]));
+        assert!(status.schema_is_newer());
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::NaiveDateTime;
+use db_model::models::{EmailOutbox, NewEmailOutbox};
+use db_model::schema::email_outbox::dsl;
+use diesel::MysqlConnection;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use utils::snowflake::generate_snowflake_id;
+
+use crate::db::run;
+use crate::error::StoreError;
+
+/// Delivery state of an `email_outbox` row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutboxStatus {
+    /// Waiting for its first or next attempt.
+    Pending,
+    Sent,
+    /// Gave up: a permanent error or too many attempts.
+    Failed,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Sent => "sent",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<OutboxStatus> {
+        match value {
+            "pending" => Some(OutboxStatus::Pending),
+            "sent" => Some(OutboxStatus::Sent),
+            "failed" => Some(OutboxStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// An email to queue. The template is rendered when the email is sent, so
+/// `payload` holds the template values as JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxDraft {
+    pub recipient: String,
+    pub template: String,
+    pub locale: String,
+    pub payload: String,
+}
+
+/// Row counts per status, for operators.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutboxCounts {
+    pub pending: i64,
+    pub sent: i64,
+    pub failed: i64,
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+/// Inserts `draft` on an existing connection, so callers can queue an email
+/// in the same transaction as the change that triggers it.
+pub(crate) fn insert_outbox_email(
+    connection: &mut MysqlConnection,
+    user_id: Option<i64>,
+    draft: OutboxDraft,
+) -> Result<i64, DieselError> {
+    let now = now();
+    let id = generate_snowflake_id();
+    diesel::insert_into(dsl::email_outbox)
+        .values(&NewEmailOutbox {
+            id,
+            user_id,
+            recipient: draft.recipient,
+            template: draft.template,
+            locale: draft.locale,
+            payload: draft.payload,
+            status: OutboxStatus::Pending.as_str().to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            created_at: now,
+        })
+        .execute(connection)?;
+    Ok(id)
+}
+
+pub async fn enqueue_email(user_id: Option<i64>, draft: OutboxDraft) -> Result<i64, StoreError> {
+    run("enqueue_email", move |connection| {
+        Ok(insert_outbox_email(connection, user_id, draft)?)
+    })
+    .await
+}
+
+/// Claims up to `limit` due emails for this worker. Each claimed row counts
+/// as an attempt and is hidden from other workers for `lease`; if the
+/// process dies mid-send the row becomes due again once the lease expires.
+/// `SKIP LOCKED` lets several instances claim from the same table.
+pub async fn claim_due_emails(
+    limit: i64,
+    lease: chrono::Duration,
+) -> Result<Vec<EmailOutbox>, StoreError> {
+    run("claim_due_emails", move |connection| {
+        connection.transaction(|conn| {
+            let now = now();
+            let mut due = dsl::email_outbox
+                .filter(dsl::status.eq(OutboxStatus::Pending.as_str()))
+                .filter(dsl::next_attempt_at.le(now))
+                .order(dsl::next_attempt_at.asc())
+                .limit(limit)
+                .select(EmailOutbox::as_select())
+                .for_update()
+                .skip_locked()
+                .load(conn)?;
+            if due.is_empty() {
+                return Ok(due);
+            }
+            let ids: Vec<i64> = due.iter().map(|e| e.id).collect();
+            let leased_until = now + lease;
+            diesel::update(dsl::email_outbox.filter(dsl::id.eq_any(&ids)))
+                .set((
+                    dsl::attempts.eq(dsl::attempts + 1),
+                    dsl::next_attempt_at.eq(leased_until),
+                ))
+                .execute(conn)?;
+            for email in &mut due {
+                email.attempts += 1;
+                email.next_attempt_at = leased_until;
+            }
+            Ok(due)
+        })
+    })
+    .await
+}
+
+pub async fn mark_email_sent(id: i64) -> Result<(), StoreError> {
+    run("mark_email_sent", move |connection| {
+        diesel::update(dsl::email_outbox.find(id))
+            .set((
+                dsl::status.eq(OutboxStatus::Sent.as_str()),
+                dsl::sent_at.eq(Some(now())),
+                dsl::last_error.eq::<Option<String>>(None),
+            ))
+            .execute(connection)?;
+        Ok(())
+    })
+    .await
+}
+
+pub async fn schedule_email_retry(
+    id: i64,
+    next_attempt_at: NaiveDateTime,
+    error: &str,
+) -> Result<(), StoreError> {
+    let error = error.to_string();
+    run("schedule_email_retry", move |connection| {
+        diesel::update(dsl::email_outbox.find(id))
+            .set((
+                dsl::next_attempt_at.eq(next_attempt_at),
+                dsl::last_error.eq(Some(error)),
+            ))
+            .execute(connection)?;
+        Ok(())
+    })
+    .await
+}
+
+pub async fn mark_email_failed(id: i64, error: &str) -> Result<(), StoreError> {
+    let error = error.to_string();
+    run("mark_email_failed", move |connection| {
+        diesel::update(dsl::email_outbox.find(id))
+            .set((
+                dsl::status.eq(OutboxStatus::Failed.as_str()),
+                dsl::last_error.eq(Some(error)),
+            ))
+            .execute(connection)?;
+        Ok(())
+    })
+    .await
+}
+
+pub async fn outbox_counts() -> Result<OutboxCounts, StoreError> {
+    run("outbox_counts", move |connection| {
+        let rows: Vec<(String, i64)> = dsl::email_outbox
+            .group_by(dsl::status)
+            .select((dsl::status, diesel::dsl::count_star()))
+            .load(connection)?;
+        let mut counts = OutboxCounts::default();
+        for (status, count) in rows {
+            match OutboxStatus::parse(&status) {
+                Some(OutboxStatus::Pending) => counts.pending = count,
+                Some(OutboxStatus::Sent) => counts.sent = count,
+                Some(OutboxStatus::Failed) => counts.failed = count,
+                None => {}
+            }
+        }
+        Ok(counts)
+    })
+    .await
+}
+
+/// Pending and failed emails, newest first.
+pub async fn list_undelivered_emails(limit: i64) -> Result<Vec<EmailOutbox>, StoreError> {
+    run("list_undelivered_emails", move |connection| {
+        Ok(dsl::email_outbox
+            .filter(dsl::status.ne(OutboxStatus::Sent.as_str()))
+            .order(dsl::created_at.desc())
+            .limit(limit)
+            .select(EmailOutbox::as_select())
+            .load(connection)?)
+    })
+    .await
+}
+
+/// Puts every failed email back in the queue with a fresh attempt budget.
+/// Returns how many were requeued.
+pub async fn retry_failed_emails() -> Result<usize, StoreError> {
+    run("retry_failed_emails", move |connection| {
+        Ok(
+            diesel::update(dsl::email_outbox.filter(dsl::status.eq(OutboxStatus::Failed.as_str())))
+                .set((
+                    dsl::status.eq(OutboxStatus::Pending.as_str()),
+                    dsl::attempts.eq(0),
+                    dsl::next_attempt_at.eq(now()),
+                ))
+                .execute(connection)?,
+        )
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_round_trips() {
+        for status in [
+            OutboxStatus::Pending,
+            OutboxStatus::Sent,
+            OutboxStatus::Failed,
+        ] {
+            assert_eq!(OutboxStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(OutboxStatus::parse("queued"), None);
+    }
+}
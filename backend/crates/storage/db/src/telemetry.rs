@@ -0,0 +1,96 @@
+use diesel::connection::{Instrumentation, InstrumentationEvent, set_default_instrumentation};
+use opentelemetry::trace::{Span, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, ContextGuard, KeyValue, global};
+
+/// Instrumentation scope of the spans below.
+const TRACER_NAME: &str = "db";
+const DB_SYSTEM: &str = "mysql";
+
+/// Starts a span for one call into this crate and makes it current, so the
+/// statements it runs become its children. The span ends when the guard is
+/// dropped. Without a tracer provider this costs next to nothing.
+pub(crate) fn call_span(operation: &'static str) -> ContextGuard {
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(format!("db::{}", operation))
+        .start(&tracer);
+    Context::current_with_span(span).attach()
+}
+
+/// Records a client span per SQL statement, timed from when diesel sends it
+/// until the result is read. Installed on connections opened after it is
+/// enabled.
+#[derive(Default)]
+struct QuerySpans {
+    current: Option<global::BoxedSpan>,
+}
+
+impl Instrumentation for QuerySpans {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { query, .. } => {
+                let text = query.to_string();
+                let sql = statement(&text);
+                let operation = operation(sql).to_string();
+                let tracer = global::tracer(TRACER_NAME);
+                let span = tracer
+                    .span_builder(operation.clone())
+                    .with_kind(SpanKind::Client)
+                    .with_attributes([
+                        KeyValue::new("db.system.name", DB_SYSTEM),
+                        KeyValue::new("db.operation.name", operation),
+                        KeyValue::new("db.query.text", sql.to_string()),
+                    ])
+                    .start(&tracer);
+                self.current = Some(span);
+            }
+            InstrumentationEvent::FinishQuery { error, .. } => {
+                if let Some(mut span) = self.current.take() {
+                    if let Some(e) = error {
+                        span.set_status(Status::error(e.to_string()));
+                    }
+                    span.end();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Gives every connection opened from now on a span per SQL statement. Bind
+/// values are left out, since they include password hashes and addresses.
+pub fn trace_queries() -> Result<(), String> {
+    set_default_instrumentation(|| Some(Box::new(QuerySpans::default())))
+        .map_err(|e| format!("failed to install query tracing: {}", e))
+}
+
+/// The SQL of a rendered diesel query, without its bind values.
+fn statement(rendered: &str) -> &str {
+    rendered
+        .split_once(" -- binds:")
+        .map_or(rendered, |(sql, _)| sql)
+        .trim()
+}
+
+/// The statement's leading keyword, e.g. `SELECT`.
+fn operation(sql: &str) -> &str {
+    sql.split_whitespace().next().unwrap_or("SQL")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statement_drops_bind_values() {
+        let rendered = "SELECT `user`.`id` FROM `user` WHERE (`user`.`email` = ?) -- binds: [\"a@example.com\"]";
+        let sql = statement(rendered);
+        assert_eq!(
+            sql,
+            "SELECT `user`.`id` FROM `user` WHERE (`user`.`email` = ?)"
+        );
+        assert_eq!(operation(sql), "SELECT");
+        assert_eq!(statement("BEGIN"), "BEGIN");
+        assert_eq!(operation(""), "SQL");
+    }
+}
@@ -1,4 +1,4 @@
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use rand::RngCore;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -7,6 +7,24 @@ const SNOWFLAKE_EPOCH_MS: i64 = 1_704_067_200_000; // 2024-01-01T00:00:00Z
 const SNOWFLAKE_NODE_BITS: u16 = 10;
 const SNOWFLAKE_SEQUENCE_BITS: u16 = 12;
 const SNOWFLAKE_MAX_SEQUENCE: u16 = (1 << SNOWFLAKE_SEQUENCE_BITS) - 1;
+/// Node ids must be below this value to fit in the id's node bits.
+pub const MAX_NODE_ID_EXCLUSIVE: u16 = 1 << SNOWFLAKE_NODE_BITS;
+
+static CONFIGURED_NODE_ID: OnceCell<u16> = OnceCell::new();
+
+/// Fixes the node id used by `generate_snowflake_id`. Must be called before
+/// the first id is generated; otherwise a random node id is used.
+pub fn set_node_id(node_id: u16) -> Result<(), String> {
+    if node_id >= MAX_NODE_ID_EXCLUSIVE {
+        return Err(format!(
+            "snowflake node id must be below {}",
+            MAX_NODE_ID_EXCLUSIVE
+        ));
+    }
+    CONFIGURED_NODE_ID
+        .set(node_id)
+        .map_err(|_| "snowflake node id is already set".to_string())
+}
 
 struct SnowflakeGenerator {
     node_id: i64,
@@ -17,9 +35,9 @@ struct SnowflakeGenerator {
 impl SnowflakeGenerator {
     fn new() -> Self {
         let node_mask = (1 << SNOWFLAKE_NODE_BITS) - 1;
-        let node_id = std::env::var("SNOWFLAKE_NODE_ID")
-            .ok()
-            .and_then(|value| value.parse::<u16>().ok())
+        let node_id = CONFIGURED_NODE_ID
+            .get()
+            .copied()
             .unwrap_or_else(|| {
                 let mut bytes = [0u8; 2];
                 rand::thread_rng().fill_bytes(&mut bytes);
@@ -91,4 +109,9 @@ mod tests {
         assert!(second > 0);
         assert_ne!(first, second);
     }
+
+    #[test]
+    fn set_node_id_rejects_ids_outside_node_bits() {
+        assert!(set_node_id(MAX_NODE_ID_EXCLUSIVE).is_err());
+    }
 }
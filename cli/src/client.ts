@@ -42,16 +42,20 @@ function authToken(): string | undefined {
 
 export interface RequestOptions {
   auth?: boolean;
+  // Sent as `Idempotency-Key`, so re-running a POST with the same key returns
+  // the first response instead of creating a duplicate.
+  idempotencyKey?: string;
 }
 
 export async function request(
   method: string,
   path: string,
   body?: unknown,
-  { auth = true }: RequestOptions = {},
+  { auth = true, idempotencyKey }: RequestOptions = {},
 ): Promise<unknown> {
   const headers: Record<string, string> = {};
   if (body !== undefined) headers["Content-Type"] = "application/json";
+  if (idempotencyKey !== undefined) headers["Idempotency-Key"] = idempotencyKey;
 
   if (auth) {
     const token = authToken();
@@ -12,6 +12,12 @@ OUTPUT
   All commands print a single JSON document to stdout on success, or a single
   JSON error document to stderr (and exit non-zero) on failure.
 
+RETRIES
+  Every create command accepts --idempotency-key <key>. Re-running it with the
+  same key and flags within 24 hours prints the first result instead of
+  creating a duplicate. api-keys create never repeats its token; a retry after
+  success fails with IDEMPOTENCY_KEY_COMPLETED.
+
 TOPICS
   topics list [--parent <id>]
   topics get <id>
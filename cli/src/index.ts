@@ -6,7 +6,7 @@
 // LLM agents and scripts.
 
 import { parseArgs, type FlagValue } from "./args.js";
-import { ApiError, request } from "./client.js";
+import { ApiError, request, type RequestOptions } from "./client.js";
 import { HELP } from "./help.js";
 
 type Flags = Record<string, FlagValue>;
@@ -139,6 +139,11 @@ function strFlag(
   return v;
 }
 
+// Options for create commands, which accept `--idempotency-key`.
+function createOptions(flags: Flags): RequestOptions {
+  return { idempotencyKey: strFlag(flags, "idempotency-key") };
+}
+
 function positionalId(rest: string[], idx: number, label: string): string {
   const raw = rest[idx];
   if (raw === undefined) {
@@ -177,7 +182,7 @@ async function topics(
       if (parent !== undefined) body.parent_topic_id = parent;
       const color = strFlag(flags, "color");
       if (color) body.display_color = color;
-      emit(await request("POST", "/topics", body));
+      emit(await request("POST", "/topics", body, createOptions(flags)));
       return;
     }
     case "update": {
@@ -234,7 +239,7 @@ async function tracks(
       };
       const comment = strFlag(flags, "comment");
       if (comment !== undefined) body.comment = comment;
-      emit(await request("POST", "/daily-tracks", body));
+      emit(await request("POST", "/daily-tracks", body, createOptions(flags)));
       return;
     }
     case "update": {
@@ -279,7 +284,7 @@ async function apiKeys(
       return;
     case "create": {
       const body = { name: requireFlag(flags, "name") };
-      emit(await request("POST", "/api-keys", body));
+      emit(await request("POST", "/api-keys", body, createOptions(flags)));
       return;
     }
     case "revoke": {
@@ -184,12 +184,6 @@ if (( NODE_CURRENT_MAJOR < NODE_MIN_MAJOR )); then
     apt-get install -y nodejs
 fi
 
-# Install Diesel CLI if not present
-if ! command -v diesel &>/dev/null; then
-    echo "==> Installing Diesel CLI"
-    cargo install diesel_cli --no-default-features --features mysql
-fi
-
 # ---------- Service user ----------
 if ! id "$SERVICE_USER" &>/dev/null; then
     echo "==> Creating service user: $SERVICE_USER"
@@ -206,6 +200,7 @@ DATABASE_URL=$DATABASE_URL
 JWT_SECRET=$JWT_SECRET
 BIND_ADDR=127.0.0.1:8080
 CORS_ORIGIN=https://$FRONTEND_DOMAIN
+PUBLIC_URL=https://$BACKEND_DOMAIN
 EOF
 chown root:root "$BACKEND_ENV"
 chmod 600 "$BACKEND_ENV"
@@ -228,11 +223,12 @@ cd "$SOURCE_REPO_DIR/backend"
 cargo build --release
 
 # ---------- Database migrations ----------
-# diesel.toml lives in crates/db_model and points at ./migrations there.
-# Migrations run from the source tree and are not copied to the server.
+# Migrations are embedded in the binary that was just built, so the schema
+# always matches the code being installed. This fails if the database is
+# already on a newer schema than this build.
 echo "==> Running database migrations"
-cd "$SOURCE_REPO_DIR/backend/crates/db_model"
-DATABASE_URL="$DATABASE_URL" diesel migration run
+DATABASE_URL="$DATABASE_URL" \
+    "$SOURCE_REPO_DIR/backend/target/release/daily_tracker_backend" migrate
 
 # ---------- Build frontend ----------
 # Vite bakes VITE_API_BASE_URL into the bundle at build time. When frontend
@@ -270,6 +266,13 @@ rsync -a --delete --chmod=Du=rwx,Dgo=rx,Fu=rw,Fgo=r \
     "$SOURCE_REPO_DIR/frontend/dist/" "$INSTALL_DIR/frontend/"
 chown -R root:root "$INSTALL_DIR/frontend"
 
+echo "==> Checking backend configuration"
+(
+    set -a
+    . "$BACKEND_ENV"
+    "$INSTALL_DIR/bin/daily-tracker-backend" config check >/dev/null
+)
+
 # Make the path traversable for nginx (www-data) and the service user.
 chmod 755 "$INSTALL_DIR" "$INSTALL_DIR/bin" "$INSTALL_DIR/frontend"
 
@@ -321,6 +324,12 @@ write_api_proxy_block() {
         proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;
         proxy_set_header X-Forwarded-Proto $scheme;
     }
+
+    # Liveness only; /readyz stays on 127.0.0.1 for local checks.
+    location = /healthz {
+        proxy_pass http://127.0.0.1:8080;
+        access_log off;
+    }
 EOF
 }
 
@@ -423,6 +432,19 @@ fi
 # ---------- (Re)start backend ----------
 systemctl restart daily-tracker-backend
 
+echo "==> Waiting for backend readiness (/readyz)"
+READY=n
+for _ in $(seq 1 30); do
+    if curl -fsS http://127.0.0.1:8080/readyz >/dev/null 2>&1; then
+        READY=y
+        break
+    fi
+    sleep 1
+done
+if [[ "$READY" != "y" ]]; then
+    echo "WARNING: backend is not ready yet. Check: curl -s http://127.0.0.1:8080/readyz" >&2
+fi
+
 cat <<EOF
 
 ==> Setup complete!
@@ -434,10 +456,12 @@ cat <<EOF
     Deploy config:  $SYSTEM_CONF
     Backend logs:   /var/log/daily-tracker/backend-YYYY-MM-DD.log (UTC, daily)
                     /var/log/daily-tracker/current.log -> latest
+                    kept 30 days; see [logging] in backend/config.example.toml
     Frontend URL:   $( [[ "$ENABLE_HTTPS" == "y" ]] && echo "https" || echo "http" )://$FRONTEND_DOMAIN
     Backend URL:    $( [[ "$ENABLE_HTTPS" == "y" ]] && echo "https" || echo "http" )://$BACKEND_DOMAIN/api/v1
 
     Backend status: systemctl status daily-tracker-backend
+    Readiness:      curl -s http://127.0.0.1:8080/readyz
     Live logs:      tail -F /var/log/daily-tracker/current.log
     Journald:       journalctl -u daily-tracker-backend -f
 
@@ -453,6 +477,7 @@ for p in \
     "$INSTALL_DIR/Makefile" \
     "$INSTALL_DIR/.git" \
     "$INSTALL_DIR/swagger.json" \
+    "$INSTALL_DIR/openapi.json" \
     "$INSTALL_DIR/README.md"
 do
     [[ -e "$p" ]] && LEGACY_PATHS+=("$p")